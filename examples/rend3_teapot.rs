@@ -1,89 +1,39 @@
 use std::fs;
 
 use glam::{UVec2, Vec3};
-use hxa::{Hxa, LayerData, NodeContent, NodeType};
+use hxa::{Hxa, NodeContent, NodeType};
 
 fn create_mesh() -> rend3::types::Mesh {
     let data = fs::read("examples/teapot.hxa").unwrap();
     let hxa = Hxa::new(&data).unwrap();
+    let diagnostics = hxa.validate();
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    assert!(diagnostics.is_empty(), "teapot.hxa is malformed");
     let node = &hxa.nodes[0];
     assert_eq!(node.type_, NodeType::Geometry);
     let content = node.content.as_ref().unwrap();
     if let NodeContent::Geometry(geometry) = content {
         let vertex_layer = &geometry.vertex_stack.layers[hxa::HC_BASE_VERTEX_LAYER_ID];
-        assert_eq!(vertex_layer.name, hxa::HC_BASE_VERTEX_LAYER_NAME);
-        assert_eq!(
-            vertex_layer.component_count,
-            hxa::HC_BASE_VERTEX_LAYER_COMPONENTS
-        );
-
-        let index_layer = &geometry.corner_stack.layers[hxa::HC_BASE_CORNER_LAYER_ID];
-        assert_eq!(index_layer.name, hxa::HC_BASE_CORNER_LAYER_NAME);
-        assert_eq!(
-            index_layer.component_count,
-            hxa::HC_BASE_CORNER_LAYER_COMPONENTS
-        );
-
-        if let LayerData::Double(vertices) = &vertex_layer.data {
+
+        if let Some(vertices) = vertex_layer.to_vec3_f32() {
             let mb = rend3::types::MeshBuilder::new(
                 vertices
-                    .chunks_exact(3)
-                    .map(|vertex| {
-                        Vec3::new(
-                            vertex[0].round() as f32,
-                            vertex[1].round() as f32,
-                            vertex[2].round() as f32,
-                        )
-                    })
+                    .into_iter()
+                    .map(|[x, y, z]| Vec3::new(x.round(), y.round(), z.round()))
                     .collect(),
             );
-            if let LayerData::Int32(indices) = &index_layer.data {
-                let mut ngon_start = 0;
-                let mut cursor = 0;
-                let mut triangulated = Vec::new();
-                loop {
-                    let idx = indices[cursor];
-                    if idx.is_negative() {
-                        let vertex_count = cursor - ngon_start + 1;
-                        if vertex_count < 3 {
-                            panic!(
-                                "A polygon must have 3 or more vertices. This one has {}",
-                                vertex_count
-                            )
-                        } else if vertex_count == 3 {
-                            triangulated.extend([
-                                -indices[ngon_start + 2] as u32 - 1,
-                                indices[ngon_start + 1] as u32,
-                                indices[ngon_start + 0] as u32,
-                            ]);
-                        } else if vertex_count == 4 {
-                            triangulated.extend([
-                                indices[ngon_start + 2] as u32,
-                                indices[ngon_start + 1] as u32,
-                                indices[ngon_start + 0] as u32,
-                                -indices[ngon_start + 3] as u32 - 1,
-                                indices[ngon_start + 2] as u32,
-                                indices[ngon_start + 0] as u32,
-                            ])
-                        } else {
-                            panic!(
-                                "Cannot handle polygons with more than 4 vertices. This one has {}",
-                                vertex_count
-                            );
-                        }
-                        ngon_start = cursor + 1;
-                    }
-
-                    cursor += 1;
-                    if cursor == indices.len() {
-                        assert_eq!(cursor - ngon_start, 0);
-                        break;
-                    }
-                    if cursor - ngon_start > 4 {}
-                }
-
-                return mb.with_indices(triangulated).build();
-            }
+            // rend3 expects the opposite winding order
+            let triangulated = geometry
+                .triangulate()
+                .unwrap()
+                .triangles
+                .into_iter()
+                .flat_map(|[a, b, c]| [c, b, a])
+                .collect();
+
+            return mb.with_indices(triangulated).build();
         }
     }
     unreachable!()
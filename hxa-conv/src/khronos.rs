@@ -0,0 +1,1089 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use gltf::binary::{Glb, Header};
+use gltf::buffer::Source;
+use gltf::json::{self, validation::Checked, Index};
+use gltf::mesh::Mode;
+use gltf::Gltf;
+use hxa::{
+    GeometryBuilder, Hxa, HxaError, HxaVersion, Layer, LayerData, MetaBuilder, Node, NodeContent,
+    NodeGeometry, NodeType, SceneGraph, Transform, SC_LAYER_ADD_BLENDSHAPE, SC_LAYER_BLENDSHAPE,
+    SC_LAYER_COLOR, SC_LAYER_MATERIAL_ID, SC_LAYER_NAME_UV0, SC_LAYER_NORMALS,
+    SC_LAYER_SKIN_REFERENCE, SC_LAYER_SKIN_WEIGHT, SC_LAYER_TANGENT, SC_NAME,
+};
+
+use crate::wavefront::MATERIAL_NAMES;
+use crate::Result;
+
+/// Metadata of the meta nodes made from glTF nodes, referencing the geometry
+/// node of the glTF node's mesh.
+const MESH: &str = "mesh";
+/// Metadata of the meta nodes made from glTF nodes, referencing the meta
+/// nodes made from the glTF node's children.
+const CHILDREN: &str = "children";
+
+/// Writes `hxa` as glTF, either as JSON with the binary data embedded in it,
+/// or as a `.glb` file.
+///
+/// Every geometry node becomes a triangulated mesh. Corner layers are turned
+/// into vertex attributes by splitting vertices wherever their corners
+/// disagree, and faces with different materials end up in different
+/// primitives. The node hierarchy formed by node references is kept, with
+/// nodes that are reached along several paths duplicated for each of them.
+///
+/// Skin references are written as joint indices as they are, since HxA has
+/// no notion of the skeleton they refer to.
+pub fn export(hxa: &Hxa<'_>, binary: bool, out: &mut impl Write) -> Result<()> {
+    let mut exporter = Exporter::default();
+    let graph = hxa.scene_graph()?;
+    let count = graph.instance_count();
+    if count > SceneGraph::MAX_INSTANCES {
+        let limit = SceneGraph::MAX_INSTANCES;
+        return Err(HxaError::TooManyInstances { count, limit }.into());
+    }
+    let mut scene_nodes = Vec::new();
+    for &root in graph.roots() {
+        // The glTF node made for each node on the path to the current one.
+        let mut path: Vec<(usize, Index<json::Node>)> = Vec::new();
+        for visit in graph.descendants(root) {
+            path.truncate(visit.depth);
+            let transform = graph.local_transform(visit.node);
+            if let Some(&(parent, node)) = path.last() {
+                // Undo what the importer does to glTF nodes with a mesh.
+                let folds = transform == Transform::IDENTITY
+                    && exporter.root.nodes[node.value()].mesh.is_none()
+                    && hxa.nodes[parent]
+                        .meta_node_refs(MESH)
+                        .is_some_and(|meshes| meshes.contains(&(visit.node as u32)));
+                if folds {
+                    if let Some(mesh) = exporter.mesh(hxa, visit.node)? {
+                        exporter.root.nodes[node.value()].mesh = Some(mesh);
+                        path.push((visit.node, node));
+                        continue;
+                    }
+                }
+            }
+            let node = exporter.node(hxa, visit.node, transform)?;
+            match path.last() {
+                Some((_, parent)) => exporter.root.nodes[parent.value()]
+                    .children
+                    .get_or_insert_with(Vec::new)
+                    .push(node),
+                None => scene_nodes.push(node),
+            }
+            path.push((visit.node, node));
+        }
+    }
+    exporter.root.scenes.push(json::Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        nodes: scene_nodes,
+    });
+    exporter.root.scene = Some(Index::new(0));
+    exporter.root.asset.generator = Some(String::from("hxa-conv"));
+    exporter.finish(binary, out)
+}
+
+#[derive(Default)]
+struct Exporter {
+    root: json::Root,
+    buffer: Vec<u8>,
+    /// The glTF mesh made for each geometry node so far, if it had any
+    /// triangles.
+    meshes: HashMap<usize, Option<Index<json::Mesh>>>,
+    materials: HashMap<String, Index<json::Material>>,
+}
+
+impl Exporter {
+    fn node(
+        &mut self,
+        hxa: &Hxa<'_>,
+        index: usize,
+        transform: Transform,
+    ) -> Result<Index<json::Node>> {
+        let node = &hxa.nodes[index];
+        let mesh = self.mesh(hxa, index)?;
+        let matrix = if transform == Transform::IDENTITY {
+            None
+        } else {
+            let mut matrix = [0.0; 16];
+            for (to, from) in matrix.iter_mut().zip(&transform.to_cols_array()) {
+                *to = *from as f32;
+            }
+            Some(matrix)
+        };
+        Ok(self.root.push(json::Node {
+            mesh,
+            matrix,
+            name: node.meta_text(SC_NAME).map(String::from),
+            ..Default::default()
+        }))
+    }
+
+    /// The glTF mesh for a geometry node, which is only written once however
+    /// many times the node is placed in the scene.
+    fn mesh(&mut self, hxa: &Hxa<'_>, index: usize) -> Result<Option<Index<json::Mesh>>> {
+        let node = &hxa.nodes[index];
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => {
+                if let Some(&mesh) = self.meshes.get(&index) {
+                    return Ok(mesh);
+                }
+                let mesh = self.write_mesh(node, geometry)?;
+                if mesh.is_none() {
+                    println!("Skipping geometry node {}, which has no polygons", index);
+                }
+                self.meshes.insert(index, mesh);
+                Ok(mesh)
+            }
+            Some(NodeContent::Image(_)) => {
+                println!("Skipping the image of node {}", index);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write_mesh(
+        &mut self,
+        node: &Node<'_>,
+        geometry: &NodeGeometry<'_>,
+    ) -> Result<Option<Index<json::Mesh>>> {
+        // glTF requires every mesh to have a primitive and every accessor to
+        // hold at least one element.
+        let triangulation = geometry.triangulate()?;
+        if triangulation.triangles.is_empty() {
+            return Ok(None);
+        }
+        let attributes = Attributes::new(geometry);
+
+        // Corners that agree on every attribute can share a glTF vertex.
+        let mut vertices = HashMap::new();
+        let mut corners = Vec::new();
+        let mut indices = Vec::with_capacity(triangulation.corners.len() * 3);
+        for (triangle, corner_triangle) in
+            triangulation.triangles.iter().zip(&triangulation.corners)
+        {
+            for (&vertex, &corner) in triangle.iter().zip(corner_triangle) {
+                let key = attributes.key(vertex as usize, corner as usize);
+                let next = corners.len() as u32;
+                let index = *vertices.entry(key).or_insert(next);
+                if index == next {
+                    corners.push((vertex as usize, corner as usize));
+                }
+                indices.push(index);
+            }
+        }
+
+        let positions = geometry.vertex_stack.layers[hxa::HC_BASE_VERTEX_LAYER_ID]
+            .to_elements::<f32, 3>()
+            .unwrap_or_default();
+        let mut primitive_attributes = std::collections::BTreeMap::new();
+        let (min, max) = bounds(corners.iter().map(|&(vertex, _)| positions[vertex]));
+        let position = self.accessor(
+            corners.iter().flat_map(|&(vertex, _)| positions[vertex]),
+            3,
+            Bounds::MinMax(min, max),
+        );
+        primitive_attributes.insert(Checked::Valid(json::mesh::Semantic::Positions), position);
+        for attribute in &attributes.attributes {
+            let values = corners
+                .iter()
+                .flat_map(|&(vertex, corner)| attribute.get(vertex, corner).iter().copied());
+            let accessor = match attribute.semantic {
+                json::mesh::Semantic::Joints(_) => {
+                    self.joints_accessor(values.map(|joint| joint as u16).collect())
+                }
+                _ => self.accessor(values, attribute.components, Bounds::None),
+            };
+            primitive_attributes.insert(Checked::Valid(attribute.semantic.clone()), accessor);
+        }
+
+        let mut targets = Vec::new();
+        for target in &attributes.targets {
+            let offsets: Vec<[f32; 3]> = corners
+                .iter()
+                .map(|&(vertex, _)| target.offset(vertex, positions[vertex]))
+                .collect();
+            let (min, max) = bounds(offsets.iter().copied());
+            targets.push(json::mesh::MorphTarget {
+                positions: Some(self.accessor(
+                    offsets.into_iter().flatten(),
+                    3,
+                    Bounds::MinMax(min, max),
+                )),
+                normals: None,
+                tangents: None,
+            });
+        }
+
+        // Split the triangles into one primitive per material.
+        let materials = geometry
+            .face_stack
+            .get(SC_LAYER_MATERIAL_ID)
+            .filter(|layer| layer.component_count == 1)
+            .and_then(|layer| layer.to_elements::<i32, 1>());
+        let mut by_material: Vec<(Option<i32>, Vec<u32>)> = Vec::new();
+        for (triangle, polygon) in indices.chunks_exact(3).zip(&triangulation.polygons) {
+            let material = materials
+                .as_ref()
+                .and_then(|materials| materials.get(*polygon as usize))
+                .map(|[id]| *id)
+                .filter(|&id| id >= 0);
+            match by_material.iter_mut().find(|(id, _)| *id == material) {
+                Some((_, indices)) => indices.extend_from_slice(triangle),
+                None => by_material.push((material, triangle.to_vec())),
+            }
+        }
+
+        let mut primitives = Vec::new();
+        for (material, indices) in by_material {
+            let material = material.map(|id| self.material(node, id));
+            let indices = self.indices_accessor(&indices);
+            primitives.push(json::mesh::Primitive {
+                attributes: primitive_attributes.clone(),
+                extensions: None,
+                extras: Default::default(),
+                indices: Some(indices),
+                material,
+                mode: Checked::Valid(Mode::Triangles),
+                targets: if targets.is_empty() {
+                    None
+                } else {
+                    Some(targets.clone())
+                },
+            });
+        }
+
+        Ok(Some(self.root.push(json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: node.meta_text(SC_NAME).map(String::from),
+            primitives,
+            weights: None,
+        })))
+    }
+
+    fn material(&mut self, node: &Node<'_>, id: i32) -> Index<json::Material> {
+        let name = match node.meta_text(&format!("{}/{}", MATERIAL_NAMES, id)) {
+            Some(name) => name.to_string(),
+            None => format!("material{}", id),
+        };
+        if let Some(&material) = self.materials.get(&name) {
+            return material;
+        }
+        let material = self.root.push(json::Material {
+            name: Some(name.clone()),
+            ..Default::default()
+        });
+        self.materials.insert(name, material);
+        material
+    }
+
+    /// Appends `data` to the buffer in a view of its own.
+    fn view(&mut self, data: &[u8], target: json::buffer::Target) -> Index<json::buffer::View> {
+        align(&mut self.buffer);
+        let offset = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+        self.root.push(json::buffer::View {
+            buffer: Index::new(0),
+            byte_length: data.len().into(),
+            byte_offset: Some(offset.into()),
+            byte_stride: None,
+            name: None,
+            target: Some(Checked::Valid(target)),
+            extensions: None,
+            extras: Default::default(),
+        })
+    }
+
+    fn accessor(
+        &mut self,
+        values: impl Iterator<Item = f32>,
+        components: usize,
+        bounds: Bounds,
+    ) -> Index<json::Accessor> {
+        let bytes: Vec<u8> = values.flat_map(f32::to_le_bytes).collect();
+        let count = bytes.len() / 4 / components;
+        let (min, max) = match bounds {
+            Bounds::MinMax(min, max) => (Some(min.to_vec().into()), Some(max.to_vec().into())),
+            Bounds::None => (None, None),
+        };
+        let view = self.view(&bytes, json::buffer::Target::ArrayBuffer);
+        self.push_accessor(
+            view,
+            count,
+            json::accessor::ComponentType::F32,
+            components,
+            min,
+            max,
+        )
+    }
+
+    fn joints_accessor(&mut self, joints: Vec<u16>) -> Index<json::Accessor> {
+        let bytes: Vec<u8> = joints
+            .iter()
+            .flat_map(|joint| joint.to_le_bytes())
+            .collect();
+        let view = self.view(&bytes, json::buffer::Target::ArrayBuffer);
+        self.push_accessor(
+            view,
+            joints.len() / 4,
+            json::accessor::ComponentType::U16,
+            4,
+            None,
+            None,
+        )
+    }
+
+    fn indices_accessor(&mut self, indices: &[u32]) -> Index<json::Accessor> {
+        let bytes: Vec<u8> = indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect();
+        let view = self.view(&bytes, json::buffer::Target::ElementArrayBuffer);
+        self.push_accessor(
+            view,
+            indices.len(),
+            json::accessor::ComponentType::U32,
+            1,
+            None,
+            None,
+        )
+    }
+
+    fn push_accessor(
+        &mut self,
+        view: Index<json::buffer::View>,
+        count: usize,
+        component_type: json::accessor::ComponentType,
+        components: usize,
+        min: Option<json::Value>,
+        max: Option<json::Value>,
+    ) -> Index<json::Accessor> {
+        let type_ = match components {
+            1 => json::accessor::Type::Scalar,
+            2 => json::accessor::Type::Vec2,
+            3 => json::accessor::Type::Vec3,
+            _ => json::accessor::Type::Vec4,
+        };
+        self.root.push(json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: count.into(),
+            component_type: Checked::Valid(json::accessor::GenericComponentType(component_type)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Checked::Valid(type_),
+            min,
+            max,
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+
+    fn finish(mut self, binary: bool, out: &mut impl Write) -> Result<()> {
+        align(&mut self.buffer);
+        let uri = if binary || self.buffer.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "data:application/octet-stream;base64,{}",
+                base64::encode(&self.buffer)
+            ))
+        };
+        if !self.buffer.is_empty() {
+            self.root.push(json::Buffer {
+                byte_length: self.buffer.len().into(),
+                name: None,
+                uri,
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+
+        if binary {
+            let json = self.root.to_vec()?;
+            let glb = Glb {
+                // `Glb::to_writer` fills in the header itself.
+                header: Header {
+                    magic: *b"glTF",
+                    version: 2,
+                    length: 0,
+                },
+                json: Cow::Owned(json),
+                bin: if self.buffer.is_empty() {
+                    None
+                } else {
+                    Some(Cow::Owned(self.buffer))
+                },
+            };
+            glb.to_writer(out)?;
+        } else {
+            out.write_all(self.root.to_string_pretty()?.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Pads the buffer to a multiple of four bytes, which is the alignment glTF
+/// asks of buffer views holding four byte components.
+fn align(buffer: &mut Vec<u8>) {
+    buffer.resize(buffer.len().div_ceil(4) * 4, 0);
+}
+
+enum Bounds {
+    None,
+    MinMax([f32; 3], [f32; 3]),
+}
+
+fn bounds(values: impl Iterator<Item = [f32; 3]>) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    let mut any = false;
+    for value in values {
+        any = true;
+        for axis in 0..3 {
+            min[axis] = min[axis].min(value[axis]);
+            max[axis] = max[axis].max(value[axis]);
+        }
+    }
+    if any {
+        (min, max)
+    } else {
+        ([0.0; 3], [0.0; 3])
+    }
+}
+
+/// The layers of a geometry node that are written as glTF attributes.
+struct Attributes {
+    attributes: Vec<Attribute>,
+    targets: Vec<Target>,
+}
+
+struct Attribute {
+    semantic: json::mesh::Semantic,
+    /// The number of components written per vertex.
+    components: usize,
+    /// Whether the layer is in the vertex stack rather than the corner stack.
+    per_vertex: bool,
+    values: Vec<f32>,
+}
+
+impl Attribute {
+    fn get(&self, vertex: usize, corner: usize) -> &[f32] {
+        let element = if self.per_vertex { vertex } else { corner };
+        &self.values[element * self.components..(element + 1) * self.components]
+    }
+}
+
+/// A `blendshape` or `addblendshape` vertex layer, holding absolute positions
+/// or offsets respectively.
+struct Target {
+    relative: bool,
+    values: Vec<[f32; 3]>,
+}
+
+impl Target {
+    fn offset(&self, vertex: usize, position: [f32; 3]) -> [f32; 3] {
+        let value = self.values[vertex];
+        if self.relative {
+            value
+        } else {
+            [
+                value[0] - position[0],
+                value[1] - position[1],
+                value[2] - position[2],
+            ]
+        }
+    }
+}
+
+impl Attributes {
+    fn new(geometry: &NodeGeometry<'_>) -> Self {
+        use json::mesh::Semantic;
+
+        let mut attributes = Vec::new();
+        let conventions = [
+            (SC_LAYER_NORMALS, Semantic::Normals, 3..=3, 3),
+            (SC_LAYER_TANGENT, Semantic::Tangents, 3..=4, 4),
+            (SC_LAYER_NAME_UV0, Semantic::TexCoords(0), 2..=2, 2),
+            (SC_LAYER_COLOR, Semantic::Colors(0), 3..=4, 0),
+            (SC_LAYER_SKIN_REFERENCE, Semantic::Joints(0), 1..=4, 4),
+            (SC_LAYER_SKIN_WEIGHT, Semantic::Weights(0), 1..=4, 4),
+        ];
+        for (name, semantic, accepted, components) in conventions.iter().cloned() {
+            let corner = geometry.corner_stack.get(name).map(|layer| (false, layer));
+            let vertex = geometry.vertex_stack.get(name).map(|layer| (true, layer));
+            let (per_vertex, layer) = match corner.or(vertex) {
+                Some(found) => found,
+                None => continue,
+            };
+            let found = layer.component_count as usize;
+            if !accepted.contains(&found) {
+                println!(
+                    r#"Skipping the "{}" layer, which has {} components"#,
+                    name, found
+                );
+                continue;
+            }
+            // Colors keep their alpha channel if they have one.
+            let components = if components == 0 { found } else { components };
+            // Tangents without a handedness are assumed to be right-handed.
+            let padding = if semantic == Semantic::Tangents {
+                1.0
+            } else {
+                0.0
+            };
+            let normalize = semantic == Semantic::Colors(0);
+            let values = layer_f32(layer, normalize)
+                .chunks_exact(found)
+                .flat_map(|element| {
+                    let mut padded = vec![padding; components];
+                    padded[..found].copy_from_slice(element);
+                    padded
+                })
+                .collect();
+            attributes.push(Attribute {
+                semantic,
+                components,
+                per_vertex,
+                values,
+            });
+        }
+
+        let targets = geometry
+            .vertex_stack
+            .attributes()
+            .filter(|layer| layer.component_count == 3)
+            .filter_map(|layer| {
+                let relative = if layer.name.starts_with(SC_LAYER_ADD_BLENDSHAPE) {
+                    true
+                } else if layer.name.starts_with(SC_LAYER_BLENDSHAPE) {
+                    false
+                } else {
+                    return None;
+                };
+                Some(Target {
+                    relative,
+                    values: layer.to_elements::<f32, 3>()?,
+                })
+            })
+            .collect();
+
+        Self {
+            attributes,
+            targets,
+        }
+    }
+
+    /// Identifies the values a corner would need its glTF vertex to have.
+    fn key(&self, vertex: usize, corner: usize) -> (usize, Vec<u32>) {
+        let values = self
+            .attributes
+            .iter()
+            .filter(|attribute| !attribute.per_vertex)
+            .flat_map(|attribute| attribute.get(vertex, corner))
+            .map(|value| value.to_bits())
+            .collect();
+        (vertex, values)
+    }
+}
+
+/// Converts the components of a layer to `f32`, mapping `Uint8` layers to
+/// the range from 0 to 1 if `normalize` is set.
+fn layer_f32(layer: &Layer<'_>, normalize: bool) -> Vec<f32> {
+    match &layer.data {
+        LayerData::Uint8(data) if normalize => {
+            data.iter().map(|&value| value as f32 / 255.0).collect()
+        }
+        LayerData::Uint8(data) => data.iter().map(|&value| value as f32).collect(),
+        LayerData::Int32(data) => data.iter().map(|&value| value as f32).collect(),
+        LayerData::Float(data) => data.to_vec(),
+        LayerData::Double(data) => data.iter().map(|&value| value as f32).collect(),
+    }
+}
+
+/// Reads a `.gltf` or `.glb` file.
+///
+/// Every glTF mesh becomes a geometry node with vertex layers for its
+/// attributes, and a `material` face layer if its primitives use materials.
+/// Every glTF node becomes a meta node with its name and transform, which
+/// references its mesh in `mesh` metadata and its children in `children`
+/// metadata.
+pub fn import(path: &Path) -> Result<Hxa<'static>> {
+    let gltf = Gltf::from_slice(&fs::read(path)?)?;
+    let mut buffers = Vec::new();
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or("The glTF file refers to a binary chunk it doesn't have")?,
+            Source::Uri(uri) => match uri.strip_prefix("data:") {
+                Some(data) => {
+                    let (_, encoded) = data
+                        .split_once(";base64,")
+                        .ok_or("Only base64 data URIs are supported")?;
+                    base64::decode(encoded)?
+                }
+                None => fs::read(path.with_file_name(uri))?,
+            },
+        };
+        if data.len() < buffer.length() {
+            return Err(format!("Buffer {} is too short", buffer.index()).into());
+        }
+        buffers.push(data);
+    }
+
+    let mesh_count = gltf.meshes().len();
+    let mut nodes = Vec::new();
+    for mesh in gltf.meshes() {
+        nodes.push(import_mesh(&mesh, &buffers)?);
+    }
+    for node in gltf.nodes() {
+        let mut metadata = MetaBuilder::new();
+        if let Some(name) = node.name() {
+            metadata = metadata.text(SC_NAME, name);
+        }
+        let columns = node.transform().matrix();
+        let mut matrix = [0.0; 16];
+        for (to, from) in matrix.iter_mut().zip(columns.iter().flatten()) {
+            *to = *from as f64;
+        }
+        let transform = Transform::from_cols_array(&matrix);
+        if transform != Transform::IDENTITY {
+            metadata = metadata.transform(transform);
+        }
+        if let Some(mesh) = node.mesh() {
+            metadata = metadata.node(MESH, &[mesh.index() as u32]);
+        }
+        let children: Vec<u32> = node
+            .children()
+            .map(|child| (mesh_count + child.index()) as u32)
+            .collect();
+        if !children.is_empty() {
+            metadata = metadata.node(CHILDREN, &children);
+        }
+        nodes.push(Node {
+            type_: NodeType::Meta,
+            metadata: metadata.build(),
+            content: None,
+        });
+    }
+
+    Ok(Hxa {
+        version: HxaVersion::LATEST,
+        nodes,
+    })
+}
+
+/// The vertex attributes of a mesh, gathered from all of its primitives.
+#[derive(Default)]
+struct MeshData {
+    positions: Vec<[f32; 3]>,
+    triangles: Vec<[u32; 3]>,
+    materials: Vec<i32>,
+    normals: Option<Vec<[f32; 3]>>,
+    tangents: Option<Vec<[f32; 4]>>,
+    uvs: Option<Vec<[f32; 2]>>,
+    colors: Option<Vec<[f32; 4]>>,
+    joints: Option<Vec<[i32; 4]>>,
+    weights: Option<Vec<[f32; 4]>>,
+    targets: Vec<Option<Vec<[f32; 3]>>>,
+}
+
+/// Adds the values of an attribute for the vertices of one primitive,
+/// filling in zeros for the primitives before it that didn't have it.
+fn extend<T: Copy + Default>(
+    attribute: &mut Option<Vec<T>>,
+    start: usize,
+    count: usize,
+    values: Option<impl Iterator<Item = T>>,
+) {
+    if let Some(values) = values {
+        let attribute = attribute.get_or_insert_with(Vec::new);
+        attribute.resize(start, T::default());
+        attribute.extend(values.take(count));
+    }
+    if let Some(attribute) = attribute {
+        attribute.resize(start + count, T::default());
+    }
+}
+
+fn import_mesh(mesh: &gltf::Mesh<'_>, buffers: &[Vec<u8>]) -> Result<Node<'static>> {
+    let mut data = MeshData::default();
+    let mut material_names = MetaBuilder::new();
+    let mut materials = Vec::new();
+    // Where the vertices of each set of accessors ended up, since primitives
+    // that differ only in their material usually share them.
+    let mut shared = HashMap::new();
+
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+        let positions: Vec<[f32; 3]> = match reader.read_positions() {
+            Some(positions) => positions.collect(),
+            None => continue,
+        };
+        let count = positions.len();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..count as u32).collect(),
+        };
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= count) {
+            return Err(format!("Vertex index {} is out of range", index).into());
+        }
+        let triangles: Vec<[u32; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            Mode::TriangleStrip => indices
+                .windows(3)
+                .enumerate()
+                .map(|(i, w)| {
+                    if i % 2 == 0 {
+                        [w[0], w[1], w[2]]
+                    } else {
+                        [w[1], w[0], w[2]]
+                    }
+                })
+                .collect(),
+            Mode::TriangleFan => indices
+                .windows(2)
+                .skip(1)
+                .map(|w| [indices[0], w[0], w[1]])
+                .collect(),
+            mode => {
+                println!(
+                    "Skipping a primitive of {:?} in mesh {}, since only triangles are supported",
+                    mode,
+                    mesh.index()
+                );
+                continue;
+            }
+        };
+        // Drop the triangles that have collapsed to lines or points.
+        let triangles = triangles
+            .into_iter()
+            .filter(|[a, b, c]| a != b && b != c && c != a);
+
+        let accessors: Vec<(String, usize)> = primitive
+            .attributes()
+            .map(|(semantic, accessor)| (semantic.to_string(), accessor.index()))
+            .chain(
+                primitive
+                    .morph_targets()
+                    .enumerate()
+                    .filter_map(|(target, morph)| {
+                        Some((format!("target{}", target), morph.positions()?.index()))
+                    }),
+            )
+            .collect();
+        let (start, is_new) = match shared.get(&accessors) {
+            Some(&start) => (start, false),
+            None => {
+                shared.insert(accessors, data.positions.len());
+                (data.positions.len(), true)
+            }
+        };
+
+        let material = match primitive.material().index() {
+            Some(index) if materials.contains(&index) => index as i32,
+            Some(index) => {
+                materials.push(index);
+                let name = match primitive.material().name() {
+                    Some(name) => name.to_string(),
+                    None => format!("material{}", index),
+                };
+                material_names = material_names.text(&index.to_string(), &name);
+                index as i32
+            }
+            None => -1,
+        };
+        for triangle in triangles {
+            data.triangles
+                .push(triangle.map(|index| (start as u32) + index));
+            data.materials.push(material);
+        }
+        if !is_new {
+            continue;
+        }
+
+        data.positions.extend(positions);
+        extend(&mut data.normals, start, count, reader.read_normals());
+        extend(&mut data.tangents, start, count, reader.read_tangents());
+        extend(
+            &mut data.uvs,
+            start,
+            count,
+            reader.read_tex_coords(0).map(|uvs| uvs.into_f32()),
+        );
+        extend(
+            &mut data.colors,
+            start,
+            count,
+            reader.read_colors(0).map(|colors| colors.into_rgba_f32()),
+        );
+        extend(
+            &mut data.joints,
+            start,
+            count,
+            reader
+                .read_joints(0)
+                .map(|joints| joints.into_u16().map(|joint| joint.map(i32::from))),
+        );
+        extend(
+            &mut data.weights,
+            start,
+            count,
+            reader.read_weights(0).map(|weights| weights.into_f32()),
+        );
+        for (target, (positions, _, _)) in reader.read_morph_targets().enumerate() {
+            if data.targets.len() <= target {
+                data.targets.push(None);
+            }
+            extend(&mut data.targets[target], start, count, positions);
+        }
+    }
+
+    let mut builder = GeometryBuilder::new(&data.positions).triangles(&data.triangles);
+    if let Some(normals) = &data.normals {
+        builder = builder.vertex_layer(SC_LAYER_NORMALS, normals);
+    }
+    if let Some(tangents) = &data.tangents {
+        builder = builder.vertex_layer(SC_LAYER_TANGENT, tangents);
+    }
+    if let Some(uvs) = &data.uvs {
+        builder = builder.vertex_layer(SC_LAYER_NAME_UV0, uvs);
+    }
+    if let Some(colors) = &data.colors {
+        builder = builder.vertex_layer(SC_LAYER_COLOR, colors);
+    }
+    if let Some(joints) = &data.joints {
+        builder = builder.vertex_layer(SC_LAYER_SKIN_REFERENCE, joints);
+    }
+    if let Some(weights) = &data.weights {
+        builder = builder.vertex_layer(SC_LAYER_SKIN_WEIGHT, weights);
+    }
+    for (index, target) in data.targets.iter_mut().enumerate() {
+        let target = target.get_or_insert_with(Vec::new);
+        target.resize(data.positions.len(), [0.0; 3]);
+        let name = if index == 0 {
+            String::from(SC_LAYER_ADD_BLENDSHAPE)
+        } else {
+            format!("{}{}", SC_LAYER_ADD_BLENDSHAPE, index)
+        };
+        builder = builder.vertex_layer(&name, target);
+    }
+
+    let mut metadata = MetaBuilder::new();
+    if let Some(name) = mesh.name() {
+        metadata = metadata.text(SC_NAME, name);
+    }
+    if !materials.is_empty() {
+        builder = builder.face_layer(SC_LAYER_MATERIAL_ID, &data.materials);
+        metadata = metadata.meta(MATERIAL_NAMES, material_names);
+    }
+    Ok(builder.metadata(metadata).build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use hxa::LayerStack;
+
+    use super::*;
+
+    /// A quad with a material per triangle, skinning and a morph target,
+    /// placed by a glTF-style node under a root node.
+    fn fixture() -> Hxa<'static> {
+        let quad = GeometryBuilder::new(&[
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ])
+        .triangles(&[[0, 1, 2], [0, 2, 3]])
+        .vertex_layer(SC_LAYER_NORMALS, &[[0.0f32, 0.0, 1.0]; 4])
+        .vertex_layer(
+            SC_LAYER_NAME_UV0,
+            &[[0.0f32, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+        )
+        .vertex_layer(
+            SC_LAYER_COLOR,
+            &[
+                [1.0f32, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0, 0.5],
+            ],
+        )
+        .vertex_layer(
+            SC_LAYER_SKIN_REFERENCE,
+            &[[0i32, 1, 0, 0], [1, 2, 0, 0], [2, 0, 0, 0], [3, 1, 0, 0]],
+        )
+        .vertex_layer(
+            SC_LAYER_SKIN_WEIGHT,
+            &[
+                [0.5f32, 0.5, 0.0, 0.0],
+                [0.25, 0.75, 0.0, 0.0],
+                [1.0, 0.0, 0.0, 0.0],
+                [0.5, 0.5, 0.0, 0.0],
+            ],
+        )
+        .vertex_layer(
+            SC_LAYER_ADD_BLENDSHAPE,
+            &[
+                [0.0f32, 0.0, 1.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 0.0, 0.5],
+                [0.0, 0.0, 0.0],
+            ],
+        )
+        .face_layer(SC_LAYER_MATERIAL_ID, &[0i32, 1])
+        .metadata(MetaBuilder::new().text(SC_NAME, "quad").meta(
+            MATERIAL_NAMES,
+            MetaBuilder::new().text("0", "red").text("1", "blue"),
+        ))
+        .build()
+        .unwrap();
+        let meta = |metadata: MetaBuilder| Node {
+            type_: NodeType::Meta,
+            metadata: metadata.build(),
+            content: None,
+        };
+        Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![
+                quad,
+                meta(
+                    MetaBuilder::new()
+                        .text(SC_NAME, "root")
+                        .node(CHILDREN, &[2]),
+                ),
+                meta(
+                    MetaBuilder::new()
+                        .text(SC_NAME, "placed")
+                        .transform(Transform::from_translation([1.0, 2.0, -3.0]))
+                        .node(MESH, &[0]),
+                ),
+            ],
+        }
+    }
+
+    fn export_bytes(hxa: &Hxa<'_>, binary: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        export(hxa, binary, &mut out).unwrap();
+        out
+    }
+
+    /// Imports from a file, since that is what the importer reads. Each test
+    /// uses its own `name` so that they can run in parallel.
+    fn import_bytes(name: &str, data: &[u8], binary: bool) -> Hxa<'static> {
+        let extension = if binary { "glb" } else { "gltf" };
+        let file_name = format!("hxa-conv-{}-{}.{}", process::id(), name, extension);
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, data).unwrap();
+        let hxa = import(&path);
+        fs::remove_file(&path).unwrap();
+        hxa.unwrap()
+    }
+
+    fn assert_same_stack(expected: &LayerStack<'_>, found: &LayerStack<'_>) {
+        assert_eq!(expected.layers.len(), found.layers.len());
+        for expected in &expected.layers {
+            let found = found.get(&expected.name).unwrap();
+            assert_eq!(
+                expected.component_count, found.component_count,
+                "{}",
+                expected.name
+            );
+            assert_eq!(
+                expected.data.type_(),
+                found.data.type_(),
+                "{}",
+                expected.name
+            );
+            assert_eq!(
+                layer_f32(expected, false),
+                layer_f32(found, false),
+                "{}",
+                expected.name
+            );
+        }
+    }
+
+    fn assert_same_scene(expected: &Hxa<'_>, found: &Hxa<'_>) {
+        assert_eq!(expected.nodes.len(), found.nodes.len());
+        for (expected, found) in expected.nodes.iter().zip(&found.nodes) {
+            assert_eq!(expected.type_, found.type_);
+            assert_eq!(expected.meta_text(SC_NAME), found.meta_text(SC_NAME));
+            assert_eq!(expected.transform(), found.transform());
+            assert_eq!(expected.meta_node_refs(MESH), found.meta_node_refs(MESH));
+            assert_eq!(
+                expected.meta_node_refs(CHILDREN),
+                found.meta_node_refs(CHILDREN)
+            );
+            for id in ["0", "1"] {
+                let path = format!("{}/{}", MATERIAL_NAMES, id);
+                assert_eq!(expected.meta_text(&path), found.meta_text(&path));
+            }
+            match (&expected.content, &found.content) {
+                (Some(NodeContent::Geometry(expected)), Some(NodeContent::Geometry(found))) => {
+                    assert_same_stack(&expected.vertex_stack, &found.vertex_stack);
+                    assert_same_stack(&expected.corner_stack, &found.corner_stack);
+                    assert_same_stack(&expected.face_stack, &found.face_stack);
+                }
+                (None, None) => {}
+                _ => panic!("node content differs"),
+            }
+        }
+    }
+
+    fn round_trip(name: &str, binary: bool) {
+        let original = fixture();
+        let exported = export_bytes(&original, binary);
+        let imported = import_bytes(name, &exported, binary);
+        assert_same_scene(&original, &imported);
+        assert_eq!(exported, export_bytes(&imported, binary));
+    }
+
+    #[test]
+    fn gltf_round_trip() {
+        round_trip("gltf_round_trip", false);
+    }
+
+    #[test]
+    fn glb_round_trip() {
+        round_trip("glb_round_trip", true);
+    }
+
+    #[test]
+    fn splits_primitives_by_material() {
+        let exported = export_bytes(&fixture(), false);
+        let gltf = Gltf::from_slice(&exported).unwrap();
+        let mesh = gltf.meshes().next().unwrap();
+        let primitives: Vec<_> = mesh.primitives().collect();
+        assert_eq!(primitives.len(), 2);
+        for primitive in &primitives {
+            assert_eq!(primitive.morph_targets().count(), 1);
+            assert!(primitive.get(&gltf::Semantic::Joints(0)).is_some());
+            assert!(primitive.get(&gltf::Semantic::Weights(0)).is_some());
+        }
+        let names: Vec<_> = gltf.materials().map(|material| material.name()).collect();
+        assert_eq!(names, [Some("red"), Some("blue")]);
+        let placed = gltf.nodes().nth(1).unwrap();
+        assert_eq!(placed.name(), Some("placed"));
+        assert!(placed.mesh().is_some());
+        let (translation, _, _) = placed.transform().decomposed();
+        assert_eq!(translation, [1.0, 2.0, -3.0]);
+    }
+}
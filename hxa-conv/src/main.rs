@@ -1,57 +1,138 @@
 use std::convert::Infallible;
+use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
 
+use hxa::Hxa;
 use pico_args::Arguments;
 
+mod khronos;
+mod off;
+mod ply;
+mod stl;
+mod wavefront;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
 fn main() {
     let mut args = Arguments::from_env();
     let file = args
         .free_from_os_str::<_, Infallible>(|s| Ok(PathBuf::from(s)))
         .unwrap();
     let target_format: Format = args.value_from_str("--to").unwrap();
+    let output = args
+        .opt_value_from_os_str::<_, _, Infallible>(["-o", "--output"], |s| Ok(PathBuf::from(s)))
+        .unwrap()
+        .unwrap_or_else(|| file.with_extension(target_format.extension()));
+    let encoding = args
+        .opt_value_from_str("--encoding")
+        .unwrap()
+        .unwrap_or(Encoding::BinaryLittleEndian);
     assert!(file.exists(), "{} does not exist", file.display());
-    convert_to(&file, target_format);
+    if let Err(error) = convert_to(&file, &output, target_format, encoding) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
 }
 
-fn convert_to(source: &Path, target_format: Format) {
-    let source_format = detect_source_format(&source);
-
-    match source_format {
-        Format::Hxa => match target_format {
-            f @ Format::Hxa => identical_format(source, f),
-            Format::Obj => convert_hxa_to_obj(source),
-        },
-        Format::Obj => match target_format {
-            Format::Hxa => convert_obj_to_hxa(source),
-            f @ Format::Obj => identical_format(source, f),
-        },
+fn convert_to(
+    source: &Path,
+    output: &Path,
+    target_format: Format,
+    encoding: Encoding,
+) -> Result<()> {
+    let source_format = detect_source_format(source);
+    if source_format == target_format {
+        return identical_format(source, target_format);
+    }
+
+    let data;
+    let hxa = match source_format {
+        Format::Hxa => {
+            data = fs::read(source)?;
+            Hxa::new(&data)?
+        }
+        Format::Obj => wavefront::import(source)?,
+        Format::Gltf | Format::Glb => khronos::import(source)?,
+        Format::Ply => ply::import(source)?,
+        Format::Stl => stl::import(source)?,
+        Format::Off => off::import(source)?,
+    };
+    check_hxa(source, &hxa)?;
+
+    // Exporting into memory first leaves any existing output alone if the
+    // export fails.
+    let mut out = Vec::new();
+    let mut materials = Vec::new();
+    let material_library = output.with_extension("mtl");
+    match target_format {
+        Format::Hxa => hxa.write_to(&mut out)?,
+        Format::Obj => {
+            let name = material_library.file_name().unwrap().to_string_lossy();
+            wavefront::export(&hxa, &name, &mut out, &mut materials)?
+        }
+        Format::Gltf => khronos::export(&hxa, false, &mut out)?,
+        Format::Glb => khronos::export(&hxa, true, &mut out)?,
+        Format::Ply => ply::export(&hxa, encoding, &mut out)?,
+        Format::Stl => stl::export(&hxa, encoding, &mut out)?,
+        Format::Off => off::export(&hxa, &mut out)?,
     }
+    fs::write(output, out)?;
+    if !materials.is_empty() {
+        fs::write(material_library, materials)?;
+    }
+    Ok(())
 }
 
-fn identical_format(source: &Path, format: Format) {
+fn identical_format(source: &Path, format: Format) -> Result<()> {
     println!("{} is already in the {} format", source.display(), format);
+    Ok(())
 }
 
 fn detect_source_format(file: &Path) -> Format {
     Format::from_str(file.extension().unwrap().to_str().unwrap()).unwrap()
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Format {
     Hxa,
     Obj,
+    Gltf,
+    Glb,
+    Ply,
+    Stl,
+    Off,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Hxa => "hxa",
+            Self::Obj => "obj",
+            Self::Gltf => "gltf",
+            Self::Glb => "glb",
+            Self::Ply => "ply",
+            Self::Stl => "stl",
+            Self::Off => "off",
+        }
+    }
 }
 
 impl FromStr for Format {
     type Err = &'static str;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         match s {
             "hxa" => Ok(Format::Hxa),
             "obj" => Ok(Format::Obj),
+            "gltf" => Ok(Format::Gltf),
+            "glb" => Ok(Format::Glb),
+            "ply" => Ok(Format::Ply),
+            "stl" => Ok(Format::Stl),
+            "off" | "coff" => Ok(Format::Off),
             _ => Err("Invalid format"),
         }
     }
@@ -62,76 +143,45 @@ impl fmt::Display for Format {
         match self {
             Self::Hxa => write!(f, "HxA"),
             Self::Obj => write!(f, "Wavefront OBJ"),
+            Self::Gltf => write!(f, "glTF"),
+            Self::Glb => write!(f, "binary glTF"),
+            Self::Ply => write!(f, "PLY"),
+            Self::Stl => write!(f, "STL"),
+            Self::Off => write!(f, "OFF"),
         }
     }
 }
 
-fn convert_hxa_to_obj(source: &Path) {
-    use hxa::{Hxa, LayerData, NodeContent, NodeType};
-    use obj::{Obj, ObjData};
+/// How formats with both a text and a binary variant are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
 
-    let data = fs::read(source).unwrap();
-    let hxa = Hxa::new(&data).unwrap();
-    let mut obj = Obj {
-        data: ObjData::default(),
-        path: PathBuf::new(),
-    };
+impl FromStr for Encoding {
+    type Err = &'static str;
 
-    println!("Warning: The {0} to {1} conversion is currently incomplete, and may omit important parts of your {0} file", Format::Hxa, Format::Obj);
-
-    for node in hxa.nodes {
-        println!(r#"Encountered node of type "{:?}""#, node.type_);
-        println!("Ignoring {} pieces of metadata", node.metadata.len());
-
-        match node.content {
-            Some(NodeContent::Geometry(geometry)) => {
-                let vertex_stack = geometry.vertex_stack.layers;
-                let corner_stack = geometry.corner_stack.layers;
-                let vertices = &vertex_stack[hxa::HC_BASE_VERTEX_LAYER_ID];
-                assert_eq!(vertices.name, hxa::HC_BASE_VERTEX_LAYER_NAME);
-                assert_eq!(
-                    vertices.component_count,
-                    hxa::HC_BASE_VERTEX_LAYER_COMPONENTS
-                );
-                let indices = &corner_stack[hxa::HC_BASE_CORNER_LAYER_ID];
-                assert_eq!(indices.name, hxa::HC_BASE_CORNER_LAYER_NAME);
-                assert_eq!(
-                    indices.component_count,
-                    hxa::HC_BASE_CORNER_LAYER_COMPONENTS
-                );
-                match &vertices.data {
-                    LayerData::Double(slice) => {
-                        for vertex in slice.chunks_exact(vertices.component_count as usize) {
-                            //
-                        }
-                    }
-                    LayerData::Float(vertices) => {
-                        //
-                    }
-                    _ => println!("Non-floating-point vertex data is currently unsupported"),
-                }
-                todo!()
-            }
-            Some(NodeContent::Image(_)) => {
-                println!(
-                    "Images cannot be directly included in a {} file",
-                    Format::Obj
-                )
-            }
-            None => match node.type_ {
-                t @ NodeType::Geometry | t @ NodeType::Image => {
-                    println!(
-                        r#"Nodes of type "{:?}" should have content, but this one doesn't"#,
-                        t
-                    )
-                }
-                NodeType::Meta => {}
-            },
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Encoding::Ascii),
+            "binary" | "binary_little_endian" => Ok(Encoding::BinaryLittleEndian),
+            "binary_big_endian" => Ok(Encoding::BinaryBigEndian),
+            _ => Err("Invalid encoding"),
         }
     }
-    todo!()
 }
 
-fn convert_obj_to_hxa(_source: &Path) {
-    todo!()
+/// Refuses to convert files that break the hard conventions, since the
+/// exporters rely on them.
+fn check_hxa(source: &Path, hxa: &Hxa<'_>) -> Result<()> {
+    let diagnostics = hxa.validate();
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    Err(format!("{} is malformed", source.display()).into())
 }
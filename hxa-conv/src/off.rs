@@ -0,0 +1,623 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use hxa::{
+    GeometryBuilder, Hxa, HxaVersion, Layer, NodeContent, NodeGeometry, Transform,
+    HC_BASE_VERTEX_LAYER_ID, SC_LAYER_COLOR, SC_LAYER_NAME_UV0, SC_LAYER_NORMALS,
+};
+
+use crate::wavefront::{format_elements, join, transform_normal};
+use crate::Result;
+
+/// Reads an OFF file into a single geometry node.
+///
+/// The `ST`, `C` and `N` variants of the format are supported, putting
+/// texture coordinates, colours and normals into the `uv`, `color` and
+/// `normal` vertex layers. Colours given after the vertices of a face go in
+/// a `color` face layer. Colours written as integers are kept as `Uint8`
+/// layers, and those written as decimals as `Float` layers.
+pub fn import(path: &Path) -> Result<Hxa<'static>> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split_whitespace().collect::<Vec<_>>());
+
+    // The keyword is optional, and the counts may follow it on the same line.
+    let mut line = lines.next().ok_or("The OFF file is empty")?;
+    let mut keyword = "OFF";
+    if line[0].ends_with("OFF") {
+        keyword = line[0];
+        line.remove(0);
+        if line.is_empty() {
+            line = lines.next().ok_or("The OFF file has no vertex count")?;
+        }
+    }
+    let variant = keyword.strip_suffix("OFF").unwrap_or_default();
+    let (has_uvs, variant) = strip(variant, "ST");
+    let (has_colors, variant) = strip(variant, "C");
+    let (has_normals, variant) = strip(variant, "N");
+    if !variant.is_empty() {
+        return Err(format!("Unsupported OFF variant {}", keyword).into());
+    }
+    let (vertex_count, face_count) = match line[..] {
+        [vertices, faces, ..] => (vertices.parse::<usize>()?, faces.parse::<usize>()?),
+        _ => return Err("The OFF file has no vertex and face counts".into()),
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    for vertex in 0..vertex_count {
+        let line = lines
+            .next()
+            .ok_or_else(|| format!("The OFF file ends before vertex {}", vertex))?;
+        let extra = 3 * has_normals as usize + 2 * has_uvs as usize;
+        let color_count = line.len().saturating_sub(3 + extra);
+        let expected = if has_colors {
+            color_count == 3 || color_count == 4
+        } else {
+            color_count == 0
+        };
+        if !expected {
+            return Err(format!("Vertex {} has the wrong number of values", vertex).into());
+        }
+        let mut values = line.iter();
+        positions.push(parse::<3>(&mut values)?);
+        if has_normals {
+            normals.push(parse::<3>(&mut values)?);
+        }
+        if has_colors {
+            colors.push(values.by_ref().take(color_count).copied().collect());
+        }
+        if has_uvs {
+            uvs.push(parse::<2>(&mut values)?);
+        }
+    }
+
+    let mut polygons = Vec::new();
+    let mut face_colors = Vec::new();
+    for face in 0..face_count {
+        let line = lines
+            .next()
+            .ok_or_else(|| format!("The OFF file ends before face {}", face))?;
+        let count: usize = line[0].parse()?;
+        let indices = count
+            .checked_add(1)
+            .and_then(|end| line.get(1..end))
+            .ok_or_else(|| format!("Face {} has fewer than {} vertices", face, count))?;
+        if count < 3 {
+            println!("Skipping face {}, which has only {} vertices", face, count);
+            continue;
+        }
+        let polygon = indices
+            .iter()
+            .map(|index| index.parse())
+            .collect::<std::result::Result<Vec<u32>, _>>()?;
+        polygons.push(polygon);
+        face_colors.push(line[1 + count..].to_vec());
+    }
+
+    let mut builder = match narrow(&positions) {
+        Some(positions) => GeometryBuilder::new(&positions),
+        None => GeometryBuilder::new(&positions),
+    }
+    .polygons(polygons.iter().map(Vec::as_slice));
+    if has_normals {
+        builder = match narrow(&normals) {
+            Some(normals) => builder.vertex_layer(SC_LAYER_NORMALS, &normals),
+            None => builder.vertex_layer(SC_LAYER_NORMALS, &normals),
+        };
+    }
+    if has_uvs {
+        builder = match narrow(&uvs) {
+            Some(uvs) => builder.vertex_layer(SC_LAYER_NAME_UV0, &uvs),
+            None => builder.vertex_layer(SC_LAYER_NAME_UV0, &uvs),
+        };
+    }
+    let mut node = builder.build()?;
+    if let Some(NodeContent::Geometry(geometry)) = &mut node.content {
+        if has_colors {
+            let layer = color_layer(&colors).ok_or("The OFF file has invalid vertex colours")?;
+            geometry.vertex_stack.insert(layer);
+        }
+        if face_colors.iter().any(|color| !color.is_empty()) {
+            match color_layer(&face_colors) {
+                Some(layer) => {
+                    geometry.face_stack.insert(layer);
+                }
+                None => println!("Skipping the face colours, which aren't all RGB or RGBA"),
+            }
+        }
+    }
+    Ok(Hxa {
+        version: HxaVersion::LATEST,
+        nodes: vec![node],
+    })
+}
+
+fn strip<'s>(variant: &'s str, prefix: &str) -> (bool, &'s str) {
+    match variant.strip_prefix(prefix) {
+        Some(rest) => (true, rest),
+        None => (false, variant),
+    }
+}
+
+fn parse<const N: usize>(values: &mut std::slice::Iter<'_, &str>) -> Result<[f64; N]> {
+    let mut parsed = [0.0; N];
+    for component in &mut parsed {
+        *component = values.next().ok_or("Missing value")?.parse()?;
+    }
+    Ok(parsed)
+}
+
+/// Converts values to `f32` if that keeps every digit they were written
+/// with, so that only files written with more precision get `Double` layers.
+fn narrow<const N: usize>(values: &[[f64; N]]) -> Option<Vec<[f32; N]>> {
+    values
+        .iter()
+        .map(|element| {
+            let mut narrowed = [0.0; N];
+            for (to, &from) in narrowed.iter_mut().zip(element) {
+                *to = from as f32;
+                if to.to_string().parse::<f64>() != Ok(from) {
+                    return None;
+                }
+            }
+            Some(narrowed)
+        })
+        .collect()
+}
+
+/// Makes a `color` layer out of RGB or RGBA colours, which are `Uint8` if
+/// they are all written as integers up to 255 and `Float` otherwise. RGB
+/// colours are made opaque if others have an alpha channel.
+fn color_layer(colors: &[Vec<&str>]) -> Option<Layer<'static>> {
+    if colors.is_empty()
+        || colors
+            .iter()
+            .any(|color| color.len() != 3 && color.len() != 4)
+    {
+        return None;
+    }
+    let components = colors.iter().map(Vec::len).max()?;
+    let integers: Option<Vec<Vec<u8>>> = colors
+        .iter()
+        .map(|color| color.iter().map(|value| value.parse().ok()).collect())
+        .collect();
+    let layer = match integers {
+        Some(integers) => {
+            let opaque = integers
+                .iter()
+                .flat_map(|color| pad(color, components, u8::MAX))
+                .collect::<Vec<_>>();
+            Layer::from_slice(SC_LAYER_COLOR, &opaque)
+        }
+        None => {
+            let floats: Option<Vec<Vec<f32>>> = colors
+                .iter()
+                .map(|color| color.iter().map(|value| value.parse().ok()).collect())
+                .collect();
+            let opaque = floats?
+                .iter()
+                .flat_map(|color| pad(color, components, 1.0))
+                .collect::<Vec<_>>();
+            Layer::from_slice(SC_LAYER_COLOR, &opaque)
+        }
+    };
+    Some(Layer {
+        component_count: components as u8,
+        ..layer
+    })
+}
+
+fn pad<T: Copy>(color: &[T], components: usize, alpha: T) -> Vec<T> {
+    let mut padded = color.to_vec();
+    padded.resize(components, alpha);
+    padded
+}
+
+/// Writes the first geometry node placed in the scene of `hxa` as an OFF
+/// file, with its transform applied to its positions and normals.
+///
+/// The `uv`, `color` and `normal` vertex layers and the `color` face layer
+/// are written if they have the right number of components, choosing the
+/// variant of the format to match.
+pub fn export(hxa: &Hxa<'_>, out: &mut impl Write) -> Result<()> {
+    let mut written = None;
+    for instance in hxa.instances()? {
+        match &hxa.nodes[instance.node].content {
+            Some(NodeContent::Geometry(_)) if written.is_some() => println!(
+                "Skipping geometry node {}, since an OFF file holds a single mesh",
+                instance.node
+            ),
+            Some(NodeContent::Geometry(geometry)) => written = Some((geometry, instance.world)),
+            Some(NodeContent::Image(_)) => println!(
+                "Skipping image node {}, since images cannot be included in an OFF file",
+                instance.node
+            ),
+            None => {}
+        }
+    }
+    let (geometry, world) = written.ok_or("There is no geometry to write")?;
+    write_geometry(geometry, world, out)
+}
+
+fn write_geometry(
+    geometry: &NodeGeometry<'_>,
+    world: Transform,
+    out: &mut impl Write,
+) -> Result<()> {
+    let vertex_layer = &geometry.vertex_stack.layers[HC_BASE_VERTEX_LAYER_ID];
+    let positions = if world == Transform::IDENTITY {
+        format_elements::<3>(vertex_layer)
+    } else {
+        let positions = vertex_layer.to_elements::<f64, 3>().unwrap_or_default();
+        positions
+            .into_iter()
+            .map(|position| join(&world.transform_point(position)))
+            .collect()
+    };
+    let normals = geometry
+        .vertex_stack
+        .get(SC_LAYER_NORMALS)
+        .filter(|layer| layer.component_count == 3)
+        .map(|layer| {
+            if world == Transform::IDENTITY {
+                return format_elements::<3>(layer);
+            }
+            let normals = layer.to_elements::<f64, 3>().unwrap_or_default();
+            normals
+                .into_iter()
+                .map(|normal| join(&transform_normal(&world, normal)))
+                .collect()
+        });
+    let colors = geometry
+        .vertex_stack
+        .get(SC_LAYER_COLOR)
+        .and_then(format_color);
+    let uvs = geometry
+        .vertex_stack
+        .get(SC_LAYER_NAME_UV0)
+        .filter(|layer| layer.component_count == 2)
+        .map(format_elements::<2>);
+    let face_colors = geometry
+        .face_stack
+        .get(SC_LAYER_COLOR)
+        .and_then(format_color);
+    for layer in geometry.corner_stack.attributes() {
+        println!(
+            r#"Skipping the "{}" corner layer, which OFF cannot hold"#,
+            layer.name
+        );
+    }
+
+    let mut keyword = String::new();
+    for (present, prefix) in [
+        (uvs.is_some(), "ST"),
+        (colors.is_some(), "C"),
+        (normals.is_some(), "N"),
+    ] {
+        if present {
+            keyword += prefix;
+        }
+    }
+    writeln!(out, "{}OFF", keyword)?;
+    let polygons = geometry.polygons()?;
+    let face_count = geometry.polygons()?.count();
+    writeln!(out, "{} {} 0", positions.len(), face_count)?;
+
+    for (vertex, position) in positions.iter().enumerate() {
+        write!(out, "{}", position)?;
+        for attribute in [&normals, &colors, &uvs].iter().copied().flatten() {
+            write!(out, " {}", attribute[vertex])?;
+        }
+        writeln!(out)?;
+    }
+    for (face, polygon) in polygons.enumerate() {
+        let polygon = polygon?;
+        write!(out, "{}", polygon.corners.len())?;
+        for vertex in polygon.vertices() {
+            write!(out, " {}", vertex)?;
+        }
+        if let Some(face_colors) = &face_colors {
+            write!(out, " {}", face_colors[face])?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Formats an RGB or RGBA `color` layer.
+fn format_color(layer: &Layer<'_>) -> Option<Vec<String>> {
+    match layer.component_count {
+        3 => Some(format_elements::<3>(layer)),
+        4 => Some(format_elements::<4>(layer)),
+        _ => {
+            println!(
+                r#"Skipping a "{}" layer with {} components"#,
+                layer.name, layer.component_count
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use hxa::{LayerData, LayerDataType, Node};
+
+    use super::*;
+
+    /// Imports from a file, since that is what the importer reads. Each test
+    /// uses its own `name` so that they can run in parallel.
+    fn try_import(name: &str, data: &str) -> Result<Hxa<'static>> {
+        let file_name = format!("hxa-conv-{}-{}.off", process::id(), name);
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, data).unwrap();
+        let hxa = import(&path);
+        fs::remove_file(&path).unwrap();
+        hxa
+    }
+
+    fn import_str(name: &str, data: &str) -> Hxa<'static> {
+        try_import(name, data).unwrap()
+    }
+
+    fn export_string(hxa: &Hxa<'_>) -> String {
+        let mut out = Vec::new();
+        export(hxa, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn geometry<'n>(node: &'n Node<'static>) -> &'n NodeGeometry<'static> {
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    /// The type, component count and values of a layer.
+    fn layer(layer: Option<&Layer<'_>>) -> (LayerDataType, u8, Vec<f64>) {
+        let layer = layer.unwrap();
+        let values = match &layer.data {
+            LayerData::Uint8(data) => data.iter().map(|&value| value as f64).collect(),
+            LayerData::Int32(data) => data.iter().map(|&value| value as f64).collect(),
+            LayerData::Float(data) => data.iter().map(|&value| value as f64).collect(),
+            LayerData::Double(data) => data.to_vec(),
+        };
+        (layer.data.type_(), layer.component_count, values)
+    }
+
+    fn vertex_layer(hxa: &Hxa<'static>, name: &str) -> (LayerDataType, u8, Vec<f64>) {
+        let geometry = geometry(&hxa.nodes[0]);
+        let layer = match name {
+            "vertex" => Some(&geometry.vertex_stack.layers[HC_BASE_VERTEX_LAYER_ID]),
+            _ => geometry.vertex_stack.get(name),
+        };
+        self::layer(layer)
+    }
+
+    #[test]
+    fn round_trips_plain_files() {
+        let off = "\
+OFF
+# A square and a triangle.
+5 2 0
+0 0 0
+1 0 0
+1 1 0
+0 1 0 # trailing comment
+0.5 2 0
+4 0 1 2 3
+3 3 2 4
+";
+        let hxa = import_str("round_trips_plain_files", off);
+        let polygons: Vec<Vec<u32>> = geometry(&hxa.nodes[0])
+            .polygons()
+            .unwrap()
+            .map(|polygon| polygon.unwrap().vertices().collect())
+            .collect();
+        assert_eq!(polygons, [vec![0, 1, 2, 3], vec![3, 2, 4]]);
+        assert_eq!(vertex_layer(&hxa, "vertex").0, LayerDataType::Float);
+        let expected = "OFF\n5 2 0\n0 0 0\n1 0 0\n1 1 0\n0 1 0\n0.5 2 0\n4 0 1 2 3\n3 3 2 4\n";
+        assert_eq!(export_string(&hxa), expected);
+    }
+
+    #[test]
+    fn reads_counts_with_or_without_a_keyword() {
+        let expected = "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n";
+        let cases = [
+            "OFF 3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n",
+            "3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n",
+            "\n# comment\nOFF\n\n3 1\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n",
+        ];
+        for (i, off) in cases.iter().enumerate() {
+            let name = format!("reads_counts_with_or_without_a_keyword{}", i);
+            assert_eq!(export_string(&import_str(&name, off)), expected);
+        }
+    }
+
+    #[test]
+    fn round_trips_every_variant() {
+        let off = "\
+STCNOFF
+3 1 0
+0 0 0 0 0 1 255 0 0 0 0
+1 0 0 0 0 1 0 255 0 1 0
+0 1 0 0 0 1 0 0 255 0 1
+3 0 1 2
+";
+        let hxa = import_str("round_trips_every_variant", off);
+        assert_eq!(
+            vertex_layer(&hxa, SC_LAYER_NORMALS),
+            (LayerDataType::Float, 3, [0.0, 0.0, 1.0].repeat(3))
+        );
+        assert_eq!(
+            vertex_layer(&hxa, SC_LAYER_NAME_UV0),
+            (LayerDataType::Float, 2, vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0])
+        );
+        assert_eq!(vertex_layer(&hxa, SC_LAYER_COLOR).0, LayerDataType::Uint8);
+        assert_eq!(export_string(&hxa), off);
+
+        for keyword in ["NOFF", "COFF", "STOFF", "CNOFF"] {
+            let name = format!("round_trips_every_variant_{}", keyword);
+            let values = match keyword {
+                "NOFF" => " 0 0 1",
+                "COFF" => " 1 2 3",
+                "STOFF" => " 0.5 0.5",
+                _ => " 0 0 1 1 2 3",
+            };
+            let off = format!(
+                "{0}\n3 1 0\n0 0 0{1}\n1 0 0{1}\n0 1 0{1}\n3 0 1 2\n",
+                keyword, values
+            );
+            assert_eq!(export_string(&import_str(&name, &off)), off);
+        }
+    }
+
+    #[test]
+    fn keeps_integer_and_float_colours() {
+        let colors = |name: &str, first: &str, second: &str| {
+            let off = format!(
+                "COFF\n3 1 0\n0 0 0 {}\n1 0 0 {}\n0 1 0 0 0 0\n3 0 1 2\n",
+                first, second
+            );
+            import_str(name, &off)
+        };
+        let hxa = colors("keeps_integer_colours", "255 128 0", "1 2 3 4");
+        assert_eq!(
+            vertex_layer(&hxa, SC_LAYER_COLOR),
+            (
+                LayerDataType::Uint8,
+                4,
+                vec![255.0, 128.0, 0.0, 255.0, 1.0, 2.0, 3.0, 4.0, 0.0, 0.0, 0.0, 255.0]
+            )
+        );
+        assert!(export_string(&hxa).contains("\n0 0 0 255 128 0 255\n"));
+
+        let hxa = colors("keeps_float_colours", "1 0.5 0", "0.25 0 1");
+        assert_eq!(
+            vertex_layer(&hxa, SC_LAYER_COLOR),
+            (
+                LayerDataType::Float,
+                3,
+                vec![1.0, 0.5, 0.0, 0.25, 0.0, 1.0, 0.0, 0.0, 0.0]
+            )
+        );
+        assert!(export_string(&hxa).contains("\n0 0 0 1 0.5 0\n"));
+
+        // Values past 255 don't fit in a byte.
+        let hxa = colors("keeps_large_colours", "256 0 0", "0 0 0 1");
+        let (type_, components, values) = vertex_layer(&hxa, SC_LAYER_COLOR);
+        assert_eq!((type_, components), (LayerDataType::Float, 4));
+        assert_eq!(values[..4], [256.0, 0.0, 0.0, 1.0]);
+
+        let error = try_import(
+            "rejects_invalid_colours",
+            "COFF\n3 1 0\n0 0 0 red 0 0\n1 0 0 0 0 0\n0 1 0 0 0 0\n3 0 1 2\n",
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "The OFF file has invalid vertex colours");
+    }
+
+    #[test]
+    fn reads_face_colours() {
+        let off = "\
+OFF
+4 2 0
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+3 0 1 2 255 0 0
+3 0 2 3 0 255 0 128
+";
+        let hxa = import_str("reads_face_colours", off);
+        let colors = geometry(&hxa.nodes[0]).face_stack.get(SC_LAYER_COLOR);
+        assert_eq!(
+            layer(colors),
+            (
+                LayerDataType::Uint8,
+                4,
+                vec![255.0, 0.0, 0.0, 255.0, 0.0, 255.0, 0.0, 128.0]
+            )
+        );
+        let written = export_string(&hxa);
+        assert!(written.ends_with("3 0 1 2 255 0 0 255\n3 0 2 3 0 255 0 128\n"));
+
+        let floats = off
+            .replace("255 0 0\n", "1 0 0.5\n")
+            .replace(" 0 255 0 128", " 0 1 0");
+        let hxa = import_str("reads_float_face_colours", &floats);
+        let colors = geometry(&hxa.nodes[0]).face_stack.get(SC_LAYER_COLOR);
+        assert_eq!(
+            layer(colors),
+            (LayerDataType::Float, 3, vec![1.0, 0.0, 0.5, 0.0, 1.0, 0.0])
+        );
+
+        // Colours that aren't RGB or RGBA are left out.
+        let uneven = off.replace(" 0 255 0 128", " 0 255");
+        let hxa = import_str("skips_uneven_face_colours", &uneven);
+        assert!(geometry(&hxa.nodes[0])
+            .face_stack
+            .get(SC_LAYER_COLOR)
+            .is_none());
+    }
+
+    #[test]
+    fn uses_doubles_only_for_precise_values() {
+        let off = "OFF\n3 1 0\n0.1 0.2 0.3\n1 0 0\n0 1 0\n3 0 1 2\n";
+        let hxa = import_str("uses_floats_for_short_values", off);
+        assert_eq!(vertex_layer(&hxa, "vertex").0, LayerDataType::Float);
+        assert_eq!(export_string(&hxa), off);
+
+        let off = "OFF\n3 1 0\n0.1234567890123 0 0\n1 0 0\n0 1 0\n3 0 1 2\n";
+        let hxa = import_str("uses_doubles_for_long_values", off);
+        assert_eq!(vertex_layer(&hxa, "vertex").0, LayerDataType::Double);
+        assert_eq!(export_string(&hxa), off);
+
+        let normals =
+            "NOFF\n3 1 0\n0 0 0 0 0.6 0.8\n1 0 0 0 0 1\n0 1 0 0.1234567890123 0 1\n3 0 1 2\n";
+        let hxa = import_str("uses_doubles_for_long_normals", normals);
+        assert_eq!(vertex_layer(&hxa, "vertex").0, LayerDataType::Float);
+        assert_eq!(
+            vertex_layer(&hxa, SC_LAYER_NORMALS).0,
+            LayerDataType::Double
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let cases = [
+            ("", "The OFF file is empty"),
+            ("4OFF\n3 1 0\n", "Unsupported OFF variant 4OFF"),
+            ("OFF\n", "The OFF file has no vertex count"),
+            ("OFF\n3\n", "The OFF file has no vertex and face counts"),
+            ("OFF\n3 1 0\n0 0 0\n", "The OFF file ends before vertex 1"),
+            ("OFF\n1 1 0\n0 0 0\n", "The OFF file ends before face 0"),
+            (
+                "OFF\n1 0 0\n0 0 0 1\n",
+                "Vertex 0 has the wrong number of values",
+            ),
+            (
+                "COFF\n1 0 0\n0 0 0 1 1\n",
+                "Vertex 0 has the wrong number of values",
+            ),
+            (
+                "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n4 0 1 2\n",
+                "Face 0 has fewer than 4 vertices",
+            ),
+        ];
+        for (i, (off, message)) in cases.iter().enumerate() {
+            let name = format!("rejects_malformed_files{}", i);
+            let error = try_import(&name, off).unwrap_err();
+            assert_eq!(error.to_string(), *message, "{:?}", off);
+        }
+    }
+}
@@ -0,0 +1,1419 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str;
+
+use hxa::{
+    GeometryBuilder, Hxa, HxaVersion, Layer, LayerData, LayerDataType, MetaBuilder, Node,
+    NodeContent, NodeGeometry, Transform, HC_BASE_VERTEX_LAYER_ID, SC_LAYER_COLOR,
+    SC_LAYER_NAME_UV0, SC_LAYER_NORMALS,
+};
+
+use crate::wavefront::transform_normal;
+use crate::{Encoding, Result};
+
+/// Node metadata holding the property declarations of the `vertex` and
+/// `face` elements a node was read from, as `Text` entries with one
+/// declaration per line.
+///
+/// HxA has fewer data types than PLY and no names for the components of a
+/// layer, so this is what lets a node be written back with the same header.
+pub const PLY_PROPERTIES: &str = "ply_properties";
+
+const VERTEX: &str = "vertex";
+const FACE: &str = "face";
+/// The list property holding per-corner texture coordinates, as written by
+/// MeshLab among others.
+const TEXCOORD: &str = "texcoord";
+/// The suffix of the layer holding the length of every list of a list
+/// property whose lists differ in length, next to a layer of the lists
+/// padded to the longest of them.
+const LIST_LENGTHS: &str = "_length";
+
+/// Properties that are read into a single layer when they appear together
+/// and in this order.
+const GROUPS: &[(&str, &[&str])] = &[
+    (SC_LAYER_NORMALS, &["nx", "ny", "nz"]),
+    (SC_LAYER_COLOR, &["red", "green", "blue", "alpha"]),
+    (SC_LAYER_COLOR, &["red", "green", "blue"]),
+    (SC_LAYER_NAME_UV0, &["s", "t"]),
+    (SC_LAYER_NAME_UV0, &["u", "v"]),
+    (SC_LAYER_NAME_UV0, &["texture_u", "texture_v"]),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scalar {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::Int8,
+            "uchar" | "uint8" => Scalar::Uint8,
+            "short" | "int16" => Scalar::Int16,
+            "ushort" | "uint16" => Scalar::Uint16,
+            "int" | "int32" => Scalar::Int32,
+            "uint" | "uint32" => Scalar::Uint32,
+            "float" | "float32" => Scalar::Float32,
+            "double" | "float64" => Scalar::Float64,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Scalar::Int8 => "char",
+            Scalar::Uint8 => "uchar",
+            Scalar::Int16 => "short",
+            Scalar::Uint16 => "ushort",
+            Scalar::Int32 => "int",
+            Scalar::Uint32 => "uint",
+            Scalar::Float32 => "float",
+            Scalar::Float64 => "double",
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::Uint8 => 1,
+            Scalar::Int16 | Scalar::Uint16 => 2,
+            Scalar::Int32 | Scalar::Uint32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    /// The layer type that holds every value of this type. `uint` values
+    /// above `i32::MAX` wrap around, and are restored when written back.
+    fn layer_type(self) -> LayerDataType {
+        match self {
+            Scalar::Uint8 => LayerDataType::Uint8,
+            Scalar::Int8 | Scalar::Int16 | Scalar::Uint16 | Scalar::Int32 | Scalar::Uint32 => {
+                LayerDataType::Int32
+            }
+            Scalar::Float32 => LayerDataType::Float,
+            Scalar::Float64 => LayerDataType::Double,
+        }
+    }
+
+    /// The type written for a layer that wasn't read from a PLY file.
+    fn for_layer(type_: LayerDataType) -> Self {
+        match type_ {
+            LayerDataType::Uint8 => Scalar::Uint8,
+            LayerDataType::Int32 => Scalar::Int32,
+            LayerDataType::Float => Scalar::Float32,
+            LayerDataType::Double => Scalar::Float64,
+        }
+    }
+
+    /// Decodes a value stored in little-endian byte order. Every PLY type
+    /// fits in an `f64` without loss.
+    fn decode(self, bytes: [u8; 8]) -> f64 {
+        let [b0, b1, b2, b3, ..] = bytes;
+        match self {
+            Scalar::Int8 => i8::from_le_bytes([b0]) as f64,
+            Scalar::Uint8 => b0 as f64,
+            Scalar::Int16 => i16::from_le_bytes([b0, b1]) as f64,
+            Scalar::Uint16 => u16::from_le_bytes([b0, b1]) as f64,
+            Scalar::Int32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Uint32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Float32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::Float64 => f64::from_le_bytes(bytes),
+        }
+    }
+
+    /// Encodes a value in little-endian byte order, in the first `size()`
+    /// bytes.
+    fn encode(self, value: f64) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        match self {
+            Scalar::Int8 => bytes[..1].copy_from_slice(&(value as i8).to_le_bytes()),
+            Scalar::Uint8 => bytes[..1].copy_from_slice(&(value as u8).to_le_bytes()),
+            Scalar::Int16 => bytes[..2].copy_from_slice(&(value as i16).to_le_bytes()),
+            Scalar::Uint16 => bytes[..2].copy_from_slice(&(value as u16).to_le_bytes()),
+            Scalar::Int32 => bytes[..4].copy_from_slice(&(value as i32).to_le_bytes()),
+            Scalar::Uint32 => bytes[..4].copy_from_slice(&(value as i64 as u32).to_le_bytes()),
+            Scalar::Float32 => bytes[..4].copy_from_slice(&(value as f32).to_le_bytes()),
+            Scalar::Float64 => bytes = value.to_le_bytes(),
+        }
+        bytes
+    }
+
+    fn format(self, value: f64) -> String {
+        match self {
+            Scalar::Int8 => (value as i8).to_string(),
+            Scalar::Uint8 => (value as u8).to_string(),
+            Scalar::Int16 => (value as i16).to_string(),
+            Scalar::Uint16 => (value as u16).to_string(),
+            Scalar::Int32 => (value as i32).to_string(),
+            Scalar::Uint32 => (value as i64 as u32).to_string(),
+            Scalar::Float32 => (value as f32).to_string(),
+            Scalar::Float64 => value.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PropertyType {
+    Scalar(Scalar),
+    /// A list with its length stored as the first type and its items as the
+    /// second.
+    List(Scalar, Scalar),
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    type_: PropertyType,
+}
+
+impl Property {
+    fn scalar(name: impl Into<String>, type_: Scalar) -> Self {
+        Self {
+            name: name.into(),
+            type_: PropertyType::Scalar(type_),
+        }
+    }
+
+    fn list(name: impl Into<String>, count: Scalar, item: Scalar) -> Self {
+        Self {
+            name: name.into(),
+            type_: PropertyType::List(count, item),
+        }
+    }
+
+    /// Parses a declaration without its leading `property` keyword.
+    fn parse(declaration: &str) -> Result<Self> {
+        let words: Vec<&str> = declaration.split_whitespace().collect();
+        let invalid = || format!("Invalid PLY property declaration {:?}", declaration);
+        let scalar = |name: &str| Scalar::parse(name).ok_or_else(invalid);
+        match words[..] {
+            ["list", count, item, name] => Ok(Self::list(name, scalar(count)?, scalar(item)?)),
+            [type_, name] => Ok(Self::scalar(name, scalar(type_)?)),
+            _ => Err(invalid().into()),
+        }
+    }
+
+    fn declaration(&self) -> String {
+        match self.type_ {
+            PropertyType::Scalar(type_) => format!("{} {}", type_.name(), self.name),
+            PropertyType::List(count, item) => {
+                format!("list {} {} {}", count.name(), item.name(), self.name)
+            }
+        }
+    }
+
+    fn scalar_type(&self) -> Option<Scalar> {
+        match self.type_ {
+            PropertyType::Scalar(type_) => Some(type_),
+            PropertyType::List(..) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    /// Parses the header, returning it along with the offset of the data
+    /// following it.
+    fn parse(data: &[u8]) -> Result<(Self, usize)> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let end = loop {
+            let length = data[start..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .ok_or("The PLY header has no end_header line")?;
+            let line = str::from_utf8(&data[start..start + length])?.trim_end_matches('\r');
+            start += length + 1;
+            if line.trim() == "end_header" {
+                break start;
+            }
+            lines.push(line);
+        };
+
+        let mut lines = lines.into_iter();
+        if lines.next().map(str::trim) != Some("ply") {
+            return Err("Not a PLY file".into());
+        }
+        let mut encoding = None;
+        let mut elements: Vec<Element> = Vec::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["format", format, version] => {
+                    if version != "1.0" {
+                        return Err(format!("Unsupported PLY version {}", version).into());
+                    }
+                    encoding = Some(match format {
+                        "ascii" => Encoding::Ascii,
+                        "binary_little_endian" => Encoding::BinaryLittleEndian,
+                        "binary_big_endian" => Encoding::BinaryBigEndian,
+                        _ => return Err(format!("Unknown PLY format {}", format).into()),
+                    });
+                }
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse()?,
+                    properties: Vec::new(),
+                }),
+                ["property", ..] => elements
+                    .last_mut()
+                    .ok_or("A PLY property is declared before any element")?
+                    .properties
+                    .push(Property::parse(&line.trim()["property".len()..])?),
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                _ => return Err(format!("Invalid PLY header line {:?}", line).into()),
+            }
+        }
+        let encoding = encoding.ok_or("The PLY header has no format line")?;
+        Ok((Self { encoding, elements }, end))
+    }
+}
+
+/// The values of one property for every item of an element.
+enum Column {
+    Scalars(Vec<f64>),
+    Lists(Vec<Vec<f64>>),
+}
+
+impl Column {
+    /// The lists of a list property, which is empty for scalar properties.
+    fn lists(&self) -> &[Vec<f64>] {
+        match self {
+            Column::Lists(lists) => lists,
+            Column::Scalars(_) => &[],
+        }
+    }
+}
+
+struct Reader<'d> {
+    data: &'d [u8],
+    position: usize,
+    encoding: Encoding,
+}
+
+impl Reader<'_> {
+    fn scalar(&mut self, type_: Scalar) -> Result<f64> {
+        if self.encoding == Encoding::Ascii {
+            let token = self.token()?;
+            return token
+                .parse()
+                .map_err(|_| format!("Invalid PLY {} value {:?}", type_.name(), token).into());
+        }
+        let size = type_.size();
+        let bytes = self
+            .data
+            .get(self.position..self.position + size)
+            .ok_or("The PLY file ends early")?;
+        self.position += size;
+        let mut buffer = [0; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.encoding == Encoding::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+        Ok(type_.decode(buffer))
+    }
+
+    fn token(&mut self) -> Result<&str> {
+        let rest = &self.data[self.position..];
+        let start = rest
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .ok_or("The PLY file ends early")?;
+        let length = rest[start..]
+            .iter()
+            .position(u8::is_ascii_whitespace)
+            .unwrap_or(rest.len() - start);
+        self.position += start + length;
+        Ok(str::from_utf8(&rest[start..start + length])?)
+    }
+
+    /// The fewest bytes a value of `type_` takes up, which is a digit in
+    /// ASCII files.
+    fn min_size(&self, type_: Scalar) -> usize {
+        match self.encoding {
+            Encoding::Ascii => 1,
+            Encoding::BinaryLittleEndian | Encoding::BinaryBigEndian => type_.size(),
+        }
+    }
+
+    /// The most values taking up at least `size` bytes each that the rest of
+    /// the file could hold.
+    fn capacity(&self, count: usize, size: usize) -> usize {
+        count.min((self.data.len() - self.position) / size.max(1))
+    }
+
+    /// The fewest bytes an item of `element` takes up, which is when all of
+    /// its lists are empty.
+    fn min_item_size(&self, element: &Element) -> usize {
+        element
+            .properties
+            .iter()
+            .map(|property| match property.type_ {
+                PropertyType::Scalar(type_) | PropertyType::List(type_, _) => self.min_size(type_),
+            })
+            .sum()
+    }
+
+    fn element(&mut self, element: &Element) -> Result<Vec<Column>> {
+        // Bound the count in the header by how many items the rest of the
+        // file could hold, so that it can't make every column allocate more
+        // than the file's size.
+        let capacity = self.capacity(element.count, self.min_item_size(element));
+        let mut columns: Vec<Column> = element
+            .properties
+            .iter()
+            .map(|property| match property.type_ {
+                PropertyType::Scalar(_) => Column::Scalars(Vec::with_capacity(capacity)),
+                PropertyType::List(..) => Column::Lists(Vec::with_capacity(capacity)),
+            })
+            .collect();
+        for _ in 0..element.count {
+            for (property, column) in element.properties.iter().zip(&mut columns) {
+                match (property.type_, column) {
+                    (PropertyType::Scalar(type_), Column::Scalars(values)) => {
+                        values.push(self.scalar(type_)?)
+                    }
+                    (PropertyType::List(count, item), Column::Lists(lists)) => {
+                        let count = self.scalar(count)?;
+                        if count < 0.0 {
+                            return Err("A PLY list has a negative length".into());
+                        }
+                        let count = count as usize;
+                        let mut list =
+                            Vec::with_capacity(self.capacity(count, self.min_size(item)));
+                        for _ in 0..count {
+                            list.push(self.scalar(item)?);
+                        }
+                        lists.push(list);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Ok(columns)
+    }
+}
+
+/// Splits the scalar properties of an element into the layers they are read
+/// into, returning the name of each layer and the properties making it up.
+///
+/// Properties are grouped by the names in [`GROUPS`], or by a common prefix
+/// followed by `_0`, `_1` and so on, as long as they are next to each other
+/// and have the same type. Any other property gets a layer of its own.
+fn group(properties: &[&Property]) -> Vec<(String, Vec<usize>)> {
+    let same_type = |range: &[&Property]| {
+        range
+            .iter()
+            .all(|property| property.type_ == range[0].type_)
+    };
+    let mut groups = Vec::new();
+    let mut start = 0;
+    while start < properties.len() {
+        let rest = &properties[start..];
+        let known = GROUPS.iter().find(|(_, names)| {
+            rest.len() >= names.len()
+                && rest
+                    .iter()
+                    .zip(names.iter())
+                    .all(|(p, name)| p.name == *name)
+                && same_type(&rest[..names.len()])
+        });
+        let (name, length) = match known {
+            Some((layer, names)) => (layer.to_string(), names.len()),
+            None => {
+                let prefix = rest[0].name.strip_suffix("_0");
+                let length = prefix.map_or(1, |prefix| {
+                    rest.iter()
+                        .enumerate()
+                        .take_while(|(i, property)| {
+                            property.type_ == rest[0].type_
+                                && property.name == format!("{}_{}", prefix, i)
+                        })
+                        .count()
+                });
+                match prefix {
+                    Some(prefix) if length > 1 => (prefix.to_string(), length),
+                    _ => (rest[0].name.clone(), 1),
+                }
+            }
+        };
+        groups.push((name, (start..start + length).collect()));
+        start += length;
+    }
+    groups
+}
+
+/// The properties a layer is written as, if it wasn't read from a PLY file.
+fn layer_properties(layer: &Layer<'_>) -> Vec<Property> {
+    let type_ = Scalar::for_layer(layer.data.type_());
+    let components = layer.component_count as usize;
+    let known = GROUPS
+        .iter()
+        .find(|(name, names)| *name == layer.name && names.len() == components);
+    match known {
+        Some((_, names)) => names
+            .iter()
+            .map(|name| Property::scalar(*name, type_))
+            .collect(),
+        None if components == 1 => vec![Property::scalar(layer.name.as_ref(), type_)],
+        None => (0..components)
+            .map(|i| Property::scalar(format!("{}_{}", layer.name, i), type_))
+            .collect(),
+    }
+}
+
+/// Makes a layer out of values read as `type_`.
+fn layer(name: &str, type_: Scalar, components: usize, values: Vec<f64>) -> Layer<'static> {
+    let data = match type_.layer_type() {
+        LayerDataType::Uint8 => LayerData::Uint8(values.iter().map(|&v| v as u8).collect()),
+        LayerDataType::Int32 => LayerData::Int32(values.iter().map(|&v| v as i64 as i32).collect()),
+        LayerDataType::Float => LayerData::Float(values.iter().map(|&v| v as f32).collect()),
+        LayerDataType::Double => LayerData::Double(Cow::Owned(values)),
+    };
+    Layer {
+        name: Cow::Owned(name.to_string()),
+        component_count: components as u8,
+        type_: type_.layer_type(),
+        data,
+    }
+}
+
+/// Interleaves the values of several scalar columns, keeping the items at
+/// the indices in `keep`.
+fn interleave(columns: &[&Column], keep: &[usize]) -> Vec<f64> {
+    let mut values = Vec::with_capacity(keep.len() * columns.len());
+    for &item in keep {
+        for column in columns {
+            if let Column::Scalars(column) = column {
+                values.push(column[item]);
+            }
+        }
+    }
+    values
+}
+
+/// Reads a PLY file into a single geometry node.
+///
+/// Vertex and face properties other than the positions and vertex indices
+/// become vertex and face layers, with related properties such as `nx`, `ny`
+/// and `nz` combined into a single layer. Face lists with the same number of
+/// values for every corner of their face become corner layers, so that
+/// texture coordinates in a `texcoord` list end up in the `uv` layer. Other
+/// lists become vertex or face layers. If their lengths differ, they are
+/// padded with zeros to the longest of them, and their lengths go in a
+/// layer named after the list with a `_length` suffix. Lists that are empty
+/// for every item are skipped. The original property declarations are
+/// kept in [`PLY_PROPERTIES`] metadata.
+pub fn import(path: &Path) -> Result<Hxa<'static>> {
+    let data = fs::read(path)?;
+    let (header, start) = Header::parse(&data)?;
+    let mut reader = Reader {
+        data: &data,
+        position: start,
+        encoding: header.encoding,
+    };
+    let mut vertices = None;
+    let mut faces = None;
+    for element in &header.elements {
+        let columns = reader.element(element)?;
+        match element.name.as_str() {
+            VERTEX => vertices = Some((element, columns)),
+            FACE => faces = Some((element, columns)),
+            name => println!(r#"Skipping the PLY element "{}""#, name),
+        }
+    }
+    let (vertex_element, vertex_columns) = vertices.ok_or("The PLY file has no vertex element")?;
+
+    let mut declarations = MetaBuilder::new();
+    let mut vertex_layers = Vec::new();
+    let mut face_layers = Vec::new();
+    let mut corner_layers = Vec::new();
+
+    // Positions, plus one layer for every other group of scalar properties.
+    let position = |axis: &str| {
+        vertex_element
+            .properties
+            .iter()
+            .position(|property| property.name == axis && property.scalar_type().is_some())
+            .ok_or("The PLY vertex element has no x, y and z properties")
+    };
+    let axes = [position("x")?, position("y")?, position("z")?];
+    let all_vertices: Vec<usize> = (0..vertex_element.count).collect();
+    let positions = interleave(&axes.map(|axis| &vertex_columns[axis]), &all_vertices);
+    let (kept, properties) = scalar_properties(vertex_element, |index| axes.contains(&index));
+    for (name, members) in group(&properties) {
+        let columns: Vec<&Column> = members.iter().map(|&m| &vertex_columns[kept[m]]).collect();
+        let type_ = properties[members[0]].scalar_type().unwrap();
+        let values = interleave(&columns, &all_vertices);
+        vertex_layers.push(layer(&name, type_, members.len(), values));
+    }
+    let mut kept_lists = Vec::new();
+    for (index, property) in vertex_element.properties.iter().enumerate() {
+        let item = match property.type_ {
+            PropertyType::List(_, item) => item,
+            PropertyType::Scalar(_) => continue,
+        };
+        let lists = vertex_columns[index].lists();
+        if let Some(length) = fixed_length(lists.iter().map(Vec::len)) {
+            vertex_layers.push(layer(&property.name, item, length, lists.concat()));
+        } else {
+            let lists: Vec<&Vec<f64>> = lists.iter().collect();
+            match padded_lists(property, &lists)? {
+                Some(layers) => vertex_layers.extend(layers),
+                None => {
+                    println!(
+                        r#"Skipping the PLY vertex list "{}", which is always empty"#,
+                        property.name
+                    );
+                    continue;
+                }
+            }
+        }
+        kept_lists.push(property.name.as_str());
+    }
+    declarations = declarations.text(
+        VERTEX,
+        &declare(vertex_element, |property| {
+            property.scalar_type().is_some() || kept_lists.contains(&property.name.as_str())
+        }),
+    );
+
+    let mut polygons: Vec<Vec<u32>> = Vec::new();
+    if let Some((face_element, face_columns)) = &faces {
+        let indices = face_element
+            .properties
+            .iter()
+            .position(|property| {
+                matches!(property.type_, PropertyType::List(..))
+                    && (property.name == "vertex_indices" || property.name == "vertex_index")
+            })
+            .ok_or("The PLY face element has no vertex_indices property")?;
+        let face_vertices = face_columns[indices].lists();
+        let mut kept_faces = Vec::with_capacity(face_vertices.len());
+        for (face, list) in face_vertices.iter().enumerate() {
+            if list.len() < 3 {
+                println!(
+                    "Skipping face {}, which has only {} vertices",
+                    face,
+                    list.len()
+                );
+                continue;
+            }
+            // Negative indices wrap around to be caught as out of range.
+            polygons.push(list.iter().map(|&index| index as i64 as u32).collect());
+            kept_faces.push(face);
+        }
+
+        let (kept, properties) = scalar_properties(face_element, |_| false);
+        for (name, members) in group(&properties) {
+            let columns: Vec<&Column> = members.iter().map(|&m| &face_columns[kept[m]]).collect();
+            let type_ = properties[members[0]].scalar_type().unwrap();
+            let values = interleave(&columns, &kept_faces);
+            face_layers.push(layer(&name, type_, members.len(), values));
+        }
+        // Other lists are per corner if each face has the same number of
+        // values for every one of its corners, and per face otherwise.
+        let mut kept_lists = vec![face_element.properties[indices].name.as_str()];
+        for (index, property) in face_element.properties.iter().enumerate() {
+            let item = match property.type_ {
+                PropertyType::List(_, item) if index != indices => item,
+                _ => continue,
+            };
+            let lists: Vec<&Vec<f64>> = kept_faces
+                .iter()
+                .map(|&face| &face_columns[index].lists()[face])
+                .collect();
+            let per_corner = fixed_length(lists.iter().zip(&kept_faces).map(|(list, &face)| {
+                let corners = face_vertices[face].len();
+                if list.len() % corners == 0 {
+                    list.len() / corners
+                } else {
+                    0
+                }
+            }));
+            let values = lists.iter().flat_map(|list| list.iter().copied()).collect();
+            if let Some(components) = per_corner {
+                let name = if property.name == TEXCOORD {
+                    SC_LAYER_NAME_UV0
+                } else {
+                    &property.name
+                };
+                corner_layers.push(layer(name, item, components, values));
+            } else if let Some(length) = fixed_length(lists.iter().map(|list| list.len())) {
+                face_layers.push(layer(&property.name, item, length, values));
+            } else if let Some(layers) = padded_lists(property, &lists)? {
+                face_layers.extend(layers);
+            } else {
+                println!(
+                    r#"Skipping the PLY face list "{}", which is always empty"#,
+                    property.name
+                );
+                continue;
+            }
+            kept_lists.push(&property.name);
+        }
+        declarations = declarations.text(
+            FACE,
+            &declare(face_element, |property| {
+                property.scalar_type().is_some() || kept_lists.contains(&property.name.as_str())
+            }),
+        );
+    }
+
+    let polygons = polygons.iter().map(Vec::as_slice);
+    let metadata = MetaBuilder::new().meta(PLY_PROPERTIES, declarations);
+    let mut node = if axes
+        .iter()
+        .all(|&axis| vertex_element.properties[axis].scalar_type() == Some(Scalar::Float32))
+    {
+        let positions: Vec<[f32; 3]> = positions
+            .chunks_exact(3)
+            .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+            .collect();
+        GeometryBuilder::new(&positions).polygons(polygons)
+    } else {
+        let positions: Vec<[f64; 3]> = positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+        GeometryBuilder::new(&positions).polygons(polygons)
+    }
+    .metadata(metadata)
+    .build()?;
+
+    if let Some(NodeContent::Geometry(geometry)) = &mut node.content {
+        geometry.vertex_stack.layers.extend(vertex_layers);
+        geometry.corner_stack.layers.extend(corner_layers);
+        geometry.face_stack.layers.extend(face_layers);
+    }
+    Ok(Hxa {
+        version: HxaVersion::LATEST,
+        nodes: vec![node],
+    })
+}
+
+/// The length shared by every list, if they all have the same one and it
+/// fits in the component count of a layer.
+fn fixed_length(mut lengths: impl Iterator<Item = usize>) -> Option<usize> {
+    let first = lengths.next()?;
+    let fits = (1..=u8::MAX as usize).contains(&first);
+    (fits && lengths.all(|length| length == first)).then_some(first)
+}
+
+/// Makes a layer out of lists of different lengths by padding them with
+/// zeros to the longest one, along with a layer of their lengths. Returns
+/// `None` if the lists are all empty.
+fn padded_lists(property: &Property, lists: &[&Vec<f64>]) -> Result<Option<[Layer<'static>; 2]>> {
+    let (count, item) = match property.type_ {
+        PropertyType::List(count, item) => (count, item),
+        PropertyType::Scalar(_) => unreachable!(),
+    };
+    let longest = lists.iter().map(|list| list.len()).max().unwrap_or(0);
+    if longest == 0 {
+        return Ok(None);
+    }
+    if longest > u8::MAX as usize {
+        let error = format!(
+            r#"The PLY list "{}" has a list of {} values, but layers hold at most {}"#,
+            property.name,
+            longest,
+            u8::MAX
+        );
+        return Err(error.into());
+    }
+    let mut values = Vec::with_capacity(lists.len() * longest);
+    for list in lists {
+        values.extend_from_slice(list);
+        values.resize(values.len() + longest - list.len(), 0.0);
+    }
+    let lengths = lists.iter().map(|list| list.len() as f64).collect();
+    let lengths_name = format!("{}{}", property.name, LIST_LENGTHS);
+    Ok(Some([
+        layer(&property.name, item, longest, values),
+        layer(&lengths_name, count, 1, lengths),
+    ]))
+}
+
+/// The scalar properties of an element that aren't excluded by their
+/// index, along with the index of each.
+fn scalar_properties(
+    element: &Element,
+    exclude: impl Fn(usize) -> bool,
+) -> (Vec<usize>, Vec<&Property>) {
+    element
+        .properties
+        .iter()
+        .enumerate()
+        .filter(|&(index, property)| property.scalar_type().is_some() && !exclude(index))
+        .unzip()
+}
+
+fn declare(element: &Element, keep: impl Fn(&Property) -> bool) -> String {
+    element
+        .properties
+        .iter()
+        .filter(|property| keep(property))
+        .map(Property::declaration)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Where the value of a property comes from when writing.
+enum Source<'v> {
+    /// A component of each element of a layer, given the layer's values, its
+    /// component count and the component.
+    Layer(&'v [f64], usize, usize),
+    /// Every component of each element of a layer as a list, given the
+    /// layer's values, its component count, and the length of each list if
+    /// the lists were padded to the same length.
+    List(&'v [f64], usize, Option<&'v [f64]>),
+    /// The vertex indices of each face.
+    Indices,
+    /// The values of a corner layer as a list per face, given the layer's
+    /// values and its component count.
+    Corners(&'v [f64], usize),
+}
+
+/// Writes the first geometry node placed in the scene of `hxa` as a PLY file,
+/// with its transform applied to its positions and normals.
+///
+/// Vertex and face layers are written as properties, using the declarations
+/// in [`PLY_PROPERTIES`] metadata for the layers they cover, in the order
+/// they were declared. Corner layers are written as face lists, with `uv`
+/// going in the `texcoord` list. Edge layers have no place in a PLY file.
+pub fn export(hxa: &Hxa<'_>, encoding: Encoding, out: &mut impl Write) -> Result<()> {
+    let mut written = None;
+    for instance in hxa.instances()? {
+        match &hxa.nodes[instance.node].content {
+            Some(NodeContent::Geometry(_)) if written.is_some() => println!(
+                "Skipping geometry node {}, since a PLY file holds a single mesh",
+                instance.node
+            ),
+            Some(NodeContent::Geometry(geometry)) => {
+                written = Some((&hxa.nodes[instance.node], geometry, instance.world))
+            }
+            Some(NodeContent::Image(_)) => println!(
+                "Skipping image node {}, since images cannot be included in a PLY file",
+                instance.node
+            ),
+            None => {}
+        }
+    }
+    let (node, geometry, world) = written.ok_or("There is no geometry to write")?;
+    write_geometry(node, geometry, world, encoding, out)
+}
+
+fn write_geometry(
+    node: &Node<'_>,
+    geometry: &NodeGeometry<'_>,
+    world: Transform,
+    encoding: Encoding,
+    out: &mut impl Write,
+) -> Result<()> {
+    let declared = |element: &str| -> Result<Vec<Property>> {
+        node.meta_text(&format!("{}/{}", PLY_PROPERTIES, element))
+            .map_or(Ok(Vec::new()), |text| {
+                text.lines().map(Property::parse).collect()
+            })
+    };
+
+    let vertex_layer = &geometry.vertex_stack.layers[HC_BASE_VERTEX_LAYER_ID];
+    let mut positions = components(vertex_layer);
+    let mut vertex_layers: Vec<(&Layer<'_>, Vec<f64>)> = geometry
+        .vertex_stack
+        .attributes()
+        .map(|layer| (layer, components(layer)))
+        .collect();
+    if world != Transform::IDENTITY {
+        transform(&mut positions, |p| world.transform_point(p));
+        for (layer, values) in &mut vertex_layers {
+            if layer.name == SC_LAYER_NORMALS && layer.component_count == 3 {
+                transform(values, |n| transform_normal(&world, n));
+            }
+        }
+    }
+    let face_layers: Vec<(&Layer<'_>, Vec<f64>)> = geometry
+        .face_stack
+        .layers
+        .iter()
+        .map(|layer| (layer, components(layer)))
+        .collect();
+    let corner_layers: Vec<(&Layer<'_>, Vec<f64>)> = geometry
+        .corner_stack
+        .attributes()
+        .map(|layer| (layer, components(layer)))
+        .collect();
+    for layer in &geometry.edge_stack.layers {
+        println!(
+            r#"Skipping the "{}" edge layer, which PLY cannot hold"#,
+            layer.name
+        );
+    }
+
+    // Vertices: positions, then every vertex layer.
+    let declared_vertex = declared(VERTEX)?;
+    let mut vertex = Vec::new();
+    for (axis, name) in ["x", "y", "z"].iter().enumerate() {
+        let type_ = declared_vertex
+            .iter()
+            .find(|property| property.name == *name)
+            .and_then(Property::scalar_type)
+            .unwrap_or_else(|| Scalar::for_layer(vertex_layer.data.type_()));
+        vertex.push((
+            Property::scalar(*name, type_),
+            Source::Layer(&positions, 3, axis),
+        ));
+    }
+    let others: Vec<&Property> = declared_vertex
+        .iter()
+        .filter(|property| !["x", "y", "z"].contains(&property.name.as_str()))
+        .collect();
+    vertex.extend(layer_sources(&vertex_layers, &others));
+    sort_declared(&mut vertex, &declared_vertex);
+
+    // Faces: vertex indices, then every face layer and per-corner uvs.
+    let face_count = geometry.polygons()?.count();
+    let declared_face = declared(FACE)?;
+    let mut face = Vec::new();
+    if face_count > 0 {
+        let mut max_vertices = 0;
+        for polygon in geometry.polygons()? {
+            max_vertices = max_vertices.max(polygon?.corners.len());
+        }
+        // The type of the length of a list of up to `length` values.
+        let count = |length: usize| {
+            if length <= u8::MAX as usize {
+                Scalar::Uint8
+            } else {
+                Scalar::Int32
+            }
+        };
+        let is_indices = |property: &Property| {
+            matches!(property.type_, PropertyType::List(..))
+                && (property.name == "vertex_indices" || property.name == "vertex_index")
+        };
+        let indices = declared_face
+            .iter()
+            .find(|property| is_indices(property))
+            .cloned()
+            .unwrap_or_else(|| {
+                Property::list("vertex_indices", count(max_vertices), Scalar::Int32)
+            });
+        face.push((indices, Source::Indices));
+
+        let others: Vec<&Property> = declared_face
+            .iter()
+            .filter(|property| !is_indices(property))
+            .collect();
+        face.extend(layer_sources(&face_layers, &others));
+
+        for (layer, values) in &corner_layers {
+            let name = if layer.name == SC_LAYER_NAME_UV0 {
+                TEXCOORD
+            } else {
+                &layer.name
+            };
+            let components = layer.component_count as usize;
+            let type_ = declared_face
+                .iter()
+                .find(|property| property.name == name)
+                .filter(|property| matches!(property.type_, PropertyType::List(..)))
+                .map(|property| property.type_)
+                .unwrap_or_else(|| {
+                    let item = Scalar::for_layer(layer.data.type_());
+                    PropertyType::List(count(max_vertices * components), item)
+                });
+            let property = Property {
+                name: name.to_string(),
+                type_,
+            };
+            face.push((property, Source::Corners(values, components)));
+        }
+        sort_declared(&mut face, &declared_face);
+    }
+
+    let vertex_count = positions.len() / 3;
+    let mut header = String::from("ply\n");
+    header += &format!("format {} 1.0\n", encoding_name(encoding));
+    header += &format!("element {} {}\n", VERTEX, vertex_count);
+    for (property, _) in &vertex {
+        header += &format!("property {}\n", property.declaration());
+    }
+    if face_count > 0 {
+        header += &format!("element {} {}\n", FACE, face_count);
+        for (property, _) in &face {
+            header += &format!("property {}\n", property.declaration());
+        }
+    }
+    header += "end_header\n";
+    out.write_all(header.as_bytes())?;
+
+    let mut writer = Writer {
+        out,
+        encoding,
+        first: true,
+    };
+    for item in 0..vertex_count {
+        for (property, source) in &vertex {
+            writer.property(property.type_, source, item)?;
+        }
+        writer.end()?;
+    }
+    for (item, polygon) in geometry.polygons()?.enumerate() {
+        let polygon = polygon?;
+        let corners = polygon.corners.clone();
+        for (property, source) in &face {
+            match (property.type_, source) {
+                (PropertyType::List(count, type_), Source::Indices) => {
+                    writer.scalar(count, corners.len() as f64)?;
+                    for vertex in polygon.vertices() {
+                        writer.scalar(type_, vertex as f64)?;
+                    }
+                }
+                (PropertyType::List(count, type_), Source::Corners(values, components)) => {
+                    let values = &values[corners.start * components..corners.end * components];
+                    writer.list(count, type_, values)?;
+                }
+                (type_, source) => writer.property(type_, source, item)?,
+            }
+        }
+        writer.end()?;
+    }
+    Ok(())
+}
+
+/// Pairs layers with the properties they are written as, using the declared
+/// properties for the layers they cover and made-up ones for the rest.
+fn layer_sources<'v>(
+    layers: &'v [(&Layer<'_>, Vec<f64>)],
+    declared: &[&Property],
+) -> Vec<(Property, Source<'v>)> {
+    let mut used = vec![false; layers.len()];
+    let mut sources = Vec::new();
+    for property in declared {
+        if let PropertyType::List(..) = property.type_ {
+            let found = layers
+                .iter()
+                .enumerate()
+                .position(|(index, (layer, _))| !used[index] && layer.name == property.name);
+            if let Some(index) = found {
+                used[index] = true;
+                let lengths_name = format!("{}{}", property.name, LIST_LENGTHS);
+                let lengths = layers.iter().enumerate().position(|(index, (layer, _))| {
+                    !used[index] && layer.name == lengths_name && layer.component_count == 1
+                });
+                if let Some(lengths) = lengths {
+                    used[lengths] = true;
+                }
+                let (layer, values) = &layers[index];
+                let lengths = lengths.map(|lengths| layers[lengths].1.as_slice());
+                let source = Source::List(values, layer.component_count as usize, lengths);
+                sources.push(((*property).clone(), source));
+            }
+        }
+    }
+    let scalars: Vec<&Property> = declared
+        .iter()
+        .copied()
+        .filter(|property| property.scalar_type().is_some())
+        .collect();
+    for (name, members) in group(&scalars) {
+        let found = layers.iter().enumerate().position(|(index, (layer, _))| {
+            !used[index] && layer.name == name && layer.component_count as usize == members.len()
+        });
+        if let Some(index) = found {
+            used[index] = true;
+            for (component, &member) in members.iter().enumerate() {
+                let source = Source::Layer(&layers[index].1, members.len(), component);
+                sources.push((scalars[member].clone(), source));
+            }
+        }
+    }
+    for ((layer, values), used) in layers.iter().zip(used) {
+        if used {
+            continue;
+        }
+        let components = layer.component_count as usize;
+        for (component, property) in layer_properties(layer).into_iter().enumerate() {
+            sources.push((property, Source::Layer(values, components, component)));
+        }
+    }
+    sources
+}
+
+/// Puts declared properties in the order of their declarations, followed by
+/// the others in the order they were added.
+fn sort_declared(properties: &mut [(Property, Source<'_>)], declared: &[Property]) {
+    properties.sort_by_key(|(property, _)| {
+        declared
+            .iter()
+            .position(|declared| declared.name == property.name)
+            .unwrap_or(usize::MAX)
+    });
+}
+
+/// The components of a layer, in order.
+fn components(layer: &Layer<'_>) -> Vec<f64> {
+    match &layer.data {
+        LayerData::Uint8(data) => data.iter().map(|&value| value as f64).collect(),
+        LayerData::Int32(data) => data.iter().map(|&value| value as f64).collect(),
+        LayerData::Float(data) => data.iter().map(|&value| value as f64).collect(),
+        LayerData::Double(data) => data.to_vec(),
+    }
+}
+
+fn transform(values: &mut [f64], f: impl Fn([f64; 3]) -> [f64; 3]) {
+    for value in values.chunks_exact_mut(3) {
+        value.copy_from_slice(&f([value[0], value[1], value[2]]));
+    }
+}
+
+fn encoding_name(encoding: Encoding) -> &'static str {
+    match encoding {
+        Encoding::Ascii => "ascii",
+        Encoding::BinaryLittleEndian => "binary_little_endian",
+        Encoding::BinaryBigEndian => "binary_big_endian",
+    }
+}
+
+struct Writer<'w, W> {
+    out: &'w mut W,
+    encoding: Encoding,
+    /// Whether nothing has been written on the current line of an ASCII file.
+    first: bool,
+}
+
+impl<W: Write> Writer<'_, W> {
+    fn scalar(&mut self, type_: Scalar, value: f64) -> Result<()> {
+        match self.encoding {
+            Encoding::Ascii => {
+                if !self.first {
+                    self.out.write_all(b" ")?;
+                }
+                self.first = false;
+                self.out.write_all(type_.format(value).as_bytes())?;
+            }
+            Encoding::BinaryLittleEndian | Encoding::BinaryBigEndian => {
+                let mut bytes = type_.encode(value);
+                let bytes = &mut bytes[..type_.size()];
+                if self.encoding == Encoding::BinaryBigEndian {
+                    bytes.reverse();
+                }
+                self.out.write_all(bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&mut self, count: Scalar, type_: Scalar, values: &[f64]) -> Result<()> {
+        self.scalar(count, values.len() as f64)?;
+        for &value in values {
+            self.scalar(type_, value)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the value of a property for an item of an element.
+    fn property(&mut self, type_: PropertyType, source: &Source<'_>, item: usize) -> Result<()> {
+        match (type_, source) {
+            (PropertyType::Scalar(type_), &Source::Layer(values, components, component)) => {
+                self.scalar(type_, values[item * components + component])
+            }
+            (PropertyType::List(count, type_), &Source::List(values, components, lengths)) => {
+                let length = lengths.map_or(components, |lengths| {
+                    (lengths[item] as usize).min(components)
+                });
+                let start = item * components;
+                self.list(count, type_, &values[start..start + length])
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Ends the line of an item of an element.
+    fn end(&mut self) -> Result<()> {
+        if self.encoding == Encoding::Ascii {
+            self.out.write_all(b"\n")?;
+            self.first = true;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    /// Every scalar type, values at the edges of their ranges, and lists
+    /// that end up in vertex, corner and face layers.
+    const FIXTURE: &str = "\
+ply
+format ascii 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property char tilt
+property short height
+property ushort depth
+property uint id
+property double weight
+property uchar red
+property uchar green
+property uchar blue
+property list uchar short bones
+element face 2
+property list uchar int vertex_indices
+property list uchar float texcoord
+property uint label
+property list uchar ushort corner_ids
+property list uchar float plane
+end_header
+0 0 0 -128 -32768 65535 4294967295 0.5 255 0 0 2 1 2
+1 0 0 127 32767 0 0 -1.25 0 255 0 2 3 4
+1 1 0 -1 -2 3 2147483648 0.001 0 0 255 2 5 6
+0 1 0.5 0 1 2 7 3.141592653589793 9 9 9 2 7 8
+3 0 1 2 6 0 0 1 0 1 1 4000000000 3 1 2 3 4 0 0 1 0
+3 0 2 3 6 0 0 1 1 0 1 5 3 4 5 6 4 0 0 1 -1
+";
+
+    /// Imports from a file, since that is what the importer reads. Each test
+    /// uses its own `name` so that they can run in parallel.
+    fn try_import(name: &str, data: &[u8]) -> Result<Hxa<'static>> {
+        let file_name = format!("hxa-conv-{}-{}.ply", process::id(), name);
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, data).unwrap();
+        let hxa = import(&path);
+        fs::remove_file(&path).unwrap();
+        hxa
+    }
+
+    fn import_bytes(name: &str, data: &[u8]) -> Hxa<'static> {
+        try_import(name, data).unwrap()
+    }
+
+    fn export_bytes(hxa: &Hxa<'_>, encoding: Encoding) -> Vec<u8> {
+        let mut out = Vec::new();
+        export(hxa, encoding, &mut out).unwrap();
+        out
+    }
+
+    fn geometry<'h>(hxa: &'h Hxa<'_>) -> &'h NodeGeometry<'h> {
+        match &hxa.nodes[0].content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    fn round_trip(name: &str, encoding: Encoding) {
+        let hxa = import_bytes(name, FIXTURE.as_bytes());
+        let written = export_bytes(&hxa, encoding);
+        let reread = import_bytes(name, &written);
+        assert_eq!(export_bytes(&reread, encoding), written);
+        let ascii = export_bytes(&reread, Encoding::Ascii);
+        assert_eq!(String::from_utf8(ascii).unwrap(), FIXTURE);
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        round_trip("ascii_round_trip", Encoding::Ascii);
+    }
+
+    #[test]
+    fn binary_little_endian_round_trip() {
+        round_trip(
+            "binary_little_endian_round_trip",
+            Encoding::BinaryLittleEndian,
+        );
+    }
+
+    #[test]
+    fn binary_big_endian_round_trip() {
+        round_trip("binary_big_endian_round_trip", Encoding::BinaryBigEndian);
+    }
+
+    #[test]
+    fn reads_properties_into_layers() {
+        let hxa = import_bytes("reads_properties_into_layers", FIXTURE.as_bytes());
+        let geometry = geometry(&hxa);
+        let layer = |stack: &hxa::LayerStack<'_>, name: &str| {
+            let layer = stack.get(name).unwrap();
+            (layer.data.type_(), layer.component_count, components(layer))
+        };
+        let vertices = &geometry.vertex_stack;
+        assert_eq!(
+            layer(vertices, "tilt"),
+            (LayerDataType::Int32, 1, vec![-128.0, 127.0, -1.0, 0.0])
+        );
+        assert_eq!(
+            layer(vertices, "depth"),
+            (LayerDataType::Int32, 1, vec![65535.0, 0.0, 3.0, 2.0])
+        );
+        // `uint` values above `i32::MAX` wrap around.
+        assert_eq!(
+            layer(vertices, "id"),
+            (
+                LayerDataType::Int32,
+                1,
+                vec![-1.0, 0.0, i32::MIN as f64, 7.0]
+            )
+        );
+        assert_eq!(layer(vertices, "weight").0, LayerDataType::Double);
+        assert_eq!(layer(vertices, SC_LAYER_COLOR).0, LayerDataType::Uint8);
+        assert_eq!(layer(vertices, SC_LAYER_COLOR).1, 3);
+        assert_eq!(
+            layer(vertices, "bones"),
+            (LayerDataType::Int32, 2, (1..=8).map(f64::from).collect())
+        );
+        let corners = &geometry.corner_stack;
+        assert_eq!(layer(corners, SC_LAYER_NAME_UV0).0, LayerDataType::Float);
+        assert_eq!(layer(corners, SC_LAYER_NAME_UV0).1, 2);
+        assert_eq!(
+            layer(corners, "corner_ids"),
+            (LayerDataType::Int32, 1, (1..=6).map(f64::from).collect())
+        );
+        let faces = &geometry.face_stack;
+        assert_eq!(
+            layer(faces, "label"),
+            (LayerDataType::Int32, 1, vec![-294967296.0, 5.0])
+        );
+        assert_eq!(
+            layer(faces, "plane"),
+            (
+                LayerDataType::Float,
+                4,
+                vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, -1.0]
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_lists_of_varying_length() {
+        let ply = "\
+ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property list uchar int ragged
+property list uchar float empty
+element face 2
+property list uchar int vertex_indices
+property list ushort int uneven
+end_header
+0 0 0 1 1 0
+1 0 0 2 1 2 0
+0 1 0 0 0
+3 0 1 2 2 5 6
+3 0 2 1 0
+";
+        let hxa = import_bytes("keeps_lists_of_varying_length", ply.as_bytes());
+        let geometry = geometry(&hxa);
+        let layer = |stack: &hxa::LayerStack<'_>, name: &str| {
+            let layer = stack.get(name).unwrap();
+            (layer.data.type_(), layer.component_count, components(layer))
+        };
+        let vertices = &geometry.vertex_stack;
+        assert_eq!(
+            layer(vertices, "ragged"),
+            (LayerDataType::Int32, 2, vec![1.0, 0.0, 1.0, 2.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            layer(vertices, "ragged_length"),
+            (LayerDataType::Uint8, 1, vec![1.0, 2.0, 0.0])
+        );
+        assert!(vertices.get("empty").is_none());
+        let faces = &geometry.face_stack;
+        assert_eq!(
+            layer(faces, "uneven"),
+            (LayerDataType::Int32, 2, vec![5.0, 6.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            layer(faces, "uneven_length"),
+            (LayerDataType::Int32, 1, vec![2.0, 0.0])
+        );
+        assert!(geometry.corner_stack.get("uneven").is_none());
+
+        // The lists are written back as they were, without the empty ones.
+        let written = String::from_utf8(export_bytes(&hxa, Encoding::Ascii)).unwrap();
+        assert_eq!(
+            written,
+            ply.replace("property list uchar float empty\n", "")
+                .replace(" 0\n1 0 0 2 1 2 0\n0 1 0 0 0\n", "\n1 0 0 2 1 2\n0 1 0 0\n")
+        );
+        let binary = export_bytes(&hxa, Encoding::BinaryLittleEndian);
+        let reread = import_bytes("keeps_lists_of_varying_length", &binary);
+        assert_eq!(export_bytes(&reread, Encoding::Ascii), written.as_bytes());
+    }
+
+    #[test]
+    fn rejects_lists_too_long_for_a_layer() {
+        let long = vec!["1"; 256].join(" ");
+        let ply = format!(
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+             property float z\nproperty list ushort int long\nend_header\n0 0 0 0\n0 0 0 256 {}\n",
+            long
+        );
+        let error = try_import("rejects_lists_too_long_for_a_layer", ply.as_bytes())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            r#"The PLY list "long" has a list of 256 values, but layers hold at most 255"#
+        );
+    }
+
+    #[test]
+    fn bounds_allocations_by_the_smallest_item() {
+        let element = Element {
+            name: VERTEX.to_string(),
+            count: usize::MAX,
+            properties: vec![
+                Property::scalar("x", Scalar::Float32),
+                Property::scalar("y", Scalar::Float64),
+                Property::list("list", Scalar::Uint16, Scalar::Float64),
+            ],
+        };
+        let data = [0; 100];
+        let mut reader = Reader {
+            data: &data,
+            position: 10,
+            encoding: Encoding::BinaryLittleEndian,
+        };
+        assert_eq!(reader.min_item_size(&element), 14);
+        assert_eq!(reader.capacity(element.count, 14), 6);
+        assert_eq!(reader.capacity(3, 14), 3);
+        assert_eq!(
+            reader.capacity(usize::MAX, reader.min_size(Scalar::Float64)),
+            11
+        );
+
+        reader.encoding = Encoding::Ascii;
+        assert_eq!(reader.min_item_size(&element), 3);
+        assert_eq!(reader.capacity(usize::MAX, 0), 90);
+        assert!(reader.element(&element).is_err());
+    }
+}
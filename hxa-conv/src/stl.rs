@@ -0,0 +1,642 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str;
+
+use hxa::{
+    GeometryBuilder, Hxa, HxaVersion, MetaBuilder, Node, NodeContent, NodeGeometry, Transform,
+    HC_BASE_VERTEX_LAYER_ID, SC_LAYER_NORMALS, SC_NAME,
+};
+
+use crate::wavefront::transform_normal;
+use crate::{Encoding, Result};
+
+/// Face layer holding the attribute byte count of each triangle of a binary
+/// STL file, which some programs use to store colours.
+pub const STL_ATTRIBUTE: &str = "stl_attribute";
+
+/// What binary STL files written by `hxa-conv` start with. It must not start
+/// with `solid`, since that is how ASCII files are told apart.
+const HEADER: &[u8] = b"Written by hxa-conv";
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+/// The byte order mark some editors put at the start of ASCII files.
+const BOM: &str = "\u{feff}";
+
+/// One `solid` of an ASCII file, or the whole of a binary file.
+#[derive(Default)]
+struct Solid {
+    name: Option<String>,
+    facets: Vec<Facet>,
+}
+
+#[derive(Default)]
+struct Facet {
+    normal: [f32; 3],
+    vertices: Vec<[f32; 3]>,
+    attribute: u16,
+}
+
+/// Reads an ASCII or binary STL file, making a geometry node out of every
+/// solid in it.
+///
+/// STL stores every triangle on its own, so vertices at the same position
+/// are welded together. Facet normals and attributes are kept in face layers
+/// unless they are all zero.
+pub fn import(path: &Path) -> Result<Hxa<'static>> {
+    let data = fs::read(path)?;
+    let solids = if is_binary(&data) {
+        vec![read_binary(&data)?]
+    } else {
+        read_ascii(str::from_utf8(&data)?.trim_start_matches(BOM))?
+    };
+    let nodes = solids
+        .into_iter()
+        .map(Solid::into_node)
+        .collect::<Result<_>>()?;
+    Ok(Hxa {
+        version: HxaVersion::LATEST,
+        nodes,
+    })
+}
+
+/// Binary files may start with `solid` as well, but then their size gives
+/// them away. ASCII files may have a byte order mark or whitespace before
+/// `solid`.
+fn is_binary(data: &[u8]) -> bool {
+    let text = data.strip_prefix(BOM.as_bytes()).unwrap_or(data);
+    let start = text
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    if !text[start..].starts_with(b"solid") {
+        return true;
+    }
+    let count = match data.get(HEADER_SIZE..HEADER_SIZE + 4) {
+        Some(count) => u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize,
+        None => return false,
+    };
+    count
+        .checked_mul(TRIANGLE_SIZE)
+        .and_then(|size| size.checked_add(HEADER_SIZE + 4))
+        == Some(data.len())
+}
+
+fn read_binary(data: &[u8]) -> Result<Solid> {
+    let count = data
+        .get(HEADER_SIZE..HEADER_SIZE + 4)
+        .ok_or("The STL file is too short to hold a header")?;
+    let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+    let triangles = &data[HEADER_SIZE + 4..];
+    if triangles.len() / TRIANGLE_SIZE < count {
+        return Err(format!("The STL file ends before its {} triangles", count).into());
+    }
+
+    let float = |bytes: &[u8], index: usize| {
+        let bytes = &bytes[index * 4..index * 4 + 4];
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    let vector = |bytes: &[u8], index: usize| {
+        [
+            float(bytes, index * 3),
+            float(bytes, index * 3 + 1),
+            float(bytes, index * 3 + 2),
+        ]
+    };
+    let facets = triangles
+        .chunks_exact(TRIANGLE_SIZE)
+        .take(count)
+        .map(|triangle| Facet {
+            normal: vector(triangle, 0),
+            vertices: vec![
+                vector(triangle, 1),
+                vector(triangle, 2),
+                vector(triangle, 3),
+            ],
+            attribute: u16::from_le_bytes([triangle[48], triangle[49]]),
+        })
+        .collect();
+    Ok(Solid { name: None, facets })
+}
+
+fn read_ascii(text: &str) -> Result<Vec<Solid>> {
+    let mut solids = Vec::new();
+    let mut solid: Option<Solid> = None;
+    let mut facet: Option<Facet> = None;
+    for (number, line) in text.lines().enumerate() {
+        let error = || format!("Invalid STL line {}: {:?}", number + 1, line.trim());
+        let mut words = line.split_whitespace();
+        let vector = |words: &mut str::SplitWhitespace<'_>| -> Result<[f32; 3]> {
+            let mut vector = [0.0; 3];
+            for component in &mut vector {
+                *component = words.next().ok_or_else(error)?.parse()?;
+            }
+            Ok(vector)
+        };
+        match words.next() {
+            Some("solid") => {
+                let name = words.collect::<Vec<_>>().join(" ");
+                solid = Some(Solid {
+                    name: if name.is_empty() { None } else { Some(name) },
+                    facets: Vec::new(),
+                });
+            }
+            Some("facet") => {
+                if words.next() != Some("normal") {
+                    return Err(error().into());
+                }
+                facet = Some(Facet {
+                    normal: vector(&mut words)?,
+                    ..Facet::default()
+                });
+            }
+            Some("vertex") => {
+                let vertex = vector(&mut words)?;
+                facet.as_mut().ok_or_else(error)?.vertices.push(vertex);
+            }
+            Some("endfacet") => {
+                let facet = facet.take().ok_or_else(error)?;
+                solid.as_mut().ok_or_else(error)?.facets.push(facet);
+            }
+            Some("endsolid") => solids.push(solid.take().ok_or_else(error)?),
+            Some("outer") | Some("endloop") | None => {}
+            Some(_) => return Err(error().into()),
+        }
+    }
+    if solid.is_some() {
+        return Err("The STL file ends in the middle of a solid".into());
+    }
+    Ok(solids)
+}
+
+impl Solid {
+    fn into_node(self) -> Result<Node<'static>> {
+        let mut welded = HashMap::new();
+        let mut positions = Vec::new();
+        let mut polygons = Vec::with_capacity(self.facets.len());
+        let mut normals = Vec::with_capacity(self.facets.len());
+        let mut attributes = Vec::with_capacity(self.facets.len());
+        for (index, facet) in self.facets.iter().enumerate() {
+            if facet.vertices.len() < 3 {
+                println!(
+                    "Skipping facet {}, which has only {} vertices",
+                    index,
+                    facet.vertices.len()
+                );
+                continue;
+            }
+            let polygon: Vec<u32> = facet
+                .vertices
+                .iter()
+                .map(|&vertex| {
+                    // Adding zero turns -0.0 into 0.0, so that both weld.
+                    let key = vertex.map(|component| (component + 0.0).to_bits());
+                    *welded.entry(key).or_insert_with(|| {
+                        positions.push(vertex);
+                        positions.len() as u32 - 1
+                    })
+                })
+                .collect();
+            polygons.push(polygon);
+            normals.push(facet.normal);
+            attributes.push(facet.attribute as i32);
+        }
+
+        let mut builder =
+            GeometryBuilder::new(&positions).polygons(polygons.iter().map(Vec::as_slice));
+        if normals.iter().any(|&normal| normal != [0.0; 3]) {
+            builder = builder.face_layer(SC_LAYER_NORMALS, &normals);
+        }
+        if attributes.iter().any(|&attribute| attribute != 0) {
+            builder = builder.face_layer(STL_ATTRIBUTE, &attributes);
+        }
+        let mut metadata = MetaBuilder::new();
+        if let Some(name) = &self.name {
+            metadata = metadata.text(SC_NAME, name);
+        }
+        Ok(builder.metadata(metadata).build()?)
+    }
+
+    /// Triangulates a geometry node placed with `world`.
+    ///
+    /// Triangles take the `normal` of the face they were cut from if it has
+    /// one, and get the normal of their own plane otherwise.
+    fn from_geometry(
+        node: &Node<'_>,
+        geometry: &NodeGeometry<'_>,
+        world: Transform,
+    ) -> Result<Self> {
+        let triangulation = geometry.triangulate()?;
+        let positions: Vec<[f64; 3]> = geometry.vertex_stack.layers[HC_BASE_VERTEX_LAYER_ID]
+            .to_elements::<f64, 3>()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|position| world.transform_point(position))
+            .collect();
+        let normals = geometry
+            .face_stack
+            .get(SC_LAYER_NORMALS)
+            .and_then(|layer| layer.to_elements::<f64, 3>());
+        let attributes = geometry
+            .face_stack
+            .get(STL_ATTRIBUTE)
+            .and_then(|layer| layer.to_elements::<i32, 1>());
+
+        let mut facets = Vec::with_capacity(triangulation.triangles.len());
+        for (triangle, &polygon) in triangulation.triangles.iter().zip(&triangulation.polygons) {
+            let vertices = triangle.map(|vertex| positions[vertex as usize]);
+            let normal = normals
+                .as_ref()
+                .map(|normals| normals[polygon as usize])
+                .filter(|&normal| normal != [0.0; 3])
+                .map(|normal| transform_normal(&world, normal))
+                .unwrap_or_else(|| plane_normal(vertices));
+            facets.push(Facet {
+                normal: normal.map(|component| component as f32),
+                vertices: vertices
+                    .iter()
+                    .map(|vertex| vertex.map(|component| component as f32))
+                    .collect(),
+                attribute: attributes
+                    .as_ref()
+                    .map_or(0, |attributes| attributes[polygon as usize][0] as u16),
+            });
+        }
+        Ok(Self {
+            name: node.meta_text(SC_NAME).map(String::from),
+            facets,
+        })
+    }
+}
+
+/// The unit normal of a counter-clockwise triangle, or zero if it has no
+/// area.
+fn plane_normal([a, b, c]: [[f64; 3]; 3]) -> [f64; 3] {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let normal = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let length = normal
+        .iter()
+        .map(|component| component * component)
+        .sum::<f64>()
+        .sqrt();
+    if length > 0.0 {
+        normal.map(|component| component / length)
+    } else {
+        [0.0; 3]
+    }
+}
+
+/// Writes every geometry node of `hxa` as a triangulated STL solid, placed
+/// using its transforms like the OBJ exporter does.
+///
+/// ASCII files get a `solid` per node, while binary files hold a single list
+/// of triangles.
+pub fn export(hxa: &Hxa<'_>, encoding: Encoding, out: &mut impl Write) -> Result<()> {
+    if encoding == Encoding::BinaryBigEndian {
+        return Err("Binary STL files are always little-endian".into());
+    }
+    let mut solids = Vec::new();
+    for instance in hxa.instances()? {
+        let node = &hxa.nodes[instance.node];
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => {
+                solids.push(Solid::from_geometry(node, geometry, instance.world)?)
+            }
+            Some(NodeContent::Image(_)) => println!(
+                "Skipping image node {}, since images cannot be included in an STL file",
+                instance.node
+            ),
+            None => {}
+        }
+    }
+    match encoding {
+        Encoding::Ascii => write_ascii(&solids, out),
+        _ => write_binary(&solids, out),
+    }
+}
+
+fn write_ascii(solids: &[Solid], out: &mut impl Write) -> Result<()> {
+    let join = |vector: &[f32; 3]| format!("{:e} {:e} {:e}", vector[0], vector[1], vector[2]);
+    for solid in solids {
+        let name = match &solid.name {
+            Some(name) => format!(" {}", name),
+            None => String::new(),
+        };
+        writeln!(out, "solid{}", name)?;
+        for facet in &solid.facets {
+            writeln!(out, "  facet normal {}", join(&facet.normal))?;
+            writeln!(out, "    outer loop")?;
+            for vertex in &facet.vertices {
+                writeln!(out, "      vertex {}", join(vertex))?;
+            }
+            writeln!(out, "    endloop")?;
+            writeln!(out, "  endfacet")?;
+        }
+        writeln!(out, "endsolid{}", name)?;
+    }
+    Ok(())
+}
+
+fn write_binary(solids: &[Solid], out: &mut impl Write) -> Result<()> {
+    let mut header = [0; HEADER_SIZE];
+    header[..HEADER.len()].copy_from_slice(HEADER);
+    out.write_all(&header)?;
+
+    let count: usize = solids.iter().map(|solid| solid.facets.len()).sum();
+    let count = u32::try_from(count).map_err(|_| "Too many triangles for a binary STL file")?;
+    out.write_all(&count.to_le_bytes())?;
+    for facet in solids.iter().flat_map(|solid| &solid.facets) {
+        for vector in std::iter::once(&facet.normal).chain(&facet.vertices) {
+            for component in vector {
+                out.write_all(&component.to_le_bytes())?;
+            }
+        }
+        out.write_all(&facet.attribute.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use hxa::NodeType;
+
+    use super::*;
+
+    /// Imports from a file, since that is what the importer reads. Each test
+    /// uses its own `name` so that they can run in parallel.
+    fn try_import(name: &str, data: &[u8]) -> Result<Hxa<'static>> {
+        let file_name = format!("hxa-conv-{}-{}.stl", process::id(), name);
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, data).unwrap();
+        let hxa = import(&path);
+        fs::remove_file(&path).unwrap();
+        hxa
+    }
+
+    fn import_bytes(name: &str, data: &[u8]) -> Hxa<'static> {
+        try_import(name, data).unwrap()
+    }
+
+    fn export_bytes(nodes: Vec<Node<'static>>, encoding: Encoding) -> Vec<u8> {
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes,
+        };
+        let mut out = Vec::new();
+        export(&hxa, encoding, &mut out).unwrap();
+        out
+    }
+
+    fn geometry<'n>(node: &'n Node<'static>) -> &'n NodeGeometry<'static> {
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    fn positions(node: &Node<'static>) -> Vec<[f32; 3]> {
+        let layer = &geometry(node).vertex_stack.layers[HC_BASE_VERTEX_LAYER_ID];
+        layer.as_elements::<f32, 3>().unwrap().to_vec()
+    }
+
+    fn polygons(node: &Node<'static>) -> Vec<Vec<u32>> {
+        geometry(node)
+            .polygons()
+            .unwrap()
+            .map(|polygon| polygon.unwrap().vertices().collect())
+            .collect()
+    }
+
+    fn face_layer(node: &Node<'static>, name: &str) -> Option<Vec<f64>> {
+        let layer = geometry(node).face_stack.get(name)?;
+        Some(match layer.component_count {
+            1 => layer.to_elements::<f64, 1>()?.concat(),
+            _ => layer.to_elements::<f64, 3>()?.concat(),
+        })
+    }
+
+    fn square() -> GeometryBuilder {
+        GeometryBuilder::new(&[
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ])
+        .polygon(&[0, 1, 2, 3])
+    }
+
+    #[test]
+    fn writes_a_solid_per_node() {
+        let triangle =
+            GeometryBuilder::new(&[[0.0f32, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 0.5, 0.0]])
+                .triangles(&[[0, 1, 2]]);
+        let named = triangle
+            .clone()
+            .metadata(MetaBuilder::new().text(SC_NAME, "tri"));
+        let out = export_bytes(
+            vec![named.build().unwrap(), triangle.build().unwrap()],
+            Encoding::Ascii,
+        );
+        let facet = "  facet normal 0e0 0e0 1e0
+    outer loop
+      vertex 0e0 0e0 0e0
+      vertex 2e0 0e0 0e0
+      vertex 0e0 5e-1 0e0
+    endloop
+  endfacet
+";
+        let expected = format!("solid tri\n{0}endsolid tri\nsolid\n{0}endsolid\n", facet);
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let hxa = import_bytes("writes_a_solid_per_node", expected.as_bytes());
+        let names: Vec<_> = hxa
+            .nodes
+            .iter()
+            .map(|node| node.meta_text(SC_NAME))
+            .collect();
+        assert_eq!(names, [Some("tri"), None]);
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        let node = square()
+            .metadata(MetaBuilder::new().text(SC_NAME, "square"))
+            .build()
+            .unwrap();
+        let out = export_bytes(vec![node], Encoding::Ascii);
+        let hxa = import_bytes("ascii_round_trip", &out);
+        let node = &hxa.nodes[0];
+        assert_eq!(node.meta_text(SC_NAME), Some("square"));
+        // The corners shared by both triangles are welded back together.
+        let mut positions = positions(node);
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            positions,
+            [
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0]
+            ]
+        );
+        assert_eq!(polygons(node).len(), 2);
+        assert_eq!(
+            face_layer(node, SC_LAYER_NORMALS),
+            Some(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0])
+        );
+        assert_eq!(face_layer(node, STL_ATTRIBUTE), None);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let node = square()
+            .face_layer(STL_ATTRIBUTE, &[7i32])
+            .face_layer(SC_LAYER_NORMALS, &[[0.0f32, 0.0, -1.0]])
+            .build()
+            .unwrap();
+        let out = export_bytes(vec![node], Encoding::BinaryLittleEndian);
+        assert_eq!(out.len(), HEADER_SIZE + 4 + 2 * TRIANGLE_SIZE);
+        assert!(out.starts_with(HEADER));
+        assert_eq!(out[HEADER_SIZE..HEADER_SIZE + 4], 2u32.to_le_bytes());
+
+        let hxa = import_bytes("binary_round_trip", &out);
+        let node = &hxa.nodes[0];
+        assert_eq!(node.meta_text(SC_NAME), None);
+        assert_eq!(positions(node).len(), 4);
+        assert_eq!(face_layer(node, STL_ATTRIBUTE), Some(vec![7.0, 7.0]));
+        assert_eq!(
+            face_layer(node, SC_LAYER_NORMALS),
+            Some(vec![0.0, 0.0, -1.0, 0.0, 0.0, -1.0])
+        );
+    }
+
+    #[test]
+    fn tells_binary_files_starting_with_solid_apart() {
+        let mut out = export_bytes(
+            vec![square().build().unwrap()],
+            Encoding::BinaryLittleEndian,
+        );
+        out[..HEADER.len()].fill(0);
+        out[..12].copy_from_slice(b"solid square");
+        assert!(is_binary(&out));
+        let hxa = import_bytes("tells_binary_files_starting_with_solid_apart", &out);
+        assert_eq!(polygons(&hxa.nodes[0]).len(), 2);
+
+        // A size that doesn't match the triangle count means it's text.
+        out.push(0);
+        assert!(!is_binary(&out));
+        assert!(!is_binary(b"solid"));
+        assert!(is_binary(b"slid"));
+    }
+
+    #[test]
+    fn reads_ascii_files_with_leading_whitespace_or_a_bom() {
+        let text = String::from_utf8(export_bytes(
+            vec![square().build().unwrap()],
+            Encoding::Ascii,
+        ))
+        .unwrap();
+        for (i, prefix) in ["\n  \t", "\u{feff}", "\u{feff}\r\n"].iter().enumerate() {
+            let data = format!("{}{}", prefix, text);
+            assert!(!is_binary(data.as_bytes()), "{:?}", prefix);
+            let name = format!("reads_ascii_files_with_leading_whitespace_or_a_bom{}", i);
+            let hxa = import_bytes(&name, data.as_bytes());
+            assert_eq!(polygons(&hxa.nodes[0]).len(), 2);
+        }
+    }
+
+    #[test]
+    fn welds_vertices_at_the_same_position() {
+        let stl = "\
+solid zeros
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex -0 -0 0
+      vertex 0 1 -0
+      vertex -1 0 0
+    endloop
+  endfacet
+endsolid zeros
+";
+        let hxa = import_bytes("welds_vertices_at_the_same_position", stl.as_bytes());
+        let node = &hxa.nodes[0];
+        assert_eq!(
+            positions(node),
+            [
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [-1.0, 0.0, 0.0]
+            ]
+        );
+        assert_eq!(polygons(node), [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(
+            face_layer(node, SC_LAYER_NORMALS),
+            Some(vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn triangulates_polygons() {
+        let pentagon = GeometryBuilder::new(&[
+            [0.0f32, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [3.0, 1.0, 0.0],
+            [1.0, 2.0, 0.0],
+            [-1.0, 1.0, 0.0],
+        ])
+        .polygon(&[0, 1, 2, 3, 4])
+        .triangles(&[[0, 2, 1]])
+        .build()
+        .unwrap();
+        let out = export_bytes(vec![pentagon], Encoding::BinaryLittleEndian);
+        assert_eq!(out[HEADER_SIZE..HEADER_SIZE + 4], 4u32.to_le_bytes());
+
+        let hxa = import_bytes("triangulates_polygons", &out);
+        let node = &hxa.nodes[0];
+        assert_eq!(positions(node).len(), 5);
+        assert!(polygons(node).iter().all(|polygon| polygon.len() == 3));
+        // Each triangle gets the normal of its own plane.
+        let normals = face_layer(node, SC_LAYER_NORMALS).unwrap();
+        assert_eq!(normals[..9], [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(normals[9..], [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn writes_nothing_but_geometry() {
+        let meta = Node {
+            type_: NodeType::Meta,
+            metadata: Vec::new(),
+            content: None,
+        };
+        let out = export_bytes(vec![meta], Encoding::BinaryLittleEndian);
+        assert_eq!(out.len(), HEADER_SIZE + 4);
+        assert_eq!(out[HEADER_SIZE..], [0; 4]);
+
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![square().build().unwrap()],
+        };
+        let error = export(&hxa, Encoding::BinaryBigEndian, &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Binary STL files are always little-endian"
+        );
+    }
+}
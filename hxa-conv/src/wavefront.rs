@@ -0,0 +1,860 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use hxa::{
+    GeometryBuilder, Hxa, HxaVersion, Layer, LayerData, MetaBuilder, Node, NodeContent,
+    NodeGeometry, Transform, SC_LAYER_MATERIAL_ID, SC_LAYER_NAME_UV0, SC_LAYER_NORMALS, SC_NAME,
+};
+use obj::{Group, Obj, ObjData};
+
+use crate::Result;
+
+/// Node metadata holding the names of the materials used by the `material`
+/// face layer, as `Text` entries named after each material index.
+///
+/// OBJ files refer to materials by name while HxA only stores indices, so
+/// this is how names survive a round trip through HxA.
+pub const MATERIAL_NAMES: &str = "material_names";
+
+/// The name the `obj` crate gives to objects and groups that aren't named in
+/// the file.
+const DEFAULT_NAME: &str = "default";
+
+/// Reads an OBJ file, making a geometry node out of every group.
+///
+/// Groups that only differ by material are merged back into one node, with a
+/// `material` face layer telling their faces apart.
+pub fn import(path: &Path) -> Result<Hxa<'static>> {
+    let obj = Obj::load(path)?;
+    let mut nodes = Vec::new();
+    for object in &obj.data.objects {
+        let mut groups = object.groups.iter().peekable();
+        while let Some(first) = groups.next() {
+            let mut merged = vec![first];
+            while let Some(group) = groups.next_if(|group| group.index > 0) {
+                merged.push(group);
+            }
+
+            let name = if first.name != DEFAULT_NAME {
+                Some(first.name.as_str())
+            } else if object.name != DEFAULT_NAME {
+                Some(object.name.as_str())
+            } else {
+                None
+            };
+            if let Some(node) = import_groups(&obj.data, name, &merged)? {
+                nodes.push(node);
+            }
+        }
+    }
+    Ok(Hxa {
+        version: HxaVersion::LATEST,
+        nodes,
+    })
+}
+
+fn import_groups(
+    data: &ObjData,
+    name: Option<&str>,
+    groups: &[&Group],
+) -> Result<Option<Node<'static>>> {
+    // OBJ indices are shared by the whole file, so only keep the positions
+    // this node uses.
+    let mut vertices = HashMap::new();
+    let mut polygons = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut has_uvs = false;
+    let mut has_normals = false;
+    let mut materials = Vec::new();
+    let mut material_names: Vec<&str> = Vec::new();
+
+    for group in groups {
+        let material = group.material.as_ref().map(|material| match material {
+            obj::ObjMaterial::Ref(name) => name.as_str(),
+            obj::ObjMaterial::Mtl(material) => material.name.as_str(),
+        });
+        let material = match material {
+            Some(material) => match material_names.iter().position(|&name| name == material) {
+                Some(id) => id as i32,
+                None => {
+                    material_names.push(material);
+                    material_names.len() as i32 - 1
+                }
+            },
+            None => -1,
+        };
+
+        for polygon in &group.polys {
+            if polygon.0.len() < 3 {
+                println!(
+                    "Skipping a face with only {} vertices in {}",
+                    polygon.0.len(),
+                    name.unwrap_or("an unnamed group")
+                );
+                continue;
+            }
+            let face = polygon
+                .0
+                .iter()
+                .map(|obj::IndexTuple(position, uv, normal)| {
+                    let mut indices = (position + 1).to_string();
+                    for index in [uv, normal] {
+                        indices += "/";
+                        if let Some(index) = index {
+                            indices += &(index + 1).to_string();
+                        }
+                    }
+                    indices.trim_end_matches('/').to_owned()
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let out_of_range = |kind: &str, index: usize, count: usize| {
+                format!(
+                    "Face \"f {}\" in {} refers to {} {}, but there are only {}",
+                    face,
+                    name.unwrap_or("an unnamed group"),
+                    kind,
+                    index + 1,
+                    count
+                )
+            };
+            let mut indices = Vec::with_capacity(polygon.0.len());
+            for &obj::IndexTuple(position, uv, normal) in &polygon.0 {
+                if position >= data.position.len() {
+                    let error = out_of_range("position", position, data.position.len());
+                    return Err(error.into());
+                }
+                vertices.insert(position, 0);
+                indices.push(position);
+
+                has_uvs |= uv.is_some();
+                has_normals |= normal.is_some();
+                uvs.push(match uv {
+                    Some(uv) => *data.texture.get(uv).ok_or_else(|| {
+                        out_of_range("texture coordinate", uv, data.texture.len())
+                    })?,
+                    None => [0.0; 2],
+                });
+                normals.push(match normal {
+                    Some(normal) => *data
+                        .normal
+                        .get(normal)
+                        .ok_or_else(|| out_of_range("normal", normal, data.normal.len()))?,
+                    None => [0.0; 3],
+                });
+            }
+            polygons.push(indices);
+            materials.push(material);
+        }
+    }
+
+    if polygons.is_empty() {
+        return Ok(None);
+    }
+
+    // Keep the used positions in the order they appear in the file.
+    let mut used: Vec<usize> = vertices.keys().copied().collect();
+    used.sort_unstable();
+    let positions: Vec<[f32; 3]> = used
+        .iter()
+        .map(|&position| data.position[position])
+        .collect();
+    for (index, position) in used.into_iter().enumerate() {
+        vertices.insert(position, index as u32);
+    }
+    let polygons: Vec<Vec<u32>> = polygons
+        .iter()
+        .map(|polygon: &Vec<usize>| polygon.iter().map(|position| vertices[position]).collect())
+        .collect();
+
+    let mut metadata = MetaBuilder::new();
+    if let Some(name) = name {
+        metadata = metadata.text(SC_NAME, name);
+    }
+    let mut builder = GeometryBuilder::new(&positions).polygons(polygons.iter().map(Vec::as_slice));
+    if has_uvs {
+        builder = builder.corner_layer(SC_LAYER_NAME_UV0, &uvs);
+    }
+    if has_normals {
+        builder = builder.corner_layer(SC_LAYER_NORMALS, &normals);
+    }
+    if !material_names.is_empty() {
+        builder = builder.face_layer(SC_LAYER_MATERIAL_ID, &materials);
+        let names = material_names
+            .iter()
+            .enumerate()
+            .fold(MetaBuilder::new(), |names, (id, name)| {
+                names.text(&id.to_string(), name)
+            });
+        metadata = metadata.meta(MATERIAL_NAMES, names);
+    }
+    Ok(Some(builder.metadata(metadata).build()?))
+}
+
+/// Writes every geometry node of `hxa` as an OBJ object.
+///
+/// Nodes referenced from the metadata of other nodes are placed using their
+/// transforms, once for every path leading to them.
+///
+/// The materials used by the `material` face layers are declared in
+/// `materials`, an MTL file that the OBJ file refers to as `material_library`.
+/// Nothing is written to `materials` if no faces have a material.
+pub fn export(
+    hxa: &Hxa<'_>,
+    material_library: &str,
+    out: &mut impl Write,
+    materials: &mut impl Write,
+) -> Result<()> {
+    let mut writer = ObjWriter {
+        out,
+        offsets: [0; 3],
+        material_library,
+        materials,
+        material_names: HashSet::new(),
+    };
+    for instance in hxa.instances()? {
+        let node = &hxa.nodes[instance.node];
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => {
+                let name = match node.meta_text(SC_NAME) {
+                    Some(name) => name.to_string(),
+                    None => format!("node{}", instance.node),
+                };
+                writer.geometry(&name, node, geometry, instance.world)?;
+            }
+            Some(NodeContent::Image(_)) => {
+                println!(
+                    "Skipping image node {}, since images cannot be included in an OBJ file",
+                    instance.node
+                );
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+struct ObjWriter<'w, W, M> {
+    out: &'w mut W,
+    /// The number of `v`, `vt` and `vn` lines written so far, since OBJ
+    /// indices count from the start of the file.
+    offsets: [usize; 3],
+    material_library: &'w str,
+    materials: &'w mut M,
+    /// The materials declared in `materials` so far.
+    material_names: HashSet<String>,
+}
+
+impl<W: Write, M: Write> ObjWriter<'_, W, M> {
+    fn geometry(
+        &mut self,
+        name: &str,
+        node: &Node<'_>,
+        geometry: &NodeGeometry<'_>,
+        world: Transform,
+    ) -> Result<()> {
+        writeln!(self.out, "o {}", name)?;
+        writeln!(self.out, "g {}", name)?;
+
+        let vertex_layer = &geometry.vertex_stack.layers[hxa::HC_BASE_VERTEX_LAYER_ID];
+        let vertices = if world == Transform::IDENTITY {
+            format_elements::<3>(vertex_layer)
+        } else {
+            let positions = vertex_layer.to_elements::<f64, 3>().unwrap_or_default();
+            positions
+                .into_iter()
+                .map(|position| join(&world.transform_point(position)))
+                .collect()
+        };
+        for vertex in &vertices {
+            writeln!(self.out, "v {}", vertex)?;
+        }
+
+        let uvs = Attribute::new(geometry, SC_LAYER_NAME_UV0, 2, format_elements::<2>);
+        let normals = Attribute::new(geometry, SC_LAYER_NORMALS, 3, |layer| {
+            if world == Transform::IDENTITY {
+                return format_elements::<3>(layer);
+            }
+            let normals = layer.to_elements::<f64, 3>().unwrap_or_default();
+            normals
+                .into_iter()
+                .map(|normal| join(&transform_normal(&world, normal)))
+                .collect()
+        });
+        let uv_indices = self.attribute("vt", uvs.as_ref())?;
+        let normal_indices = self.attribute("vn", normals.as_ref())?;
+
+        let materials = geometry
+            .face_stack
+            .get(SC_LAYER_MATERIAL_ID)
+            .filter(|layer| layer.component_count == 1)
+            .and_then(|layer| layer.to_elements::<i32, 1>());
+        let mut material = None;
+
+        for (face, polygon) in geometry.polygons()?.enumerate() {
+            let polygon = polygon?;
+            if let Some(materials) = &materials {
+                let id = materials.get(face).map(|[id]| *id);
+                if id != material {
+                    material = id;
+                    if let Some(id) = id.filter(|&id| id >= 0) {
+                        let name = material_name(node, id);
+                        self.declare_material(&name)?;
+                        writeln!(self.out, "usemtl {}", name)?;
+                    }
+                }
+            }
+
+            write!(self.out, "f")?;
+            for (corner, vertex) in polygon.corners.clone().zip(polygon.vertices()) {
+                write!(self.out, " {}", self.offsets[0] + vertex as usize + 1)?;
+                let uv = uv_indices
+                    .as_ref()
+                    .map(|indices| indices.index(corner, vertex));
+                let normal = normal_indices
+                    .as_ref()
+                    .map(|indices| indices.index(corner, vertex));
+                match (uv, normal) {
+                    (Some(uv), Some(normal)) => write!(
+                        self.out,
+                        "/{}/{}",
+                        self.offsets[1] + uv + 1,
+                        self.offsets[2] + normal + 1
+                    )?,
+                    (Some(uv), None) => write!(self.out, "/{}", self.offsets[1] + uv + 1)?,
+                    (None, Some(normal)) => write!(self.out, "//{}", self.offsets[2] + normal + 1)?,
+                    (None, None) => {}
+                }
+            }
+            writeln!(self.out)?;
+        }
+
+        self.offsets[0] += vertices.len();
+        self.offsets[1] += uv_indices.map_or(0, |indices| indices.count);
+        self.offsets[2] += normal_indices.map_or(0, |indices| indices.count);
+        Ok(())
+    }
+
+    /// Adds a material to the MTL file the first time it is used, referring
+    /// to the file before the first material.
+    fn declare_material(&mut self, name: &str) -> Result<()> {
+        if self.material_names.is_empty() {
+            writeln!(self.out, "mtllib {}", self.material_library)?;
+        }
+        if self.material_names.insert(name.to_string()) {
+            writeln!(self.materials, "newmtl {}", name)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the distinct values of an attribute, returning where to find
+    /// the value of each corner.
+    fn attribute(
+        &mut self,
+        keyword: &str,
+        attribute: Option<&Attribute>,
+    ) -> Result<Option<Indices>> {
+        let attribute = match attribute {
+            Some(attribute) => attribute,
+            None => return Ok(None),
+        };
+        let mut distinct = HashMap::new();
+        let mut indices = Vec::with_capacity(attribute.values.len());
+        for value in &attribute.values {
+            let next = distinct.len();
+            let index = *distinct.entry(value).or_insert_with(|| next);
+            if index == next {
+                writeln!(self.out, "{} {}", keyword, value)?;
+            }
+            indices.push(index);
+        }
+        Ok(Some(Indices {
+            per_vertex: attribute.per_vertex,
+            count: distinct.len(),
+            indices,
+        }))
+    }
+}
+
+/// A `uv` or `normal` layer, formatted the way it is written.
+struct Attribute {
+    /// Whether the layer is in the vertex stack rather than the corner stack.
+    per_vertex: bool,
+    values: Vec<String>,
+}
+
+impl Attribute {
+    /// Looks for the layer in the corner stack first, then the vertex stack.
+    fn new(
+        geometry: &NodeGeometry<'_>,
+        name: &str,
+        components: u8,
+        format: impl Fn(&Layer<'_>) -> Vec<String>,
+    ) -> Option<Self> {
+        let corner = geometry.corner_stack.get(name).map(|layer| (false, layer));
+        let vertex = geometry.vertex_stack.get(name).map(|layer| (true, layer));
+        let (per_vertex, layer) = corner.or(vertex)?;
+        if layer.component_count != components {
+            println!(
+                r#"Skipping the "{}" layer, which has {} components instead of {}"#,
+                name, layer.component_count, components
+            );
+            return None;
+        }
+        Some(Self {
+            per_vertex,
+            values: format(layer),
+        })
+    }
+}
+
+struct Indices {
+    per_vertex: bool,
+    /// The number of distinct values written.
+    count: usize,
+    indices: Vec<usize>,
+}
+
+impl Indices {
+    fn index(&self, corner: usize, vertex: u32) -> usize {
+        if self.per_vertex {
+            self.indices[vertex as usize]
+        } else {
+            self.indices[corner]
+        }
+    }
+}
+
+fn material_name(node: &Node<'_>, id: i32) -> String {
+    match node.meta_text(&format!("{}/{}", MATERIAL_NAMES, id)) {
+        Some(name) => name.to_string(),
+        None => format!("material{}", id),
+    }
+}
+
+/// Formats each element of a layer with the precision it is stored in.
+pub(crate) fn format_elements<const N: usize>(layer: &Layer<'_>) -> Vec<String> {
+    match &layer.data {
+        LayerData::Float(_) => layer
+            .as_elements::<f32, N>()
+            .map(|elements| elements.iter().map(|element| join(element)).collect()),
+        _ => layer
+            .to_elements::<f64, N>()
+            .map(|elements| elements.iter().map(|element| join(element)).collect()),
+    }
+    .unwrap_or_default()
+}
+
+pub(crate) fn join<T: ToString>(components: &[T]) -> String {
+    components
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Transforms a normal by the inverse transpose of `world`, keeping its
+/// length.
+pub(crate) fn transform_normal(world: &Transform, normal: [f64; 3]) -> [f64; 3] {
+    let c = &world.columns;
+    let m = |column: usize, row: usize| c[column][row];
+    // The cofactor matrix is the inverse transpose scaled by the determinant.
+    // The renormalisation below takes care of the scale, but not of the sign.
+    let cofactor = |column: usize, row: usize| {
+        let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        m(c0, r0) * m(c1, r1) - m(c1, r0) * m(c0, r1)
+    };
+    let determinant: f64 = (0..3).map(|k| m(k, 0) * cofactor(k, 0)).sum();
+    let mut transformed = [0.0; 3];
+    for (row, value) in transformed.iter_mut().enumerate() {
+        *value = (0..3).map(|k| cofactor(k, row) * normal[k]).sum::<f64>() * determinant.signum();
+    }
+    let length = transformed.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let original = normal.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if length > 0.0 {
+        transformed.iter_mut().for_each(|x| *x *= original / length);
+    }
+    transformed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use hxa::{NodeType, HC_BASE_VERTEX_LAYER_ID};
+
+    use super::*;
+
+    /// Two triangles sharing their diagonal, with a material each.
+    fn quad() -> Node<'static> {
+        GeometryBuilder::new(&[
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ])
+        .triangles(&[[0, 1, 2], [0, 2, 3]])
+        .corner_layer(
+            SC_LAYER_NAME_UV0,
+            &[
+                [0.0f32, 0.0],
+                [1.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 1.0],
+            ],
+        )
+        .vertex_layer(SC_LAYER_NORMALS, &[[0.0f32, 0.0, 1.0]; 4])
+        .face_layer(SC_LAYER_MATERIAL_ID, &[0i32, 1])
+        .metadata(MetaBuilder::new().text(SC_NAME, "quad").meta(
+            MATERIAL_NAMES,
+            MetaBuilder::new().text("0", "red").text("1", "blue"),
+        ))
+        .build()
+        .unwrap()
+    }
+
+    fn meta(metadata: MetaBuilder) -> Node<'static> {
+        Node {
+            type_: NodeType::Meta,
+            metadata: metadata.build(),
+            content: None,
+        }
+    }
+
+    fn export_string(nodes: Vec<Node<'static>>) -> (String, String) {
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes,
+        };
+        let mut out = Vec::new();
+        let mut materials = Vec::new();
+        export(&hxa, "scene.mtl", &mut out, &mut materials).unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(materials).unwrap(),
+        )
+    }
+
+    fn scale(x: f64, y: f64, z: f64) -> Transform {
+        let mut transform = Transform::IDENTITY;
+        transform.columns[0][0] = x;
+        transform.columns[1][1] = y;
+        transform.columns[2][2] = z;
+        transform
+    }
+
+    #[test]
+    fn writes_distinct_attributes_once() {
+        let (obj, mtl) = export_string(vec![quad()]);
+        let expected = "\
+o quad
+g quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+mtllib scene.mtl
+usemtl red
+f 1/1/1 2/2/1 3/3/1
+usemtl blue
+f 1/1/1 3/3/1 4/4/1
+";
+        assert_eq!(obj, expected);
+        assert_eq!(mtl, "newmtl red\nnewmtl blue\n");
+    }
+
+    #[test]
+    fn declares_each_material_once() {
+        let mut unnamed = quad();
+        unnamed.metadata.clear();
+        let (obj, mtl) = export_string(vec![quad(), quad(), unnamed]);
+        assert_eq!(obj.matches("mtllib").count(), 1);
+        assert_eq!(obj.matches("usemtl red").count(), 2);
+        assert_eq!(obj.matches("usemtl material1").count(), 1);
+        assert_eq!(
+            mtl,
+            "newmtl red\nnewmtl blue\nnewmtl material0\nnewmtl material1\n"
+        );
+
+        let mut plain = quad();
+        if let Some(NodeContent::Geometry(geometry)) = &mut plain.content {
+            geometry.face_stack.remove(SC_LAYER_MATERIAL_ID);
+        }
+        let (obj, mtl) = export_string(vec![plain]);
+        assert!(!obj.contains("mtllib") && !obj.contains("usemtl"));
+        assert!(mtl.is_empty());
+    }
+
+    #[test]
+    fn places_every_instance() {
+        let triangle =
+            GeometryBuilder::new(&[[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+                .triangles(&[[0, 1, 2]])
+                .vertex_layer(SC_LAYER_NORMALS, &[[0.0f32, 0.0, 1.0]; 3])
+                .metadata(MetaBuilder::new().text(SC_NAME, "tri"))
+                .build()
+                .unwrap();
+        let (obj, mtl) = export_string(vec![
+            triangle,
+            meta(
+                MetaBuilder::new()
+                    .transform(Transform::from_translation([0.0, 0.0, 2.0]))
+                    .node("children", &[0, 2]),
+            ),
+            meta(
+                MetaBuilder::new()
+                    .transform(scale(2.0, 1.0, 1.0))
+                    .node("children", &[0]),
+            ),
+        ]);
+        let expected = "\
+o tri
+g tri
+v 0 0 2
+v 1 0 2
+v 0 1 2
+vn 0 0 1
+f 1//1 2//1 3//1
+o tri
+g tri
+v 0 0 2
+v 2 0 2
+v 0 1 2
+vn 0 0 1
+f 4//2 5//2 6//2
+";
+        assert_eq!(obj, expected);
+        assert!(mtl.is_empty());
+    }
+
+    #[test]
+    fn transforms_normals_by_the_inverse_transpose() {
+        let translate = Transform::from_translation([1.0, 2.0, 3.0]);
+        assert_eq!(
+            transform_normal(&translate, [0.0, 0.6, 0.8]),
+            [0.0, 0.6, 0.8]
+        );
+
+        // Stretching along x tilts normals away from x, keeping their length.
+        let [x, y, z] = transform_normal(&scale(2.0, 1.0, 1.0), [1.0, 1.0, 0.0]);
+        let length = 2f64.sqrt() / 5f64.sqrt();
+        assert!((x - length).abs() < 1e-12 && (y - 2.0 * length).abs() < 1e-12);
+        assert_eq!(z, 0.0);
+
+        // Mirroring flips normals rather than turning them inside out.
+        assert_eq!(
+            transform_normal(&scale(-1.0, 1.0, 1.0), [1.0, 0.0, 0.0]),
+            [-1.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            transform_normal(&scale(-1.0, -1.0, -1.0), [0.0, 0.0, 1.0]),
+            [0.0, 0.0, -1.0]
+        );
+
+        let mut rotate = Transform::IDENTITY;
+        rotate.columns[0] = [0.0, 1.0, 0.0, 0.0];
+        rotate.columns[1] = [-1.0, 0.0, 0.0, 0.0];
+        assert_eq!(transform_normal(&rotate, [1.0, 0.0, 0.0]), [0.0, 1.0, 0.0]);
+
+        // Degenerate transforms leave nothing to normalise.
+        assert_eq!(
+            transform_normal(&scale(0.0, 0.0, 0.0), [1.0, 0.0, 0.0]),
+            [0.0, 0.0, 0.0]
+        );
+    }
+
+    /// Imports from a file, since that is what the importer reads. Each test
+    /// uses its own `name` so that they can run in parallel.
+    fn import_str(name: &str, data: &str) -> Result<Hxa<'static>> {
+        let file_name = format!("hxa-conv-{}-{}.obj", process::id(), name);
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, data).unwrap();
+        let hxa = import(&path);
+        fs::remove_file(&path).unwrap();
+        hxa
+    }
+
+    fn geometry<'n>(node: &'n Node<'static>) -> &'n NodeGeometry<'static> {
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    fn positions(node: &Node<'static>) -> Vec<[f32; 3]> {
+        let layer = &geometry(node).vertex_stack.layers[HC_BASE_VERTEX_LAYER_ID];
+        layer.as_elements::<f32, 3>().unwrap().to_vec()
+    }
+
+    fn polygons(node: &Node<'static>) -> Vec<Vec<u32>> {
+        geometry(node)
+            .polygons()
+            .unwrap()
+            .map(|polygon| polygon.unwrap().vertices().collect())
+            .collect()
+    }
+
+    fn materials(node: &Node<'static>) -> Option<Vec<i32>> {
+        let layer = geometry(node).face_stack.get(SC_LAYER_MATERIAL_ID)?;
+        Some(
+            layer
+                .as_elements::<i32, 1>()?
+                .iter()
+                .map(|[id]| *id)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn merges_groups_split_by_material() {
+        let hxa = import_str(
+            "merges_groups_split_by_material",
+            "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 5 5 5
+o box
+g side
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 3 4
+usemtl red
+f 1 2 4
+g other
+f 2 3 4
+",
+        )
+        .unwrap();
+        assert_eq!(hxa.nodes.len(), 2);
+
+        let side = &hxa.nodes[0];
+        assert_eq!(side.meta_text(SC_NAME), Some("side"));
+        assert_eq!(
+            positions(side),
+            [
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0]
+            ]
+        );
+        assert_eq!(polygons(side), [[0, 1, 2], [0, 2, 3], [0, 1, 3]]);
+        assert_eq!(materials(side), Some(vec![0, 1, 0]));
+        assert_eq!(side.meta_text("material_names/0"), Some("red"));
+        assert_eq!(side.meta_text("material_names/1"), Some("blue"));
+        assert_eq!(side.meta_text("material_names/2"), None);
+
+        // Only the positions a node uses are kept, in file order.
+        let other = &hxa.nodes[1];
+        assert_eq!(other.meta_text(SC_NAME), Some("other"));
+        assert_eq!(
+            positions(other),
+            [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(polygons(other), [[0, 1, 2]]);
+        assert_eq!(materials(other), None);
+        assert!(other.meta(MATERIAL_NAMES).is_none());
+    }
+
+    #[test]
+    fn names_nodes_after_groups_or_objects() {
+        let hxa = import_str(
+            "names_nodes_after_groups_or_objects",
+            "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+o named
+f 3 2 1
+g group
+f 1 2 3
+",
+        )
+        .unwrap();
+        let names: Vec<_> = hxa
+            .nodes
+            .iter()
+            .map(|node| node.meta_text(SC_NAME))
+            .collect();
+        assert_eq!(names, [None, Some("named"), Some("group")]);
+    }
+
+    #[test]
+    fn reads_corner_attributes() {
+        let hxa = import_str(
+            "reads_corner_attributes",
+            "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vn 0 0 1
+f 1/1/1 2/2/1 3//1
+",
+        )
+        .unwrap();
+        let geometry = geometry(&hxa.nodes[0]);
+        let uvs = geometry.corner_stack.get(SC_LAYER_NAME_UV0).unwrap();
+        assert_eq!(
+            uvs.as_elements::<f32, 2>().unwrap(),
+            [[0.0, 0.0], [1.0, 0.0], [0.0, 0.0]]
+        );
+        let normals = geometry.corner_stack.get(SC_LAYER_NORMALS).unwrap();
+        assert_eq!(
+            normals.as_elements::<f32, 3>().unwrap(),
+            [[0.0, 0.0, 1.0]; 3]
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let cases = [
+            ("f 1 2 9", "refers to position 9, but there are only 3"),
+            (
+                "f 1/1 2/5 3/1",
+                "refers to texture coordinate 5, but there are only 1",
+            ),
+            (
+                "f 1//1 2//1 3//2",
+                "refers to normal 2, but there are only 1",
+            ),
+        ];
+        for (i, (face, message)) in cases.iter().enumerate() {
+            let data = format!(
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\ng tri\n{}\n",
+                face
+            );
+            let name = format!("rejects_out_of_range_indices{}", i);
+            let error = import_str(&name, &data).unwrap_err().to_string();
+            let expected = format!("Face \"{}\" in tri {}", face, message);
+            assert_eq!(error, expected);
+        }
+    }
+
+    #[test]
+    fn keeps_material_names_through_a_round_trip() {
+        let (obj, _) = export_string(vec![quad()]);
+        let hxa = import_str("keeps_material_names_through_a_round_trip", &obj).unwrap();
+        let node = &hxa.nodes[0];
+        assert_eq!(node.meta_text(SC_NAME), Some("quad"));
+        assert_eq!(polygons(node), [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(materials(node), Some(vec![0, 1]));
+        assert_eq!(node.meta_text("material_names/0"), Some("red"));
+        assert_eq!(node.meta_text("material_names/1"), Some("blue"));
+    }
+}
@@ -0,0 +1,260 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::ops::Range;
+
+use crate::{
+    decode_reference, HxaError, HxaResult, Layer, LayerData, NodeGeometry, PolygonStarts,
+    HC_EDGE_NEIGHBOUR_LAYER_NAME, HC_EDGE_NEIGHBOUR_LAYER_TYPE,
+};
+
+impl NodeGeometry<'_> {
+    /// Builds the edge neighbour layer from the reference layer.
+    ///
+    /// Edge `c` runs from the vertex of corner `c` to the vertex of the next
+    /// corner in the same polygon. Its neighbour is the edge running the
+    /// other way between the same two vertices, or -1 if there is no such
+    /// edge or more than one edge could be paired with it.
+    pub fn compute_neighbours(&self) -> HxaResult<Layer<'static>> {
+        let references = self.references()?;
+        if i32::try_from(references.len()).is_err() {
+            return Err(HxaError::CountTooLarge(references.len()));
+        }
+
+        // Every directed edge as (from, to, corner), sorted so that all edges
+        // between the same two vertices end up next to each other.
+        let mut edges = Vec::with_capacity(references.len());
+        for polygon in self.polygons()? {
+            let polygon = polygon?;
+            let start = polygon.corners.start;
+            let len = polygon.len();
+            for i in 0..len {
+                let from = polygon.vertex(i).unwrap_or_default();
+                let to = polygon.vertex((i + 1) % len).unwrap_or_default();
+                edges.push((from, to, start + i));
+            }
+        }
+        edges.sort_unstable();
+
+        let matching = |from: u32, to: u32| {
+            let start = edges.partition_point(|&(f, t, _)| (f, t) < (from, to));
+            let end = edges.partition_point(|&(f, t, _)| (f, t) <= (from, to));
+            &edges[start..end]
+        };
+        let mut neighbours = alloc::vec![-1; references.len()];
+        for &(from, to, corner) in &edges {
+            if let ([_], [(_, _, opposite)]) = (matching(from, to), matching(to, from)) {
+                // Corner counts were checked to fit in an `i32` above.
+                neighbours[corner] = *opposite as i32;
+            }
+        }
+
+        Ok(Layer::from_slice(HC_EDGE_NEIGHBOUR_LAYER_NAME, &neighbours))
+    }
+
+    /// Recomputes the edge neighbour layer and stores it in the edge stack,
+    /// replacing any previous one.
+    ///
+    /// Edge stacks are only written for [`crate::HxaVersion::V3`] and later.
+    pub fn update_neighbours(&mut self) -> HxaResult<()> {
+        let layer = self.compute_neighbours()?;
+        self.edge_stack.insert(layer);
+        Ok(())
+    }
+
+    /// Gives access to the connectivity described by the reference and edge
+    /// neighbour layers.
+    pub fn adjacency(&self) -> HxaResult<Adjacency<'_>> {
+        let references = self.references()?;
+        let neighbours = self
+            .edge_stack
+            .get(HC_EDGE_NEIGHBOUR_LAYER_NAME)
+            .filter(|layer| {
+                layer.component_count == 1 && layer.type_ == HC_EDGE_NEIGHBOUR_LAYER_TYPE
+            })
+            .and_then(|layer| match &layer.data {
+                LayerData::Int32(neighbours) => Some(&**neighbours),
+                _ => None,
+            })
+            .filter(|neighbours| neighbours.len() == references.len())
+            .ok_or(HxaError::InvalidNeighbourLayer)?;
+        Ok(Adjacency {
+            references,
+            neighbours,
+            polygons: self.polygon_starts()?,
+        })
+    }
+}
+
+/// Connectivity queries over the corners of a geometry node.
+///
+/// All methods panic if given a corner that does not exist.
+#[derive(Clone, Debug)]
+pub struct Adjacency<'g> {
+    references: &'g [i32],
+    neighbours: &'g [i32],
+    polygons: PolygonStarts,
+}
+
+impl Adjacency<'_> {
+    pub fn corner_count(&self) -> usize {
+        self.references.len()
+    }
+
+    /// The vertex that `corner` refers to.
+    pub fn vertex(&self, corner: usize) -> u32 {
+        decode_reference(self.references[corner])
+    }
+
+    /// The corner on the other side of the edge starting at `corner`, which
+    /// starts at the vertex the edge ends at.
+    pub fn opposite(&self, corner: usize) -> Option<usize> {
+        usize::try_from(self.neighbours[corner]).ok()
+    }
+
+    /// The corner after `corner` in the same polygon.
+    pub fn next(&self, corner: usize) -> usize {
+        if self.references[corner] < 0 {
+            self.polygon(corner).start
+        } else {
+            corner + 1
+        }
+    }
+
+    /// The corner before `corner` in the same polygon.
+    pub fn previous(&self, corner: usize) -> usize {
+        let polygon = self.polygon(corner);
+        if corner == polygon.start {
+            polygon.end - 1
+        } else {
+            corner - 1
+        }
+    }
+
+    /// The corners of the polygon that `corner` belongs to.
+    fn polygon(&self, corner: usize) -> Range<usize> {
+        assert!(
+            corner < self.corner_count(),
+            "corner {} does not exist",
+            corner
+        );
+        // Every corner belongs to a polygon, since `NodeGeometry::adjacency`
+        // checked that the last one is terminated.
+        let polygon = self.polygons.polygon_of_corner(corner).unwrap_or_default();
+        self.polygons.corners(polygon).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GeometryBuilder, Node, NodeContent};
+
+    fn geometry(triangles: &[&[u32]], vertex_count: usize) -> NodeGeometry<'static> {
+        let node = GeometryBuilder::new(&alloc::vec![[0.0f32; 3]; vertex_count])
+            .polygons(triangles.iter().copied())
+            .build()
+            .unwrap();
+        match node {
+            Node {
+                content: Some(NodeContent::Geometry(mut geometry)),
+                ..
+            } => {
+                geometry.update_neighbours().unwrap();
+                geometry
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn neighbours(geometry: &NodeGeometry<'_>) -> Vec<i32> {
+        match &geometry.edge_stack.layers[..] {
+            [layer] => match &layer.data {
+                LayerData::Int32(neighbours) => neighbours.to_vec(),
+                _ => panic!("expected an int32 neighbour layer"),
+            },
+            layers => panic!("expected one edge layer, got {:?}", layers),
+        }
+    }
+
+    #[test]
+    fn pairs_every_edge_of_a_closed_mesh() {
+        let tetrahedron = geometry(&[&[0, 2, 1], &[0, 1, 3], &[1, 2, 3], &[2, 0, 3]], 4);
+        let adjacency = tetrahedron.adjacency().unwrap();
+        assert_eq!(adjacency.corner_count(), 12);
+        for corner in 0..12 {
+            let opposite = adjacency.opposite(corner).unwrap();
+            assert_ne!(corner, opposite);
+            assert_eq!(adjacency.opposite(opposite), Some(corner));
+            // The edges run in opposite directions between the same vertices.
+            assert_eq!(
+                adjacency.vertex(opposite),
+                adjacency.vertex(adjacency.next(corner))
+            );
+            assert_eq!(
+                adjacency.vertex(adjacency.next(opposite)),
+                adjacency.vertex(corner)
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_boundary_edges_unpaired() {
+        let quad = geometry(&[&[0, 1, 2, 3]], 4);
+        assert_eq!(neighbours(&quad), [-1; 4]);
+        let adjacency = quad.adjacency().unwrap();
+        assert!((0..4).all(|corner| adjacency.opposite(corner).is_none()));
+    }
+
+    #[test]
+    fn leaves_non_manifold_edges_unpaired() {
+        // Three triangles share the edge between vertices 0 and 1, while the
+        // last one shares an ordinary edge with the first.
+        let fan = geometry(&[&[0, 1, 2], &[1, 0, 3], &[1, 0, 4], &[2, 1, 5]], 6);
+        assert_eq!(
+            neighbours(&fan),
+            [-1, 9, -1, -1, -1, -1, -1, -1, -1, 1, -1, -1]
+        );
+    }
+
+    #[test]
+    fn walks_around_polygons() {
+        let quads = geometry(&[&[0, 1, 2, 3], &[1, 4, 5, 2]], 6);
+        let adjacency = quads.adjacency().unwrap();
+        let next: Vec<usize> = (0..8).map(|corner| adjacency.next(corner)).collect();
+        assert_eq!(next, [1, 2, 3, 0, 5, 6, 7, 4]);
+        let previous: Vec<usize> = (0..8).map(|corner| adjacency.previous(corner)).collect();
+        assert_eq!(previous, [3, 0, 1, 2, 7, 4, 5, 6]);
+        let vertices: Vec<u32> = (0..8).map(|corner| adjacency.vertex(corner)).collect();
+        assert_eq!(vertices, [0, 1, 2, 3, 1, 4, 5, 2]);
+        assert_eq!(adjacency.opposite(1), Some(7));
+        assert_eq!(adjacency.opposite(7), Some(1));
+        assert_eq!(adjacency.opposite(0), None);
+    }
+
+    #[test]
+    fn replaces_and_requires_the_neighbour_layer() {
+        let mut quad = geometry(&[&[0, 1, 2, 3]], 4);
+        quad.update_neighbours().unwrap();
+        assert_eq!(neighbours(&quad), [-1; 4]);
+
+        quad.edge_stack.layers.clear();
+        assert!(matches!(
+            quad.adjacency(),
+            Err(HxaError::InvalidNeighbourLayer)
+        ));
+        quad.edge_stack
+            .layers
+            .push(Layer::from_slice(HC_EDGE_NEIGHBOUR_LAYER_NAME, &[-1; 3]));
+        assert!(matches!(
+            quad.adjacency(),
+            Err(HxaError::InvalidNeighbourLayer)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "corner 4 does not exist")]
+    fn panics_on_missing_corners() {
+        let quad = geometry(&[&[0, 1, 2, 3]], 4);
+        quad.adjacency().unwrap().previous(4);
+    }
+}
@@ -0,0 +1,545 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::image::pixel_count;
+use crate::{
+    FloatComponent, HxaError, HxaResult, ImageType, Layer, LayerElement, LayerStack, Meta,
+    MetaValue, MetadataType, Node, NodeContent, NodeGeometry, NodeImage, NodeType,
+    HC_BASE_CORNER_LAYER_NAME, HC_BASE_VERTEX_LAYER_NAME,
+};
+
+/// Assembles a geometry node whose base vertex and corner layers are always
+/// present and well-formed.
+#[derive(Clone, Debug)]
+pub struct GeometryBuilder {
+    vertices: Layer<'static>,
+    polygons: Vec<u32>,
+    polygon_ends: Vec<usize>,
+    vertex_layers: Vec<Layer<'static>>,
+    corner_layers: Vec<Layer<'static>>,
+    edge_layers: Vec<Layer<'static>>,
+    face_layers: Vec<Layer<'static>>,
+    metadata: Vec<Meta<'static>>,
+}
+
+impl GeometryBuilder {
+    pub fn new<T: FloatComponent>(vertices: &[[T; 3]]) -> Self {
+        Self {
+            vertices: Layer::from_slice(HC_BASE_VERTEX_LAYER_NAME, vertices),
+            polygons: Vec::new(),
+            polygon_ends: Vec::new(),
+            vertex_layers: Vec::new(),
+            corner_layers: Vec::new(),
+            edge_layers: Vec::new(),
+            face_layers: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Adds a polygon made up of the given vertex indices, in order.
+    pub fn polygon(mut self, vertices: &[u32]) -> Self {
+        self.polygons.extend_from_slice(vertices);
+        self.polygon_ends.push(self.polygons.len());
+        self
+    }
+
+    pub fn polygons<'p, I>(self, polygons: I) -> Self
+    where
+        I: IntoIterator<Item = &'p [u32]>,
+    {
+        polygons.into_iter().fold(self, Self::polygon)
+    }
+
+    pub fn triangles(self, triangles: &[[u32; 3]]) -> Self {
+        triangles
+            .iter()
+            .fold(self, |builder, triangle| builder.polygon(triangle))
+    }
+
+    /// Adds a layer with one element per vertex.
+    pub fn vertex_layer<E: LayerElement>(mut self, name: &str, elements: &[E]) -> Self {
+        self.vertex_layers
+            .push(Layer::from_slice(String::from(name), elements));
+        self
+    }
+
+    /// Adds a layer with one element per corner, in the order the corners
+    /// were added through the polygons.
+    pub fn corner_layer<E: LayerElement>(mut self, name: &str, elements: &[E]) -> Self {
+        self.corner_layers
+            .push(Layer::from_slice(String::from(name), elements));
+        self
+    }
+
+    /// Adds a layer with one element per edge. Edges are numbered the same
+    /// way as the corners they start at.
+    pub fn edge_layer<E: LayerElement>(mut self, name: &str, elements: &[E]) -> Self {
+        self.edge_layers
+            .push(Layer::from_slice(String::from(name), elements));
+        self
+    }
+
+    /// Adds a layer with one element per polygon.
+    pub fn face_layer<E: LayerElement>(mut self, name: &str, elements: &[E]) -> Self {
+        self.face_layers
+            .push(Layer::from_slice(String::from(name), elements));
+        self
+    }
+
+    pub fn metadata(mut self, metadata: MetaBuilder) -> Self {
+        self.metadata.extend(metadata.build());
+        self
+    }
+
+    pub fn build(self) -> HxaResult<Node<'static>> {
+        check_names(&self.metadata)?;
+        let vertex_count = self.vertices.data.len() / 3;
+        if i32::try_from(vertex_count).is_err() {
+            return Err(HxaError::CountTooLarge(vertex_count));
+        }
+
+        let mut references = Vec::with_capacity(self.polygons.len());
+        let mut start = 0;
+        for (polygon, &end) in self.polygon_ends.iter().enumerate() {
+            let vertices = &self.polygons[start..end];
+            if vertices.len() < 3 {
+                return Err(HxaError::DegeneratePolygon(polygon));
+            }
+            for (i, &vertex) in vertices.iter().enumerate() {
+                if vertex as usize >= vertex_count {
+                    return Err(HxaError::VertexIndexOutOfRange(vertex));
+                }
+                // Vertex indices were checked to fit in an `i32` above.
+                let vertex = vertex as i32;
+                references.push(if i == vertices.len() - 1 {
+                    -vertex - 1
+                } else {
+                    vertex
+                });
+            }
+            start = end;
+        }
+        let corner_count = references.len();
+        let face_count = self.polygon_ends.len();
+
+        let vertex_stack = stack(Some(self.vertices), self.vertex_layers, vertex_count)?;
+        let corner_stack = stack(
+            Some(Layer::from_slice(HC_BASE_CORNER_LAYER_NAME, &references)),
+            self.corner_layers,
+            corner_count,
+        )?;
+        let edge_stack = stack(None, self.edge_layers, corner_count)?;
+        let face_stack = stack(None, self.face_layers, face_count)?;
+
+        Ok(Node {
+            type_: NodeType::Geometry,
+            metadata: self.metadata,
+            content: Some(NodeContent::Geometry(NodeGeometry {
+                vertex_stack,
+                corner_stack,
+                edge_stack,
+                face_stack,
+            })),
+        })
+    }
+}
+
+/// Assembles an image node, checking that every layer covers every pixel.
+#[derive(Clone, Debug)]
+pub struct ImageBuilder {
+    type_: ImageType,
+    resolution: [u32; 3],
+    layers: Vec<Layer<'static>>,
+    metadata: Vec<Meta<'static>>,
+}
+
+impl ImageBuilder {
+    fn new(type_: ImageType, resolution: [u32; 3]) -> Self {
+        Self {
+            type_,
+            resolution,
+            layers: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn image_1d(width: u32) -> Self {
+        Self::new(ImageType::Image1D, [width, 1, 1])
+    }
+
+    pub fn image_2d(width: u32, height: u32) -> Self {
+        Self::new(ImageType::Image2D, [width, height, 1])
+    }
+
+    pub fn image_3d(width: u32, height: u32, depth: u32) -> Self {
+        Self::new(ImageType::Image3D, [width, height, depth])
+    }
+
+    /// A cube map made of six `width` by `height` faces.
+    pub fn cube(width: u32, height: u32) -> Self {
+        Self::new(ImageType::ImageCube, [width, height, 1])
+    }
+
+    /// Adds a layer with one element per pixel, stored row by row.
+    pub fn layer<E: LayerElement>(mut self, name: &str, pixels: &[E]) -> Self {
+        self.layers
+            .push(Layer::from_slice(String::from(name), pixels));
+        self
+    }
+
+    pub fn metadata(mut self, metadata: MetaBuilder) -> Self {
+        self.metadata.extend(metadata.build());
+        self
+    }
+
+    pub fn build(self) -> HxaResult<Node<'static>> {
+        check_names(&self.metadata)?;
+        let size = pixel_count(&self.type_, self.resolution)?;
+        let image_stack = stack(None, self.layers, size)?;
+
+        Ok(Node {
+            type_: NodeType::Image,
+            metadata: self.metadata,
+            content: Some(NodeContent::Image(NodeImage {
+                type_: self.type_,
+                resolution: self.resolution,
+                image_stack,
+            })),
+        })
+    }
+}
+
+/// Assembles a list of metadata entries.
+#[derive(Clone, Debug, Default)]
+pub struct MetaBuilder {
+    metadata: Vec<Meta<'static>>,
+}
+
+impl MetaBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(mut self, name: &str, type_: MetadataType, value: MetaValue<'static>) -> Self {
+        self.metadata.push(Meta {
+            name: Cow::Owned(String::from(name)),
+            type_,
+            value,
+        });
+        self
+    }
+
+    pub fn int64(self, name: &str, values: &[i64]) -> Self {
+        let value = MetaValue::Int64(Cow::Owned(values.to_vec()));
+        self.push(name, MetadataType::Int64, value)
+    }
+
+    pub fn double(self, name: &str, values: &[f64]) -> Self {
+        let value = MetaValue::Double(Cow::Owned(values.to_vec()));
+        self.push(name, MetadataType::Double, value)
+    }
+
+    /// Adds references to other nodes, by their index in the file.
+    pub fn node(self, name: &str, nodes: &[u32]) -> Self {
+        let value = MetaValue::Node(Cow::Owned(nodes.to_vec()));
+        self.push(name, MetadataType::Node, value)
+    }
+
+    pub fn text(self, name: &str, text: &str) -> Self {
+        let value = MetaValue::Text(Cow::Owned(String::from(text)));
+        self.push(name, MetadataType::Text, value)
+    }
+
+    pub fn binary(self, name: &str, data: &[u8]) -> Self {
+        let value = MetaValue::Bin(Cow::Owned(data.to_vec()));
+        self.push(name, MetadataType::Binary, value)
+    }
+
+    pub fn meta(self, name: &str, metadata: MetaBuilder) -> Self {
+        let value = MetaValue::Meta(metadata.build());
+        self.push(name, MetadataType::Meta, value)
+    }
+
+    pub fn build(self) -> Vec<Meta<'static>> {
+        self.metadata
+    }
+}
+
+/// Makes sure that every name in `metadata`, at any depth, fits in the 255
+/// bytes the format allows.
+fn check_names(metadata: &[Meta<'_>]) -> HxaResult<()> {
+    for meta in metadata {
+        check_name(&meta.name)?;
+        if let MetaValue::Meta(children) = &meta.value {
+            check_names(children)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_name(name: &str) -> HxaResult<()> {
+    if name.len() > u8::MAX as usize {
+        return Err(HxaError::NameTooLong(name.len()));
+    }
+    Ok(())
+}
+
+/// Puts `base` and `layers` together in a stack, making sure that each layer
+/// has `length` elements and a name that fits, and that no two layers share a
+/// name.
+fn stack(
+    base: Option<Layer<'static>>,
+    layers: Vec<Layer<'static>>,
+    length: usize,
+) -> HxaResult<LayerStack<'static>> {
+    let mut stack = Vec::with_capacity(layers.len() + 1);
+    stack.extend(base);
+    for layer in layers {
+        check_name(&layer.name)?;
+        if stack.iter().any(|l: &Layer| l.name == layer.name) {
+            return Err(HxaError::DuplicateLayerName(layer.name.into_owned()));
+        }
+        stack.push(layer);
+    }
+    for layer in &stack {
+        let expected = layer.component_count as usize * length;
+        if layer.data.len() != expected {
+            return Err(HxaError::LayerLengthMismatch {
+                expected,
+                found: layer.data.len(),
+            });
+        }
+    }
+    Ok(LayerStack { layers: stack })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LayerData, LayerDataType, NodeImage};
+
+    const SQUARE: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    fn long_name() -> String {
+        "x".repeat(256)
+    }
+
+    fn image<'n>(node: &'n Node<'static>) -> &'n NodeImage<'static> {
+        match &node.content {
+            Some(NodeContent::Image(image)) => image,
+            _ => panic!("expected an image node"),
+        }
+    }
+
+    #[test]
+    fn builds_geometry_with_base_layers() {
+        let node = GeometryBuilder::new(&SQUARE)
+            .triangles(&[[0, 1, 2]])
+            .polygon(&[0, 2, 3])
+            .vertex_layer("normal", &[[0.0f32, 0.0, 1.0]; 4])
+            .corner_layer("uv", &[[0.0f32; 2]; 6])
+            .edge_layer("crease", &[0u8; 6])
+            .face_layer("material", &[3, 4])
+            .metadata(MetaBuilder::new().text("name", "square"))
+            .build()
+            .unwrap();
+        assert_eq!(node.type_, NodeType::Geometry);
+        assert_eq!(node.metadata.len(), 1);
+        let geometry = match &node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        };
+
+        let names = |stack: &LayerStack<'_>| -> Vec<String> {
+            stack
+                .layers
+                .iter()
+                .map(|layer| layer.name.to_string())
+                .collect()
+        };
+        assert_eq!(names(&geometry.vertex_stack), ["vertex", "normal"]);
+        assert_eq!(names(&geometry.corner_stack), ["reference", "uv"]);
+        assert_eq!(names(&geometry.edge_stack), ["crease"]);
+        assert_eq!(names(&geometry.face_stack), ["material"]);
+        match &geometry.corner_stack.layers[0].data {
+            LayerData::Int32(references) => assert_eq!(**references, [0, 1, -3, 0, 2, -4]),
+            data => panic!("unexpected reference layer {:?}", data),
+        }
+        assert_eq!(geometry.vertex_stack.layers[0].type_, LayerDataType::Float);
+    }
+
+    #[test]
+    fn rejects_duplicate_layer_names() {
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 2, 3])
+            .vertex_layer("vertex", &[0u8; 4])
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::DuplicateLayerName(name) if name == "vertex"));
+
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 2, 3])
+            .corner_layer("uv", &[[0.0f32; 2]; 4])
+            .corner_layer("uv", &[[1.0f32; 2]; 4])
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::DuplicateLayerName(name) if name == "uv"));
+    }
+
+    #[test]
+    fn rejects_layers_of_the_wrong_length() {
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 2, 3])
+            .vertex_layer("normal", &[[0.0f32; 3]; 3])
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            HxaError::LayerLengthMismatch {
+                expected: 12,
+                found: 9
+            }
+        ));
+
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 2, 3])
+            .face_layer("material", &[0, 1])
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            HxaError::LayerLengthMismatch {
+                expected: 1,
+                found: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_polygons() {
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 2])
+            .polygon(&[2, 3])
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::DegeneratePolygon(1)));
+
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 4])
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::VertexIndexOutOfRange(4)));
+    }
+
+    #[test]
+    fn rejects_long_names() {
+        let error = GeometryBuilder::new(&SQUARE)
+            .polygon(&[0, 1, 2, 3])
+            .face_layer(&long_name(), &[0])
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::NameTooLong(256)));
+
+        let nested = MetaBuilder::new().meta("outer", MetaBuilder::new().int64(&long_name(), &[1]));
+        let error = ImageBuilder::image_1d(1)
+            .metadata(nested)
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::NameTooLong(256)));
+
+        // 255 bytes still fit.
+        let name = "x".repeat(255);
+        assert!(ImageBuilder::image_1d(1)
+            .layer(&name, &[0u8])
+            .metadata(MetaBuilder::new().double(&name, &[1.0]))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn sizes_images_by_type() {
+        let cases = [
+            (ImageBuilder::image_1d(5), ImageType::Image1D, [5, 1, 1], 5),
+            (
+                ImageBuilder::image_2d(3, 2),
+                ImageType::Image2D,
+                [3, 2, 1],
+                6,
+            ),
+            (
+                ImageBuilder::image_3d(2, 3, 4),
+                ImageType::Image3D,
+                [2, 3, 4],
+                24,
+            ),
+            (
+                ImageBuilder::cube(2, 3),
+                ImageType::ImageCube,
+                [2, 3, 1],
+                36,
+            ),
+        ];
+        for (builder, type_, resolution, pixels) in cases {
+            let node = builder
+                .clone()
+                .layer("color", &alloc::vec![[0u8; 3]; pixels])
+                .build()
+                .unwrap();
+            assert_eq!(node.type_, NodeType::Image);
+            let image = image(&node);
+            assert_eq!(image.type_, type_);
+            assert_eq!(image.resolution, resolution);
+            assert_eq!(image.image_stack.layers[0].data.len(), 3 * pixels);
+
+            let error = builder
+                .layer("color", &alloc::vec![[0u8; 3]; pixels + 1])
+                .build()
+                .unwrap_err();
+            assert!(matches!(
+                error,
+                HxaError::LayerLengthMismatch { expected, found }
+                    if expected == 3 * pixels && found == 3 * pixels + 3
+            ));
+        }
+
+        let error = ImageBuilder::image_3d(u32::MAX, u32::MAX, u32::MAX)
+            .build()
+            .unwrap_err();
+        assert!(matches!(error, HxaError::ImageSizeOverflow(_)));
+    }
+
+    #[test]
+    fn builds_every_kind_of_metadata() {
+        let metadata = MetaBuilder::new()
+            .int64("int", &[-1])
+            .double("double", &[0.5])
+            .node("node", &[0])
+            .text("text", "hello")
+            .binary("binary", &[1, 2])
+            .meta("meta", MetaBuilder::new().text("inner", ""))
+            .build();
+        let types: Vec<MetadataType> = metadata.iter().map(|meta| meta.type_.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                MetadataType::Int64,
+                MetadataType::Double,
+                MetadataType::Node,
+                MetadataType::Text,
+                MetadataType::Binary,
+                MetadataType::Meta,
+            ]
+        );
+        for meta in &metadata {
+            assert_eq!(meta.value.type_(), meta.type_);
+        }
+        assert!(matches!(&metadata[3].value, MetaValue::Text(text) if text == "hello"));
+    }
+}
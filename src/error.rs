@@ -2,33 +2,92 @@ use core::array::TryFromSliceError;
 use core::fmt;
 use core::str::Utf8Error;
 
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{HxaVersion, NodeType, StackKind};
+
 #[cfg(feature = "std")]
 use std::error::Error;
+#[cfg(feature = "std")]
+use std::io;
 
 pub type HxaResult<T> = Result<T, HxaError>;
 
 #[derive(Debug)]
 pub enum HxaError {
     InvalidMagicNumber(u32),
+    UnsupportedVersion(u32),
     UnexpectedEndOfData,
+    TrailingData {
+        offset: usize,
+        len: usize,
+    },
     UnexpectedNodeType(u8),
     UnexpectedLayerDataType(u8),
     UnexpectedImageType(u8),
     UnexpectedMetadataType(u8),
     InvalidUtf8(Utf8Error),
+    CountOverflow,
+    LimitExceeded(Limit),
+    ImageSizeOverflow([u32; 3]),
+    NodeContentMismatch(NodeType),
+    EdgeStackUnsupported(HxaVersion),
+    NameTooLong(usize),
+    DuplicateLayerName(String),
+    DegeneratePolygon(usize),
+    VertexIndexOutOfRange(u32),
+    InvalidBaseLayer(StackKind),
+    InvalidNeighbourLayer,
+    NodeIndexOutOfRange(u32),
+    NodeCycle(usize),
+    TooManyInstances {
+        count: usize,
+        limit: usize,
+    },
+    UnterminatedPolygon {
+        corner: usize,
+    },
+    CountTooLarge(usize),
+    LayerLengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    Parse(Box<ParseError>),
     InternalError(InternalError),
 }
 
+impl HxaError {
+    /// Strips the location information from parse errors.
+    pub fn root(&self) -> &HxaError {
+        match self {
+            HxaError::Parse(inner) => inner.error.root(),
+            _ => self,
+        }
+    }
+}
+
 impl fmt::Display for HxaError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             HxaError::InvalidMagicNumber(n) => write!(f, "Invalid magic numer: {}", n),
+            HxaError::UnsupportedVersion(n) => write!(f, "Unsupported version: {}", n),
             HxaError::UnexpectedEndOfData => {
                 write!(
                     f,
                     "The parser unexpectedly reached the end of the data stream"
                 )
             }
+            HxaError::TrailingData { offset, len } => {
+                write!(
+                    f,
+                    "Found {} bytes of trailing data at offset {}",
+                    len, offset
+                )
+            }
             HxaError::UnexpectedNodeType(n) => write!(f, "Unexpected node type {} encountered", n),
             HxaError::UnexpectedLayerDataType(n) => {
                 write!(f, "Unexpected layer data type encountered: {}", n)
@@ -40,13 +99,155 @@ impl fmt::Display for HxaError {
                 write!(f, "Unexpected metadata type encountered: {}", n)
             }
             HxaError::InvalidUtf8(inner) => inner.fmt(f),
+            HxaError::CountOverflow => {
+                write!(f, "An element count does not fit in the address space")
+            }
+            HxaError::LimitExceeded(limit) => {
+                let limit = match limit {
+                    Limit::Nodes => "node count",
+                    Limit::LayersPerStack => "number of layers per stack",
+                    Limit::MetadataDepth => "metadata nesting depth",
+                    Limit::Allocation => "total allocation size",
+                    Limit::ImageResolution => "image resolution",
+                };
+                write!(f, "The {} exceeds the configured limit", limit)
+            }
+            HxaError::ImageSizeOverflow([x, y, z]) => {
+                write!(
+                    f,
+                    "An image with a resolution of {}x{}x{} does not fit in the address space",
+                    x, y, z
+                )
+            }
+            HxaError::NodeContentMismatch(t) => {
+                write!(f, r#"Content does not match the node type "{:?}""#, t)
+            }
+            HxaError::EdgeStackUnsupported(version) => {
+                write!(f, "{:?} files cannot contain edge layers", version)
+            }
+            HxaError::NameTooLong(n) => {
+                write!(f, "Name is {} bytes long, but at most 255 are allowed", n)
+            }
+            HxaError::DuplicateLayerName(name) => {
+                write!(f, r#"More than one layer is named "{}""#, name)
+            }
+            HxaError::DegeneratePolygon(n) => {
+                write!(f, "Polygon {} has fewer than 3 vertices", n)
+            }
+            HxaError::VertexIndexOutOfRange(n) => {
+                write!(f, "Vertex index {} is out of range", n)
+            }
+            HxaError::InvalidBaseLayer(stack) => {
+                write!(f, "The {} stack has no valid base layer", stack)
+            }
+            HxaError::InvalidNeighbourLayer => {
+                write!(f, "The edge stack has no valid neighbour layer")
+            }
+            HxaError::NodeIndexOutOfRange(n) => {
+                write!(f, "Node index {} is out of range", n)
+            }
+            HxaError::NodeCycle(node) => {
+                write!(f, "Node {} is part of a cycle of node references", node)
+            }
+            HxaError::TooManyInstances { count, limit } => {
+                write!(
+                    f,
+                    "The scene places {} node instances, but at most {} are allowed",
+                    count, limit
+                )
+            }
+            HxaError::UnterminatedPolygon { corner } => {
+                write!(
+                    f,
+                    "The polygon starting at corner {} is unterminated",
+                    corner
+                )
+            }
+            HxaError::CountTooLarge(n) => {
+                write!(f, "Count {} does not fit in an unsigned 32-bit integer", n)
+            }
+            HxaError::LayerLengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Layer has {} elements, but its stack requires {}",
+                    found, expected
+                )
+            }
+            #[cfg(feature = "std")]
+            HxaError::Io(inner) => inner.fmt(f),
+            HxaError::Parse(inner) => inner.fmt(f),
             HxaError::InternalError(inner) => write!(f, "Internal parser error: {}", inner),
         }
     }
 }
 
 #[cfg(feature = "std")]
-impl Error for HxaError {}
+impl Error for HxaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HxaError::InvalidUtf8(inner) => Some(inner),
+            HxaError::Io(inner) => Some(inner),
+            HxaError::Parse(inner) => Some(&inner.error),
+            HxaError::InternalError(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+/// The [`ParseOptions`](crate::ParseOptions) limit that a file exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Nodes,
+    LayersPerStack,
+    MetadataDepth,
+    Allocation,
+    ImageResolution,
+}
+
+/// An error that occurred while parsing, along with where it occurred.
+#[derive(Debug)]
+pub struct ParseError {
+    /// Offset of the read that failed, in bytes from the start of the file.
+    pub offset: usize,
+    /// The elements that were being parsed, from outermost to innermost.
+    pub path: Vec<PathSegment>,
+    pub error: HxaError,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "At offset {}", self.offset)?;
+        for (i, segment) in self.path.iter().enumerate() {
+            f.write_str(if i == 0 { " in " } else { " / " })?;
+            segment.fmt(f)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    Node(usize),
+    Metadata { index: usize, name: Option<String> },
+    Stack(StackKind),
+    Layer { index: usize, name: Option<String> },
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, index, name) = match self {
+            PathSegment::Node(index) => return write!(f, "node {}", index),
+            PathSegment::Stack(kind) => return write!(f, "{} stack", kind),
+            PathSegment::Metadata { index, name } => ("metadata", index, name),
+            PathSegment::Layer { index, name } => ("layer", index, name),
+        };
+        write!(f, "{} {}", kind, index)?;
+        match name {
+            Some(name) => write!(f, r#" "{}""#, name),
+            None => Ok(()),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct InternalError {
@@ -74,3 +275,21 @@ impl Error for InternalError {}
 pub(crate) enum InternalErrorKind {
     TryFromSlice(TryFromSliceError),
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposes_inner_errors() {
+        let bytes = alloc::vec![0xff];
+        let utf8 = core::str::from_utf8(&bytes).unwrap_err();
+        let error = HxaError::InvalidUtf8(utf8);
+        assert!(error.source().unwrap().is::<Utf8Error>());
+
+        let error = HxaError::Io(io::Error::from(io::ErrorKind::WriteZero));
+        assert!(error.source().unwrap().is::<io::Error>());
+
+        assert!(HxaError::UnexpectedEndOfData.source().is_none());
+    }
+}
@@ -0,0 +1,284 @@
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use crate::{
+    HxaError, HxaResult, LayerData, NodeGeometry, StackKind, HC_BASE_CORNER_LAYER_ID,
+    HC_BASE_CORNER_LAYER_TYPE,
+};
+
+impl NodeGeometry<'_> {
+    /// The base corner layer, holding one vertex reference per corner.
+    pub fn references(&self) -> HxaResult<&[i32]> {
+        match self.corner_stack.layers.get(HC_BASE_CORNER_LAYER_ID) {
+            Some(layer) if layer.type_ == HC_BASE_CORNER_LAYER_TYPE => match &layer.data {
+                LayerData::Int32(references) => Ok(references),
+                _ => Err(HxaError::InvalidBaseLayer(StackKind::Corner)),
+            },
+            _ => Err(HxaError::InvalidBaseLayer(StackKind::Corner)),
+        }
+    }
+
+    /// Iterates over the polygons described by the reference layer.
+    pub fn polygons(&self) -> HxaResult<Polygons<'_>> {
+        Ok(Polygons {
+            references: self.references()?,
+            start: 0,
+        })
+    }
+
+    pub fn polygon_count(&self) -> HxaResult<usize> {
+        self.polygons()?
+            .try_fold(0, |count, polygon| polygon.map(|_| count + 1))
+    }
+
+    /// Finds the index of the polygon that `corner` belongs to, if there is
+    /// such a corner.
+    ///
+    /// This walks the reference layer up to `corner` on every call. Use
+    /// [`NodeGeometry::polygon_starts`] to look up many corners.
+    pub fn polygon_of_corner(&self, corner: usize) -> HxaResult<Option<usize>> {
+        for (index, polygon) in self.polygons()?.enumerate() {
+            if polygon?.corners.contains(&corner) {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Records where every polygon starts, failing if the last one is
+    /// unterminated.
+    pub fn polygon_starts(&self) -> HxaResult<PolygonStarts> {
+        let mut starts = alloc::vec![0];
+        for polygon in self.polygons()? {
+            starts.push(polygon?.corners.end);
+        }
+        Ok(PolygonStarts { starts })
+    }
+}
+
+/// The corners at which the polygons of a geometry node start, returned by
+/// [`NodeGeometry::polygon_starts`].
+///
+/// Looking up the polygon of a corner takes a binary search instead of a walk
+/// over the reference layer.
+#[derive(Clone, Debug)]
+pub struct PolygonStarts {
+    /// The first corner of each polygon, followed by the number of corners.
+    starts: Vec<usize>,
+}
+
+impl PolygonStarts {
+    pub fn polygon_count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    /// The corners making up `polygon`, if there is such a polygon.
+    pub fn corners(&self, polygon: usize) -> Option<Range<usize>> {
+        let end = *self.starts.get(polygon + 1)?;
+        Some(self.starts[polygon]..end)
+    }
+
+    /// Finds the index of the polygon that `corner` belongs to, if there is
+    /// such a corner.
+    pub fn polygon_of_corner(&self, corner: usize) -> Option<usize> {
+        let corner_count = self.starts[self.starts.len() - 1];
+        if corner >= corner_count {
+            return None;
+        }
+        Some(self.starts.partition_point(|&start| start <= corner) - 1)
+    }
+}
+
+/// Turns a reference into the index of the vertex it refers to, undoing the
+/// encoding used for the last corner of each polygon.
+pub fn decode_reference(reference: i32) -> u32 {
+    if reference < 0 {
+        (-(reference + 1)) as u32
+    } else {
+        reference as u32
+    }
+}
+
+/// An iterator over the polygons of a geometry node.
+///
+/// Yields an error for corners at the end of the reference layer that are not
+/// terminated by a negative reference, after which it stops.
+#[derive(Clone, Debug)]
+pub struct Polygons<'g> {
+    references: &'g [i32],
+    start: usize,
+}
+
+impl<'g> Iterator for Polygons<'g> {
+    type Item = HxaResult<Polygon<'g>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.start;
+        let remaining = self
+            .references
+            .get(start..)
+            .filter(|rest| !rest.is_empty())?;
+        match remaining.iter().position(|&reference| reference < 0) {
+            Some(last) => {
+                let end = start + last + 1;
+                self.start = end;
+                Some(Ok(Polygon {
+                    corners: start..end,
+                    references: &self.references[start..end],
+                }))
+            }
+            None => {
+                self.start = self.references.len();
+                Some(Err(HxaError::UnterminatedPolygon { corner: start }))
+            }
+        }
+    }
+}
+
+impl FusedIterator for Polygons<'_> {}
+
+/// A single polygon of a geometry node.
+#[derive(Clone, Debug)]
+pub struct Polygon<'g> {
+    /// The corners making up the polygon, in order.
+    pub corners: Range<usize>,
+    references: &'g [i32],
+}
+
+impl<'g> Polygon<'g> {
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    /// The index of the polygon's `i`th vertex.
+    pub fn vertex(&self, i: usize) -> Option<u32> {
+        self.references.get(i).copied().map(decode_reference)
+    }
+
+    /// The indices of the polygon's vertices, in order.
+    pub fn vertices(&self) -> impl ExactSizeIterator<Item = u32> + 'g {
+        self.references.iter().copied().map(decode_reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{GeometryBuilder, Layer, Node, NodeContent};
+
+    /// A triangle, a quad and a pentagon sharing vertices.
+    fn mesh() -> Node<'static> {
+        GeometryBuilder::new(&[[0.0f32; 3]; 6])
+            .polygon(&[0, 1, 2])
+            .polygon(&[2, 1, 3, 4])
+            .polygon(&[5, 4, 3, 2, 0])
+            .build()
+            .unwrap()
+    }
+
+    fn geometry<'n>(node: &'n mut Node<'static>) -> &'n mut NodeGeometry<'static> {
+        match &mut node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    #[test]
+    fn splits_references_into_polygons() {
+        let mut node = mesh();
+        let geometry = geometry(&mut node);
+        assert_eq!(
+            geometry.references().unwrap(),
+            [0, 1, -3, 2, 1, 3, -5, 5, 4, 3, 2, -1]
+        );
+
+        let polygons: Vec<_> = geometry.polygons().unwrap().map(Result::unwrap).collect();
+        let corners: Vec<_> = polygons.iter().map(|p| p.corners.clone()).collect();
+        assert_eq!(corners, [0..3, 3..7, 7..12]);
+        let vertices: Vec<Vec<u32>> = polygons.iter().map(|p| p.vertices().collect()).collect();
+        assert_eq!(
+            vertices,
+            [vec![0, 1, 2], vec![2, 1, 3, 4], vec![5, 4, 3, 2, 0]]
+        );
+        assert_eq!(polygons[1].len(), 4);
+        assert_eq!(polygons[1].vertex(3), Some(4));
+        assert_eq!(polygons[1].vertex(4), None);
+        assert_eq!(geometry.polygon_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn finds_the_polygon_of_each_corner() {
+        let mut node = mesh();
+        let geometry = geometry(&mut node);
+        let starts = geometry.polygon_starts().unwrap();
+        assert_eq!(starts.polygon_count(), 3);
+        assert_eq!(starts.corners(1), Some(3..7));
+        assert_eq!(starts.corners(3), None);
+
+        let expected = [0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2];
+        for (corner, &polygon) in expected.iter().enumerate() {
+            assert_eq!(geometry.polygon_of_corner(corner).unwrap(), Some(polygon));
+            assert_eq!(starts.polygon_of_corner(corner), Some(polygon));
+        }
+        assert_eq!(geometry.polygon_of_corner(12).unwrap(), None);
+        assert_eq!(starts.polygon_of_corner(12), None);
+    }
+
+    #[test]
+    fn reports_unterminated_polygons() {
+        let mut node = mesh();
+        let geometry = geometry(&mut node);
+        geometry.corner_stack.layers[0] = Layer::from_slice("reference", &[0, 1, -3, 2, 1, 3]);
+
+        let mut polygons = geometry.polygons().unwrap();
+        assert_eq!(polygons.next().unwrap().unwrap().corners, 0..3);
+        assert!(matches!(
+            polygons.next(),
+            Some(Err(HxaError::UnterminatedPolygon { corner: 3 }))
+        ));
+        assert!(polygons.next().is_none());
+
+        assert!(matches!(
+            geometry.polygon_count(),
+            Err(HxaError::UnterminatedPolygon { corner: 3 })
+        ));
+        assert!(matches!(
+            geometry.polygon_starts(),
+            Err(HxaError::UnterminatedPolygon { corner: 3 })
+        ));
+        assert_eq!(geometry.polygon_of_corner(1).unwrap(), Some(0));
+        assert!(geometry.polygon_of_corner(4).is_err());
+    }
+
+    #[test]
+    fn requires_a_reference_layer() {
+        let mut node = mesh();
+        let geometry = geometry(&mut node);
+        geometry.corner_stack.layers[0] = Layer::from_slice("reference", &[0.0f32; 12]);
+        assert!(matches!(
+            geometry.polygons(),
+            Err(HxaError::InvalidBaseLayer(StackKind::Corner))
+        ));
+        geometry.corner_stack.layers.clear();
+        assert!(matches!(
+            geometry.polygon_count(),
+            Err(HxaError::InvalidBaseLayer(StackKind::Corner))
+        ));
+    }
+
+    #[test]
+    fn decodes_references() {
+        assert_eq!(decode_reference(0), 0);
+        assert_eq!(decode_reference(7), 7);
+        assert_eq!(decode_reference(-1), 0);
+        assert_eq!(decode_reference(-8), 7);
+        assert_eq!(decode_reference(i32::MIN), i32::MAX as u32);
+    }
+}
@@ -0,0 +1,319 @@
+use core::convert::TryFrom;
+use core::slice::ChunksExact;
+
+use crate::{HxaError, HxaResult, ImageType, LayerComponent, NodeImage};
+
+/// The faces of a cube map, in the order they are stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum CubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+}
+
+impl ImageType {
+    /// The number of axes stored in the file for this type of image. Cube
+    /// maps store the width and height of a single face.
+    pub fn dimensions(&self) -> usize {
+        match self {
+            ImageType::ImageCube => 2,
+            ImageType::Image1D => 1,
+            ImageType::Image2D => 2,
+            ImageType::Image3D => 3,
+        }
+    }
+}
+
+/// The number of pixels in an image, counting all six faces of a cube map.
+pub(crate) fn pixel_count(type_: &ImageType, resolution: [u32; 3]) -> HxaResult<usize> {
+    let faces = if *type_ == ImageType::ImageCube { 6 } else { 1 };
+    resolution[..type_.dimensions()]
+        .iter()
+        .try_fold(faces, |size: usize, &axis| {
+            size.checked_mul(usize::try_from(axis).ok()?)
+        })
+        .ok_or(HxaError::ImageSizeOverflow(resolution))
+}
+
+impl NodeImage<'_> {
+    pub fn width(&self) -> usize {
+        self.resolution[0] as usize
+    }
+
+    /// The height of the image, which is 1 for 1D images.
+    pub fn height(&self) -> usize {
+        if self.type_.dimensions() >= 2 {
+            self.resolution[1] as usize
+        } else {
+            1
+        }
+    }
+
+    /// The number of slices of a 3D image or faces of a cube map, which is 1
+    /// for other images.
+    pub fn depth(&self) -> usize {
+        match self.type_ {
+            ImageType::ImageCube => 6,
+            ImageType::Image3D => self.resolution[2] as usize,
+            _ => 1,
+        }
+    }
+
+    /// The number of elements in each image layer, which for cube maps is
+    /// six times the size of a face.
+    pub fn pixel_count(&self) -> HxaResult<usize> {
+        pixel_count(&self.type_, self.resolution)
+    }
+
+    /// The index of the pixel at `x`, `y` and `z` in each image layer, or
+    /// `None` if it is outside the image.
+    ///
+    /// Pixels are stored row by row, with `x` changing fastest, then `y`, then
+    /// `z`. A cube map is laid out like a 3D image whose slices are its faces,
+    /// in the order given by [`CubeFace`].
+    pub fn pixel_index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        index([self.width(), self.height(), self.depth()], x, y, z)
+    }
+
+    /// The index of the pixel at `x` and `y` on a face of a cube map in each
+    /// image layer, or `None` if this isn't a cube map or the pixel is
+    /// outside the face.
+    pub fn cube_pixel_index(&self, face: CubeFace, x: usize, y: usize) -> Option<usize> {
+        if self.type_ != ImageType::ImageCube {
+            return None;
+        }
+        self.pixel_index(x, y, face as usize)
+    }
+
+    /// A view of the image layer called `name`, if its pixels are made of `N`
+    /// components of type `T` and there is one for every pixel.
+    pub fn view<T: LayerComponent, const N: usize>(
+        &self,
+        name: &str,
+    ) -> Option<ImageView<'_, [T; N]>> {
+        let pixels = self.image_stack.get(name)?.as_elements::<T, N>()?;
+        if self.pixel_count().ok() != Some(pixels.len()) {
+            return None;
+        }
+        Some(ImageView {
+            pixels,
+            size: [self.width(), self.height(), self.depth()],
+        })
+    }
+}
+
+fn index([width, height, depth]: [usize; 3], x: usize, y: usize, z: usize) -> Option<usize> {
+    if x < width && y < height && z < depth {
+        Some((z * height + y) * width + x)
+    } else {
+        None
+    }
+}
+
+/// The pixels of a single image layer, as returned by [`NodeImage::view`].
+#[derive(Clone, Copy, Debug)]
+pub struct ImageView<'l, P> {
+    pixels: &'l [P],
+    size: [usize; 3],
+}
+
+impl<'l, P> ImageView<'l, P> {
+    pub fn width(&self) -> usize {
+        self.size[0]
+    }
+
+    pub fn height(&self) -> usize {
+        self.size[1]
+    }
+
+    /// The number of slices, or 6 for cube maps.
+    pub fn depth(&self) -> usize {
+        self.size[2]
+    }
+
+    /// All pixels, in the order they are stored in.
+    pub fn pixels(&self) -> &'l [P] {
+        self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&'l P> {
+        self.pixels.get(index(self.size, x, y, z)?)
+    }
+
+    /// The pixel at `x` and `y` on a face of a cube map.
+    ///
+    /// This doesn't know whether the image is actually a cube map, and treats
+    /// other images as having their slices in place of faces.
+    pub fn get_face(&self, face: CubeFace, x: usize, y: usize) -> Option<&'l P> {
+        self.get(x, y, face as usize)
+    }
+
+    /// A row of `width` pixels.
+    pub fn row(&self, y: usize, z: usize) -> Option<&'l [P]> {
+        let start = index(self.size, 0, y, z)?;
+        Some(&self.pixels[start..start + self.width()])
+    }
+
+    /// A slice, or a face of a cube map, of `width * height` pixels.
+    pub fn slice(&self, z: usize) -> Option<&'l [P]> {
+        let start = index(self.size, 0, 0, z)?;
+        Some(&self.pixels[start..start + self.width() * self.height()])
+    }
+
+    /// Iterates over all rows of all slices in order.
+    pub fn rows(&self) -> ChunksExact<'l, P> {
+        self.pixels.chunks_exact(self.width().max(1))
+    }
+
+    /// Iterates over all slices, or the faces of a cube map, in order.
+    pub fn slices(&self) -> ChunksExact<'l, P> {
+        self.pixels
+            .chunks_exact((self.width() * self.height()).max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{ImageBuilder, NodeContent};
+
+    /// An image whose "color" layer holds each pixel's index and a marker.
+    fn image(builder: ImageBuilder, pixels: usize) -> NodeImage<'static> {
+        let pixels: Vec<[i32; 2]> = (0..pixels as i32).map(|i| [i, 7]).collect();
+        match builder.layer("color", &pixels).build().unwrap().content {
+            Some(NodeContent::Image(image)) => image,
+            _ => panic!("expected an image node"),
+        }
+    }
+
+    fn indices(pixels: &[[i32; 2]]) -> Vec<i32> {
+        pixels.iter().map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn indexes_1d_images() {
+        let image = image(ImageBuilder::image_1d(4), 4);
+        assert_eq!((image.width(), image.height(), image.depth()), (4, 1, 1));
+        assert_eq!(image.pixel_index(3, 0, 0), Some(3));
+        assert_eq!(image.pixel_index(4, 0, 0), None);
+        assert_eq!(image.pixel_index(0, 1, 0), None);
+        assert_eq!(image.pixel_index(0, 0, 1), None);
+        assert_eq!(image.cube_pixel_index(CubeFace::PositiveX, 0, 0), None);
+
+        let view = image.view::<i32, 2>("color").unwrap();
+        assert_eq!(view.get(2, 0, 0), Some(&[2, 7]));
+        assert_eq!(view.get(4, 0, 0), None);
+        assert_eq!(indices(view.row(0, 0).unwrap()), [0, 1, 2, 3]);
+        assert_eq!(view.row(1, 0), None);
+        assert_eq!(view.rows().count(), 1);
+        assert_eq!(view.slices().count(), 1);
+    }
+
+    #[test]
+    fn indexes_2d_images() {
+        let image = image(ImageBuilder::image_2d(3, 2), 6);
+        assert_eq!((image.width(), image.height(), image.depth()), (3, 2, 1));
+        assert_eq!(image.pixel_index(0, 1, 0), Some(3));
+        assert_eq!(image.pixel_index(2, 1, 0), Some(5));
+        assert_eq!(image.pixel_index(3, 0, 0), None);
+        assert_eq!(image.pixel_index(0, 2, 0), None);
+
+        let view = image.view::<i32, 2>("color").unwrap();
+        assert_eq!(view.get(1, 1, 0), Some(&[4, 7]));
+        assert_eq!(view.get(0, 0, 1), None);
+        assert_eq!(indices(view.row(1, 0).unwrap()), [3, 4, 5]);
+        assert_eq!(indices(view.slice(0).unwrap()), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(view.slice(1), None);
+        let rows: Vec<Vec<i32>> = view.rows().map(indices).collect();
+        assert_eq!(rows, [[0, 1, 2], [3, 4, 5]]);
+    }
+
+    #[test]
+    fn indexes_3d_images() {
+        let image = image(ImageBuilder::image_3d(2, 3, 4), 24);
+        assert_eq!((image.width(), image.height(), image.depth()), (2, 3, 4));
+        assert_eq!(image.pixel_index(1, 2, 3), Some(23));
+        assert_eq!(image.pixel_index(1, 0, 2), Some(13));
+        assert_eq!(image.pixel_index(0, 0, 4), None);
+        assert_eq!(image.cube_pixel_index(CubeFace::PositiveX, 0, 0), None);
+
+        let view = image.view::<i32, 2>("color").unwrap();
+        assert_eq!(view.depth(), 4);
+        assert_eq!(view.get(1, 2, 3), Some(&[23, 7]));
+        assert_eq!(indices(view.row(2, 1).unwrap()), [10, 11]);
+        assert_eq!(view.row(3, 0), None);
+        assert_eq!(indices(view.slice(3).unwrap()), [18, 19, 20, 21, 22, 23]);
+        assert_eq!(view.slice(4), None);
+        assert_eq!(view.rows().count(), 12);
+        let slices: Vec<i32> = view.slices().map(|slice| slice[0][0]).collect();
+        assert_eq!(slices, [0, 6, 12, 18]);
+    }
+
+    #[test]
+    fn indexes_cube_faces_in_order() {
+        assert_eq!(
+            CubeFace::ALL
+                .iter()
+                .map(|&face| face as usize)
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5]
+        );
+
+        let image = image(ImageBuilder::cube(2, 3), 36);
+        assert_eq!((image.width(), image.height(), image.depth()), (2, 3, 6));
+        let order = [
+            CubeFace::PositiveX,
+            CubeFace::NegativeX,
+            CubeFace::PositiveY,
+            CubeFace::NegativeY,
+            CubeFace::PositiveZ,
+            CubeFace::NegativeZ,
+        ];
+        let view = image.view::<i32, 2>("color").unwrap();
+        for (i, &face) in order.iter().enumerate() {
+            assert_eq!(image.cube_pixel_index(face, 0, 0), Some(i * 6));
+            assert_eq!(image.cube_pixel_index(face, 1, 2), Some(i * 6 + 5));
+            assert_eq!(image.cube_pixel_index(face, 2, 0), None);
+            assert_eq!(image.cube_pixel_index(face, 0, 3), None);
+            assert_eq!(view.get_face(face, 1, 1), Some(&[i as i32 * 6 + 3, 7]));
+            assert_eq!(view.slice(i).unwrap()[0][0], i as i32 * 6);
+        }
+        assert_eq!(image.pixel_index(0, 0, 6), None);
+        assert_eq!(view.slices().count(), 6);
+    }
+
+    #[test]
+    fn views_layers_with_matching_components() {
+        let image = image(ImageBuilder::image_2d(3, 2), 6);
+        assert!(image.view::<i32, 2>("color").is_some());
+        assert!(image.view::<i32, 1>("color").is_none());
+        assert!(image.view::<i32, 3>("color").is_none());
+        assert!(image.view::<f32, 2>("color").is_none());
+        assert!(image.view::<i32, 2>("missing").is_none());
+
+        // Layers must have exactly one element per pixel.
+        let mut short = image.clone();
+        short.resolution = [3, 1, 1];
+        assert!(short.view::<i32, 2>("color").is_none());
+        let mut long = image;
+        long.resolution = [4, 2, 1];
+        assert!(long.view::<i32, 2>("color").is_none());
+    }
+}
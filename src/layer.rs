@@ -0,0 +1,350 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+use crate::{Layer, LayerData, LayerDataType};
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for i32 {}
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
+}
+
+/// A scalar type that layer data can be made of.
+pub trait LayerComponent: Copy + sealed::Sealed {
+    const TYPE: LayerDataType;
+
+    fn into_data(data: Vec<Self>) -> LayerData<'static>;
+
+    fn from_data<'d>(data: &'d LayerData<'_>) -> Option<&'d [Self]>;
+
+    /// Converts from `f64`, saturating at the bounds of integer types.
+    fn from_f64(value: f64) -> Self;
+
+    fn to_f64(self) -> f64;
+}
+
+impl LayerComponent for u8 {
+    const TYPE: LayerDataType = LayerDataType::Uint8;
+
+    fn into_data(data: Vec<Self>) -> LayerData<'static> {
+        LayerData::Uint8(Cow::Owned(data))
+    }
+
+    fn from_data<'d>(data: &'d LayerData<'_>) -> Option<&'d [Self]> {
+        match data {
+            LayerData::Uint8(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as u8
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl LayerComponent for i32 {
+    const TYPE: LayerDataType = LayerDataType::Int32;
+
+    fn into_data(data: Vec<Self>) -> LayerData<'static> {
+        LayerData::Int32(Cow::Owned(data))
+    }
+
+    fn from_data<'d>(data: &'d LayerData<'_>) -> Option<&'d [Self]> {
+        match data {
+            LayerData::Int32(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as i32
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl LayerComponent for f32 {
+    const TYPE: LayerDataType = LayerDataType::Float;
+
+    fn into_data(data: Vec<Self>) -> LayerData<'static> {
+        LayerData::Float(Cow::Owned(data))
+    }
+
+    fn from_data<'d>(data: &'d LayerData<'_>) -> Option<&'d [Self]> {
+        match data {
+            LayerData::Float(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl LayerComponent for f64 {
+    const TYPE: LayerDataType = LayerDataType::Double;
+
+    fn into_data(data: Vec<Self>) -> LayerData<'static> {
+        LayerData::Double(Cow::Owned(data))
+    }
+
+    fn from_data<'d>(data: &'d LayerData<'_>) -> Option<&'d [Self]> {
+        match data {
+            LayerData::Double(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// A component type that vertex positions can be stored as.
+pub trait FloatComponent: LayerComponent {}
+
+impl FloatComponent for f32 {}
+impl FloatComponent for f64 {}
+
+/// A single element of a layer, i.e. one value per vertex, corner, edge, face
+/// or pixel. This is either a bare component or an array of components.
+pub trait LayerElement: Copy {
+    type Component: LayerComponent;
+    const COMPONENTS: u8;
+
+    fn components(&self) -> &[Self::Component];
+}
+
+impl<T: LayerComponent> LayerElement for T {
+    type Component = T;
+    const COMPONENTS: u8 = 1;
+
+    fn components(&self) -> &[T] {
+        core::slice::from_ref(self)
+    }
+}
+
+impl<T: LayerComponent, const N: usize> LayerElement for [T; N] {
+    type Component = T;
+    const COMPONENTS: u8 = {
+        // Fails to compile for larger arrays, whose count wouldn't fit.
+        assert!(N <= 255, "layer elements have at most 255 components");
+        N as u8
+    };
+
+    fn components(&self) -> &[T] {
+        self
+    }
+}
+
+impl<'a> Layer<'a> {
+    /// Creates a layer holding a copy of `elements`.
+    pub fn from_slice<E: LayerElement>(name: impl Into<Cow<'a, str>>, elements: &[E]) -> Self {
+        let mut data = Vec::with_capacity(elements.len() * E::COMPONENTS as usize);
+        for element in elements {
+            data.extend_from_slice(element.components());
+        }
+        Self {
+            name: name.into(),
+            component_count: E::COMPONENTS,
+            type_: E::Component::TYPE,
+            data: E::Component::into_data(data),
+        }
+    }
+
+    /// The layer's components, if they are of type `T`.
+    pub fn as_slice<T: LayerComponent>(&self) -> Option<&[T]> {
+        T::from_data(&self.data)
+    }
+
+    /// The layer's elements, if they are made of `N` components of type `T`.
+    pub fn as_elements<T: LayerComponent, const N: usize>(&self) -> Option<&[[T; N]]> {
+        let data = self.as_slice::<T>()?;
+        if N == 0 || self.component_count as usize != N || data.len() % N != 0 {
+            return None;
+        }
+        // SAFETY: `[T; N]` has the same alignment as `T`, and `data` holds
+        // exactly `data.len() / N` of them.
+        Some(unsafe { core::slice::from_raw_parts(data.as_ptr().cast(), data.len() / N) })
+    }
+
+    pub fn as_vec2<T: LayerComponent>(&self) -> Option<&[[T; 2]]> {
+        self.as_elements()
+    }
+
+    pub fn as_vec3<T: LayerComponent>(&self) -> Option<&[[T; 3]]> {
+        self.as_elements()
+    }
+
+    pub fn as_vec4<T: LayerComponent>(&self) -> Option<&[[T; 4]]> {
+        self.as_elements()
+    }
+
+    /// Copies the layer's elements into `N` components of type `T`,
+    /// converting from whatever type the layer is stored as.
+    ///
+    /// Returns `None` if the layer doesn't have `N` components per element.
+    pub fn to_elements<T: LayerComponent, const N: usize>(&self) -> Option<Vec<[T; N]>> {
+        fn convert<S: LayerComponent, T: LayerComponent, const N: usize>(
+            data: &[S],
+        ) -> Vec<[T; N]> {
+            data.chunks_exact(N)
+                .map(|element| {
+                    let mut converted = [T::from_f64(0.0); N];
+                    for (to, from) in converted.iter_mut().zip(element) {
+                        *to = T::from_f64(from.to_f64());
+                    }
+                    converted
+                })
+                .collect()
+        }
+
+        if N == 0 || self.component_count as usize != N || self.data.len() % N != 0 {
+            return None;
+        }
+        Some(match &self.data {
+            LayerData::Uint8(data) => convert(data),
+            LayerData::Int32(data) => convert(data),
+            LayerData::Float(data) => convert(data),
+            LayerData::Double(data) => convert(data),
+        })
+    }
+
+    pub fn to_vec2_f32(&self) -> Option<Vec<[f32; 2]>> {
+        self.to_elements()
+    }
+
+    pub fn to_vec3_f32(&self) -> Option<Vec<[f32; 3]>> {
+        self.to_elements()
+    }
+
+    pub fn to_vec4_f32(&self) -> Option<Vec<[f32; 4]>> {
+        self.to_elements()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{Hxa, HxaVersion, LayerStack, Node, NodeContent, NodeImage, NodeType};
+
+    #[test]
+    fn stores_elements_as_components() {
+        let layer = Layer::from_slice("color", &[[1u8, 2, 3], [4, 5, 6]]);
+        assert_eq!(layer.component_count, 3);
+        assert_eq!(layer.type_, LayerDataType::Uint8);
+        assert_eq!(layer.as_slice::<u8>(), Some(&[1, 2, 3, 4, 5, 6][..]));
+
+        let layer = Layer::from_slice("material", &[7, -1]);
+        assert_eq!(layer.component_count, 1);
+        assert_eq!(layer.type_, LayerDataType::Int32);
+        assert_eq!(layer.as_slice::<i32>(), Some(&[7, -1][..]));
+        assert_eq!(layer.as_slice::<u8>(), None);
+        assert_eq!(layer.as_slice::<f32>(), None);
+    }
+
+    #[test]
+    fn views_components_as_elements() {
+        let layer = Layer::from_slice("uv", &[[0.5f32, 1.0], [0.0, 0.25]]);
+        assert_eq!(layer.as_vec2::<f32>(), Some(&[[0.5, 1.0], [0.0, 0.25]][..]));
+        assert_eq!(layer.as_elements::<f32, 1>(), None);
+        assert_eq!(layer.as_vec4::<f32>(), None);
+        assert_eq!(layer.as_vec2::<f64>(), None);
+        assert_eq!(layer.as_elements::<f32, 0>(), None);
+
+        // The component count and the data don't agree.
+        let mut layer = Layer::from_slice("vertex", &[0.0f64; 10]);
+        layer.component_count = 3;
+        assert_eq!(layer.as_vec3::<f64>(), None);
+        assert_eq!(layer.to_vec3_f32(), None);
+    }
+
+    #[test]
+    fn views_borrowed_and_copied_data() {
+        let positions = [[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let image = Node {
+            type_: NodeType::Image,
+            metadata: Vec::new(),
+            content: Some(NodeContent::Image(NodeImage {
+                type_: crate::ImageType::Image1D,
+                resolution: [2, 1, 1],
+                image_stack: LayerStack {
+                    layers: vec![Layer::from_slice("normal", &positions)],
+                },
+            })),
+        };
+        let bytes = Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![image],
+        }
+        .to_bytes()
+        .unwrap();
+
+        // At some of these offsets the layer is borrowed from the buffer, and
+        // at the others it is copied out of it, since it would be misaligned.
+        let mut buffer = vec![0; bytes.len() + 8];
+        for offset in 0..4 {
+            let start = buffer.as_ptr().align_offset(4) + offset;
+            buffer[start..start + bytes.len()].copy_from_slice(&bytes);
+            let hxa = Hxa::new(&buffer[start..start + bytes.len()]).unwrap();
+            let layer = match &hxa.nodes[0].content {
+                Some(NodeContent::Image(image)) => &image.image_stack.layers[0],
+                _ => unreachable!(),
+            };
+            assert_eq!(layer.as_vec3::<f32>(), Some(&positions[..]));
+        }
+    }
+
+    #[test]
+    fn converts_between_component_types() {
+        let bytes = Layer::from_slice("color", &[[0u8, 128], [255, 1]]);
+        assert_eq!(bytes.to_vec2_f32(), Some(vec![[0.0, 128.0], [255.0, 1.0]]));
+        let ints = Layer::from_slice("ids", &[[-7, i32::MAX, 0]]);
+        assert_eq!(ints.to_vec3_f32(), Some(vec![[-7.0, 2147483648.0, 0.0]]));
+        let floats = Layer::from_slice("color", &[[0.25f32, 0.5, 0.75, 1.0]]);
+        assert_eq!(floats.to_vec4_f32(), Some(vec![[0.25, 0.5, 0.75, 1.0]]));
+        let doubles = Layer::from_slice("vertex", &[[0.1f64, 1e300, -1e300]]);
+        assert_eq!(
+            doubles.to_vec3_f32(),
+            Some(vec![[0.1, f32::INFINITY, f32::NEG_INFINITY]])
+        );
+        assert_eq!(
+            doubles.to_elements::<f64, 3>(),
+            Some(vec![[0.1, 1e300, -1e300]])
+        );
+
+        // Narrowing into integers saturates.
+        let floats = Layer::from_slice("weight", &[[300.0f32, -5.0, f32::NAN, 1.9]]);
+        assert_eq!(floats.to_elements::<u8, 4>(), Some(vec![[255, 0, 0, 1]]));
+        assert_eq!(
+            doubles.to_elements::<i32, 3>(),
+            Some(vec![[0, i32::MAX, i32::MIN]])
+        );
+        assert_eq!(
+            bytes.to_elements::<i32, 2>(),
+            Some(vec![[0, 128], [255, 1]])
+        );
+
+        assert_eq!(bytes.to_vec3_f32(), None);
+        assert_eq!(ints.to_vec2_f32(), None);
+    }
+}
@@ -4,21 +4,75 @@ extern crate alloc;
 
 use alloc::borrow::Cow;
 use alloc::vec::Vec;
+use core::fmt;
 
+mod adjacency;
+mod build;
 mod error;
+mod geometry;
+mod image;
+mod layer;
+mod meta;
+mod owned;
 mod parse;
+mod scene;
+mod stack;
+mod transform;
+mod triangulate;
+mod validate;
+mod write;
 
-pub use error::{HxaError, HxaResult};
+pub use adjacency::Adjacency;
+pub use build::{GeometryBuilder, ImageBuilder, MetaBuilder};
+pub use error::{HxaError, HxaResult, Limit, ParseError, PathSegment};
+pub use geometry::{decode_reference, Polygon, PolygonStarts, Polygons};
+pub use image::{CubeFace, ImageView};
+pub use layer::{FloatComponent, LayerComponent, LayerElement};
+pub use meta::MetaWalk;
+pub use parse::{ParseOptions, TrailingData};
+pub use scene::{Descendants, SceneGraph, Visit};
+pub use transform::{Instance, Transform};
+pub use triangulate::Triangulation;
+pub use validate::{Diagnostic, DiagnosticKind};
 
 #[derive(Clone, Debug)]
 pub struct Hxa<'a> {
-    pub version: u8,
+    pub version: HxaVersion,
     pub nodes: Vec<Node<'a>>,
 }
 
 impl<'a> Hxa<'a> {
     pub fn new(data: &'a [u8]) -> HxaResult<Self> {
-        Self::parse(data)
+        Self::parse(data, &ParseOptions::default()).map(|(hxa, _)| hxa)
+    }
+
+    /// Parses a file from the start of `data`, returning it along with the
+    /// number of bytes that follow it. That number is always 0 unless
+    /// `options.trailing_data` is [`TrailingData::Allow`].
+    pub fn new_with_options(data: &'a [u8], options: &ParseOptions) -> HxaResult<(Self, usize)> {
+        Self::parse(data, options)
+    }
+}
+
+/// The revision of the file format.
+///
+/// Versions 1 and 2 share the same layout. Version 3 added the edge stack to
+/// geometry nodes, which is left empty when reading older files. Files are
+/// written in the layout of whichever version `Hxa::version` is set to, so
+/// older versions can be emitted as long as no edge layers are present.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum HxaVersion {
+    V1 = 1,
+    V2 = 2,
+    V3 = 3,
+}
+
+impl HxaVersion {
+    pub const LATEST: HxaVersion = HxaVersion::V3;
+
+    pub fn has_edge_stack(self) -> bool {
+        self >= HxaVersion::V3
     }
 }
 
@@ -55,6 +109,27 @@ pub struct LayerStack<'a> {
     pub layers: Vec<Layer<'a>>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackKind {
+    Vertex,
+    Corner,
+    Edge,
+    Face,
+    Image,
+}
+
+impl fmt::Display for StackKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StackKind::Vertex => "vertex",
+            StackKind::Corner => "corner",
+            StackKind::Edge => "edge",
+            StackKind::Face => "face",
+            StackKind::Image => "image",
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Layer<'a> {
     pub name: Cow<'a, str>,
@@ -71,6 +146,30 @@ pub enum LayerData<'a> {
     Double(Cow<'a, [f64]>),
 }
 
+impl LayerData<'_> {
+    pub fn type_(&self) -> LayerDataType {
+        match self {
+            LayerData::Uint8(_) => LayerDataType::Uint8,
+            LayerData::Int32(_) => LayerDataType::Int32,
+            LayerData::Float(_) => LayerDataType::Float,
+            LayerData::Double(_) => LayerDataType::Double,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            LayerData::Uint8(data) => data.len(),
+            LayerData::Int32(data) => data.len(),
+            LayerData::Float(data) => data.len(),
+            LayerData::Double(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Meta<'a> {
     pub name: Cow<'a, str>,
@@ -88,6 +187,19 @@ pub enum MetaValue<'a> {
     Meta(Vec<Meta<'a>>),
 }
 
+impl MetaValue<'_> {
+    pub fn type_(&self) -> MetadataType {
+        match self {
+            MetaValue::Int64(_) => MetadataType::Int64,
+            MetaValue::Double(_) => MetadataType::Double,
+            MetaValue::Node(_) => MetadataType::Node,
+            MetaValue::Text(_) => MetadataType::Text,
+            MetaValue::Bin(_) => MetadataType::Binary,
+            MetaValue::Meta(_) => MetadataType::Meta,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum NodeType {
@@ -0,0 +1,244 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::slice;
+
+use crate::{Meta, MetaValue, Node};
+
+impl<'a> Node<'a> {
+    /// Looks up metadata by a `/`-separated path of names, descending into
+    /// nested metadata for each segment, e.g. `"material/albedo/texture"`.
+    ///
+    /// When several entries at the same level share a name, the first one is
+    /// used.
+    pub fn meta(&self, path: &str) -> Option<&Meta<'a>> {
+        let mut segments = path.split('/');
+        let mut meta = find(&self.metadata, segments.next()?)?;
+        for segment in segments {
+            match &meta.value {
+                MetaValue::Meta(children) => meta = find(children, segment)?,
+                _ => return None,
+            }
+        }
+        Some(meta)
+    }
+
+    pub fn meta_i64s(&self, path: &str) -> Option<&[i64]> {
+        self.meta(path)?.value.as_i64s()
+    }
+
+    pub fn meta_f64s(&self, path: &str) -> Option<&[f64]> {
+        self.meta(path)?.value.as_f64s()
+    }
+
+    pub fn meta_node_refs(&self, path: &str) -> Option<&[u32]> {
+        self.meta(path)?.value.as_node_refs()
+    }
+
+    pub fn meta_text(&self, path: &str) -> Option<&str> {
+        self.meta(path)?.value.as_text()
+    }
+
+    pub fn meta_bytes(&self, path: &str) -> Option<&[u8]> {
+        self.meta(path)?.value.as_bytes()
+    }
+
+    /// Walks all of the node's metadata depth-first, parents before their
+    /// children, yielding each entry along with its path.
+    pub fn meta_walk(&self) -> MetaWalk<'_, 'a> {
+        MetaWalk {
+            stack: alloc::vec![(0, self.metadata.iter())],
+            path: String::new(),
+        }
+    }
+}
+
+fn find<'m, 'a>(metadata: &'m [Meta<'a>], name: &str) -> Option<&'m Meta<'a>> {
+    metadata.iter().find(|meta| meta.name == name)
+}
+
+impl<'a> MetaValue<'a> {
+    pub fn as_i64s(&self) -> Option<&[i64]> {
+        match self {
+            MetaValue::Int64(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64s(&self) -> Option<&[f64]> {
+        match self {
+            MetaValue::Double(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_node_refs(&self) -> Option<&[u32]> {
+        match self {
+            MetaValue::Node(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            MetaValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            MetaValue::Bin(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_meta(&self) -> Option<&[Meta<'a>]> {
+        match self {
+            MetaValue::Meta(children) => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// A depth-first iterator over nested metadata, returned by
+/// [`Node::meta_walk`].
+///
+/// Paths are built the same way [`Node::meta`] splits them, so names that
+/// contain a `/` produce paths that can't be looked up again.
+#[derive(Clone, Debug)]
+pub struct MetaWalk<'m, 'a> {
+    /// The length of the parent's path and the remaining siblings, for each
+    /// level of nesting entered so far.
+    stack: Vec<(usize, slice::Iter<'m, Meta<'a>>)>,
+    path: String,
+}
+
+impl<'m, 'a> Iterator for MetaWalk<'m, 'a> {
+    type Item = (String, &'m Meta<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let depth = self.stack.len();
+            let (parent_len, siblings) = self.stack.last_mut()?;
+            let parent_len = *parent_len;
+            let meta = match siblings.next() {
+                Some(meta) => meta,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            self.path.truncate(parent_len);
+            if depth > 1 {
+                self.path.push('/');
+            }
+            self.path.push_str(&meta.name);
+            if let MetaValue::Meta(children) = &meta.value {
+                self.stack.push((self.path.len(), children.iter()));
+            }
+            return Some((self.path.clone(), meta));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MetaBuilder, NodeType};
+
+    fn node() -> Node<'static> {
+        let albedo = MetaBuilder::new()
+            .node("texture", &[3])
+            .double("factor", &[0.5, 0.25, 1.0]);
+        let material = MetaBuilder::new()
+            .text("name", "steel")
+            .meta("albedo", albedo)
+            .binary("blob", &[1, 2]);
+        Node {
+            type_: NodeType::Meta,
+            metadata: MetaBuilder::new()
+                .int64("id", &[42])
+                .meta("material", material)
+                .text("name", "root")
+                .text("name", "shadowed")
+                .build(),
+            content: None,
+        }
+    }
+
+    #[test]
+    fn looks_up_nested_paths() {
+        let node = node();
+        assert_eq!(node.meta_i64s("id"), Some(&[42][..]));
+        assert_eq!(node.meta_text("material/name"), Some("steel"));
+        assert_eq!(
+            node.meta_node_refs("material/albedo/texture"),
+            Some(&[3][..])
+        );
+        assert_eq!(
+            node.meta_f64s("material/albedo/factor"),
+            Some(&[0.5, 0.25, 1.0][..])
+        );
+        assert_eq!(node.meta_bytes("material/blob"), Some(&[1, 2][..]));
+        assert_eq!(node.meta("material/albedo").unwrap().name, "albedo");
+        assert!(node.meta("material").unwrap().value.as_meta().is_some());
+
+        // The first of several entries with the same name wins.
+        assert_eq!(node.meta_text("name"), Some("root"));
+    }
+
+    #[test]
+    fn misses_absent_paths() {
+        let node = node();
+        assert!(node.meta("").is_none());
+        assert!(node.meta("missing").is_none());
+        assert!(node.meta("material/missing").is_none());
+        assert!(node.meta("material/").is_none());
+        assert!(node.meta("/material").is_none());
+        // Only `Meta` values can be descended into.
+        assert!(node.meta("id/anything").is_none());
+        assert!(node.meta("material/name/anything").is_none());
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        let node = node();
+        assert_eq!(node.meta_f64s("id"), None);
+        assert_eq!(node.meta_text("id"), None);
+        assert_eq!(node.meta_i64s("material/albedo/factor"), None);
+        assert_eq!(node.meta_node_refs("material/name"), None);
+        assert_eq!(node.meta_bytes("material/name"), None);
+        assert_eq!(node.meta_text("material"), None);
+        assert!(node.meta("id").unwrap().value.as_meta().is_none());
+    }
+
+    #[test]
+    fn walks_depth_first() {
+        let node = node();
+        let paths: Vec<String> = node.meta_walk().map(|(path, _)| path).collect();
+        assert_eq!(
+            paths,
+            [
+                "id",
+                "material",
+                "material/name",
+                "material/albedo",
+                "material/albedo/texture",
+                "material/albedo/factor",
+                "material/blob",
+                "name",
+                "name",
+            ]
+        );
+        for (path, meta) in node.meta_walk().take(7) {
+            assert!(core::ptr::eq(node.meta(&path).unwrap(), meta));
+        }
+
+        let empty = Node {
+            metadata: Vec::new(),
+            ..node
+        };
+        assert_eq!(empty.meta_walk().count(), 0);
+    }
+}
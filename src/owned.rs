@@ -0,0 +1,243 @@
+use alloc::borrow::{Cow, ToOwned};
+
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::{
+    Hxa, Layer, LayerData, LayerStack, Meta, MetaValue, Node, NodeContent, NodeGeometry, NodeImage,
+};
+#[cfg(feature = "std")]
+use crate::{HxaError, HxaResult, ParseOptions};
+
+fn owned<T: ?Sized + ToOwned>(cow: Cow<'_, T>) -> Cow<'static, T> {
+    Cow::Owned(cow.into_owned())
+}
+
+impl Hxa<'_> {
+    /// Copies everything still borrowed from the input buffer, detaching the
+    /// file from it.
+    pub fn into_owned(self) -> Hxa<'static> {
+        Hxa {
+            version: self.version,
+            nodes: self.nodes.into_iter().map(Node::into_owned).collect(),
+        }
+    }
+
+    /// Like [`into_owned`](Self::into_owned), but leaves `self` as it is,
+    /// copying even the parts it already owns.
+    pub fn to_owned_static(&self) -> Hxa<'static> {
+        Hxa {
+            version: self.version,
+            nodes: self.nodes.iter().map(Node::to_owned_static).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Hxa<'static> {
+    /// Reads and parses a whole file from `reader`.
+    pub fn from_reader<R: io::Read>(mut reader: R) -> HxaResult<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).map_err(HxaError::Io)?;
+        // Arrays would have to be copied out of `data` either way, so they
+        // might as well be decoded into owned buffers right away.
+        let options = ParseOptions {
+            zero_copy: false,
+            ..ParseOptions::default()
+        };
+        Hxa::parse(&data, &options).map(|(hxa, _)| hxa.into_owned())
+    }
+}
+
+impl Node<'_> {
+    pub fn into_owned(self) -> Node<'static> {
+        Node {
+            type_: self.type_,
+            metadata: self.metadata.into_iter().map(Meta::into_owned).collect(),
+            content: self.content.map(NodeContent::into_owned),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> Node<'static> {
+        Node {
+            type_: self.type_.clone(),
+            metadata: self.metadata.iter().map(Meta::to_owned_static).collect(),
+            content: self.content.as_ref().map(NodeContent::to_owned_static),
+        }
+    }
+}
+
+impl NodeContent<'_> {
+    pub fn into_owned(self) -> NodeContent<'static> {
+        match self {
+            NodeContent::Geometry(geometry) => NodeContent::Geometry(geometry.into_owned()),
+            NodeContent::Image(image) => NodeContent::Image(image.into_owned()),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> NodeContent<'static> {
+        match self {
+            NodeContent::Geometry(geometry) => NodeContent::Geometry(geometry.to_owned_static()),
+            NodeContent::Image(image) => NodeContent::Image(image.to_owned_static()),
+        }
+    }
+}
+
+impl NodeGeometry<'_> {
+    pub fn into_owned(self) -> NodeGeometry<'static> {
+        NodeGeometry {
+            vertex_stack: self.vertex_stack.into_owned(),
+            corner_stack: self.corner_stack.into_owned(),
+            edge_stack: self.edge_stack.into_owned(),
+            face_stack: self.face_stack.into_owned(),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> NodeGeometry<'static> {
+        NodeGeometry {
+            vertex_stack: self.vertex_stack.to_owned_static(),
+            corner_stack: self.corner_stack.to_owned_static(),
+            edge_stack: self.edge_stack.to_owned_static(),
+            face_stack: self.face_stack.to_owned_static(),
+        }
+    }
+}
+
+impl NodeImage<'_> {
+    pub fn into_owned(self) -> NodeImage<'static> {
+        NodeImage {
+            type_: self.type_,
+            resolution: self.resolution,
+            image_stack: self.image_stack.into_owned(),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> NodeImage<'static> {
+        NodeImage {
+            type_: self.type_.clone(),
+            resolution: self.resolution,
+            image_stack: self.image_stack.to_owned_static(),
+        }
+    }
+}
+
+impl LayerStack<'_> {
+    pub fn into_owned(self) -> LayerStack<'static> {
+        LayerStack {
+            layers: self.layers.into_iter().map(Layer::into_owned).collect(),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> LayerStack<'static> {
+        LayerStack {
+            layers: self.layers.iter().map(Layer::to_owned_static).collect(),
+        }
+    }
+}
+
+impl Layer<'_> {
+    pub fn into_owned(self) -> Layer<'static> {
+        Layer {
+            name: owned(self.name),
+            component_count: self.component_count,
+            type_: self.type_,
+            data: self.data.into_owned(),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> Layer<'static> {
+        Layer {
+            name: owned(self.name.clone()),
+            component_count: self.component_count,
+            type_: self.type_.clone(),
+            data: self.data.to_owned_static(),
+        }
+    }
+}
+
+impl LayerData<'_> {
+    pub fn into_owned(self) -> LayerData<'static> {
+        match self {
+            LayerData::Uint8(data) => LayerData::Uint8(owned(data)),
+            LayerData::Int32(data) => LayerData::Int32(owned(data)),
+            LayerData::Float(data) => LayerData::Float(owned(data)),
+            LayerData::Double(data) => LayerData::Double(owned(data)),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> LayerData<'static> {
+        match self {
+            LayerData::Uint8(data) => LayerData::Uint8(owned(data.clone())),
+            LayerData::Int32(data) => LayerData::Int32(owned(data.clone())),
+            LayerData::Float(data) => LayerData::Float(owned(data.clone())),
+            LayerData::Double(data) => LayerData::Double(owned(data.clone())),
+        }
+    }
+}
+
+impl Meta<'_> {
+    pub fn into_owned(self) -> Meta<'static> {
+        Meta {
+            name: owned(self.name),
+            type_: self.type_,
+            value: self.value.into_owned(),
+        }
+    }
+
+    pub fn to_owned_static(&self) -> Meta<'static> {
+        Meta {
+            name: owned(self.name.clone()),
+            type_: self.type_.clone(),
+            value: self.value.to_owned_static(),
+        }
+    }
+}
+
+impl MetaValue<'_> {
+    pub fn into_owned(self) -> MetaValue<'static> {
+        match self {
+            MetaValue::Int64(data) => MetaValue::Int64(owned(data)),
+            MetaValue::Double(data) => MetaValue::Double(owned(data)),
+            MetaValue::Node(data) => MetaValue::Node(owned(data)),
+            MetaValue::Text(data) => MetaValue::Text(owned(data)),
+            MetaValue::Bin(data) => MetaValue::Bin(owned(data)),
+            MetaValue::Meta(metadata) => {
+                MetaValue::Meta(metadata.into_iter().map(Meta::into_owned).collect())
+            }
+        }
+    }
+
+    pub fn to_owned_static(&self) -> MetaValue<'static> {
+        match self {
+            MetaValue::Int64(data) => MetaValue::Int64(owned(data.clone())),
+            MetaValue::Double(data) => MetaValue::Double(owned(data.clone())),
+            MetaValue::Node(data) => MetaValue::Node(owned(data.clone())),
+            MetaValue::Text(data) => MetaValue::Text(owned(data.clone())),
+            MetaValue::Bin(data) => MetaValue::Bin(owned(data.clone())),
+            MetaValue::Meta(metadata) => {
+                MetaValue::Meta(metadata.iter().map(Meta::to_owned_static).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use crate::Hxa;
+
+    #[test]
+    fn copies_without_consuming() {
+        let data = include_bytes!("../examples/teapot.hxa").to_vec();
+        let hxa = Hxa::new(&data).unwrap();
+        let copy = hxa.to_owned_static();
+        let expected = format!("{:?}", hxa);
+        drop(hxa);
+        drop(data);
+        assert_eq!(format!("{:?}", copy), expected);
+        assert_eq!(format!("{:?}", copy.to_owned_static()), expected);
+    }
+}
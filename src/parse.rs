@@ -1,14 +1,17 @@
 use alloc::borrow::{Cow, ToOwned};
+use alloc::boxed::Box;
 use alloc::str;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::convert::{TryFrom, TryInto};
 use core::mem;
 use core::slice;
 
 use crate::{
-    error::{InternalError, InternalErrorKind},
-    Hxa, HxaError, HxaResult, ImageType, Layer, LayerData, LayerDataType, LayerStack, Meta,
-    MetaValue, MetadataType, Node, NodeContent, NodeGeometry, NodeImage, NodeType,
+    error::{InternalError, InternalErrorKind, Limit, ParseError, PathSegment},
+    image::pixel_count,
+    Hxa, HxaError, HxaResult, HxaVersion, ImageType, Layer, LayerData, LayerDataType, LayerStack,
+    Meta, MetaValue, MetadataType, Node, NodeContent, NodeGeometry, NodeImage, NodeType, StackKind,
 };
 
 trait FromData: Sized {
@@ -25,7 +28,7 @@ impl FromData for u8 {
     const SIZE: usize = 1;
 
     fn _parse(data: &[u8]) -> HxaResult<Self> {
-        data.get(0).copied().ok_or(HxaError::UnexpectedEndOfData)
+        data.first().copied().ok_or(HxaError::UnexpectedEndOfData)
     }
 }
 
@@ -33,7 +36,7 @@ impl FromData for i8 {
     const SIZE: usize = 1;
 
     fn _parse(data: &[u8]) -> HxaResult<Self> {
-        data.get(0)
+        data.first()
             .copied()
             .ok_or(HxaError::UnexpectedEndOfData)
             .map(|n| n as i8)
@@ -135,6 +138,19 @@ impl FromData for f64 {
     }
 }
 
+impl FromData for HxaVersion {
+    const SIZE: usize = 4;
+
+    fn _parse(data: &[u8]) -> HxaResult<Self> {
+        u32::_parse(data).and_then(|n| match n {
+            1 => Ok(Self::V1),
+            2 => Ok(Self::V2),
+            3 => Ok(Self::V3),
+            _ => Err(HxaError::UnsupportedVersion(n)),
+        })
+    }
+}
+
 impl FromData for NodeType {
     const SIZE: usize = 1;
 
@@ -192,26 +208,109 @@ impl FromData for MetadataType {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// Borrow layer and metadata arrays straight from the input buffer where
+    /// possible. Turning this off decodes every array into an owned buffer,
+    /// the same way it always happens on big-endian targets.
+    pub zero_copy: bool,
+    pub trailing_data: TrailingData,
+    pub max_nodes: usize,
+    pub max_layers_per_stack: usize,
+    /// How deeply `Meta` values may nest. Node metadata is at depth 1.
+    pub max_metadata_depth: usize,
+    /// The total number of bytes the parser may allocate for nodes, layers,
+    /// metadata and any arrays it has to copy out of the input buffer.
+    pub max_allocation: usize,
+    /// The largest size allowed along any axis of an image.
+    pub max_image_resolution: u32,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            zero_copy: true,
+            trailing_data: TrailingData::Reject,
+            max_nodes: usize::MAX,
+            max_layers_per_stack: usize::MAX,
+            max_metadata_depth: 64,
+            max_allocation: usize::MAX,
+            max_image_resolution: u32::MAX,
+        }
+    }
+}
+
+/// What to do with bytes that follow the last node of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrailingData {
+    /// Fail with [`HxaError::TrailingData`].
+    Reject,
+    /// Stop at the end of the last node, and report how many bytes were left
+    /// over from [`Hxa::new_with_options`].
+    Allow,
+}
+
 struct Cursor<'a> {
-    // base_addr: usize,
+    base_len: usize,
     data: &'a [u8],
+    /// Offset at which the most recent read started.
+    mark: usize,
+    /// Breadcrumbs leading to whatever is currently being parsed. These are
+    /// pushed on the way down and popped on success, so that on error they
+    /// still describe where the parser was.
+    path: Vec<Segment<'a>>,
+    options: ParseOptions,
+    allocated: usize,
+    depth: usize,
+}
+
+enum Segment<'a> {
+    Node(usize),
+    Metadata(usize, Option<&'a str>),
+    Stack(StackKind),
+    Layer(usize, Option<&'a str>),
 }
 
 impl<'a> Cursor<'a> {
-    fn new(data: &'a [u8]) -> Self {
+    fn new(data: &'a [u8], options: &ParseOptions) -> Self {
         Self {
-            // base_addr: data.as_ptr() as usize,
+            base_len: data.len(),
             data,
+            mark: 0,
+            path: Vec::new(),
+            options: options.clone(),
+            allocated: 0,
+            depth: 0,
+        }
+    }
+
+    fn allocate(&mut self, bytes: usize) -> HxaResult<()> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        if self.allocated > self.options.max_allocation {
+            return Err(HxaError::LimitExceeded(Limit::Allocation));
         }
+        Ok(())
     }
 
-    // fn offset(&self) -> usize {
-    //     self.data.as_ptr() as usize - self.base_addr
-    // }
+    /// Creates a vector for `count` elements read from the file, each of which
+    /// takes up at least `min_size` bytes in it.
+    ///
+    /// The full size of the vector counts against the allocation limit up
+    /// front, but since `count` has not been checked against the data yet,
+    /// the initial capacity is capped at what the rest of the data could hold.
+    fn vec_for<T>(&mut self, count: usize, min_size: usize) -> HxaResult<Vec<T>> {
+        self.allocate(mem::size_of::<T>().saturating_mul(count))?;
+        Ok(Vec::with_capacity(count.min(self.data.len() / min_size)))
+    }
+
+    fn offset(&self) -> usize {
+        self.base_len - self.data.len()
+    }
 
     fn take_bytes(&mut self, size: usize) -> HxaResult<&'a [u8]> {
         // trace!("offset is {}, taking {} bytes", self.offset(), size);
 
+        self.mark = self.offset();
         let bytes = self
             .data
             .get(0..size)
@@ -219,50 +318,104 @@ impl<'a> Cursor<'a> {
         self.data = &self.data[size..];
         Ok(bytes)
     }
+
+    fn enter(&mut self, segment: Segment<'a>) {
+        self.path.push(segment);
+    }
+
+    fn name(&mut self, name: &'a str) {
+        match self.path.last_mut() {
+            Some(Segment::Metadata(_, slot)) | Some(Segment::Layer(_, slot)) => *slot = Some(name),
+            _ => {}
+        }
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    fn error(&self, error: HxaError) -> HxaError {
+        let path = self
+            .path
+            .iter()
+            .map(|segment| match *segment {
+                Segment::Node(index) => PathSegment::Node(index),
+                Segment::Metadata(index, name) => PathSegment::Metadata {
+                    index,
+                    name: name.map(String::from),
+                },
+                Segment::Stack(kind) => PathSegment::Stack(kind),
+                Segment::Layer(index, name) => PathSegment::Layer {
+                    index,
+                    name: name.map(String::from),
+                },
+            })
+            .collect();
+        HxaError::Parse(Box::new(ParseError {
+            offset: self.mark,
+            path,
+            error,
+        }))
+    }
 }
 
 impl<'a> Hxa<'a> {
-    pub(crate) fn parse(data: &'a [u8]) -> HxaResult<Self> {
-        let mut cursor = Cursor::new(data);
+    pub(crate) fn parse(data: &'a [u8], options: &ParseOptions) -> HxaResult<(Self, usize)> {
+        let mut cursor = Cursor::new(data, options);
+        let hxa = Self::load(&mut cursor).map_err(|error| cursor.error(error))?;
+        let len = cursor.data.len();
+        if len > 0 && options.trailing_data == TrailingData::Reject {
+            return Err(HxaError::TrailingData {
+                offset: data.len() - len,
+                len,
+            });
+        }
+        Ok((hxa, len))
+    }
 
-        let magic_number = u32::parse(&mut cursor)?;
-        let reference = u32::from_ne_bytes(*b"HxA\0");
+    fn load<'c>(cursor: &'c mut Cursor<'a>) -> HxaResult<Self> {
+        let magic_number = u32::parse(cursor)?;
+        let reference = u32::from_le_bytes(*b"HxA\0");
         if magic_number != reference {
             return Err(HxaError::InvalidMagicNumber(magic_number));
         }
 
-        let version = u32::parse(&mut cursor)? as u8;
-        let node_count = u32::parse(&mut cursor)?.try_into().unwrap();
-        let mut nodes = Vec::with_capacity(mem::size_of::<Node>() * node_count);
-        for _ in 0..node_count {
-            nodes.push(Node::new(&mut cursor, version)?);
+        let version = HxaVersion::parse(cursor)?;
+        let node_count = load_count(cursor)?;
+        if node_count > cursor.options.max_nodes {
+            return Err(HxaError::LimitExceeded(Limit::Nodes));
+        }
+        // Node type and metadata count
+        let mut nodes = cursor.vec_for(node_count, 5)?;
+        for index in 0..node_count {
+            cursor.enter(Segment::Node(index));
+            nodes.push(Node::new(cursor, version)?);
+            cursor.leave();
         }
-
-        debug_assert!(cursor.data.is_empty());
 
         Ok(Self { version, nodes })
     }
 }
 
 impl<'a> Node<'a> {
-    fn new<'c>(cursor: &'c mut Cursor<'a>, version: u8) -> HxaResult<Self> {
+    fn new<'c>(cursor: &'c mut Cursor<'a>, version: HxaVersion) -> HxaResult<Self> {
         let type_ = NodeType::parse(cursor)?;
-        let metadata_count = u32::parse(cursor)?.try_into().unwrap();
+        let metadata_count = load_count(cursor)?;
         let metadata = Meta::load(cursor, metadata_count)?;
 
         let content = match type_ {
             NodeType::Geometry => {
-                let vertex_count = u32::parse(cursor)?.try_into().unwrap();
-                let vertex_stack = LayerStack::new(cursor, vertex_count)?;
-                let edge_corner_count = u32::parse(cursor)?.try_into().unwrap();
-                let corner_stack = LayerStack::new(cursor, edge_corner_count)?;
-                let edge_stack = if version > 2 {
-                    LayerStack::new(cursor, edge_corner_count)?
+                let vertex_count = load_count(cursor)?;
+                let vertex_stack = LayerStack::new(cursor, StackKind::Vertex, vertex_count)?;
+                let edge_corner_count = load_count(cursor)?;
+                let corner_stack = LayerStack::new(cursor, StackKind::Corner, edge_corner_count)?;
+                let edge_stack = if version.has_edge_stack() {
+                    LayerStack::new(cursor, StackKind::Edge, edge_corner_count)?
                 } else {
                     LayerStack::empty()
                 };
-                let face_count = u32::parse(cursor)?.try_into().unwrap();
-                let face_stack = LayerStack::new(cursor, face_count)?;
+                let face_count = load_count(cursor)?;
+                let face_stack = LayerStack::new(cursor, StackKind::Face, face_count)?;
 
                 Some(NodeContent::Geometry(NodeGeometry {
                     vertex_stack,
@@ -273,27 +426,21 @@ impl<'a> Node<'a> {
             }
             NodeType::Image => {
                 let type_ = ImageType::parse(cursor)?;
-                let dimensions = match type_ {
-                    ImageType::ImageCube => 2,
-                    ImageType::Image1D => 1,
-                    ImageType::Image2D => 2,
-                    ImageType::Image3D => 3,
-                };
+                let dimensions = type_.dimensions();
                 #[rustfmt::skip]
                 let resolution = [
                     u32::parse(cursor)?,
                     if dimensions >= 2 { u32::parse(cursor)? } else { 1 },
                     if dimensions >= 3 { u32::parse(cursor)? } else { 1 },
                 ];
-                #[rustfmt::skip]
-                let mut size =
-                      usize::try_from(resolution[0]).unwrap()
-                    * usize::try_from(resolution[1]).unwrap()
-                    * usize::try_from(resolution[2]).unwrap();
-                if type_ == ImageType::ImageCube {
-                    size *= 6;
+                if resolution
+                    .iter()
+                    .any(|&axis| axis > cursor.options.max_image_resolution)
+                {
+                    return Err(HxaError::LimitExceeded(Limit::ImageResolution));
                 }
-                let image_stack = LayerStack::new(cursor, size)?;
+                let size = pixel_count(&type_, resolution)?;
+                let image_stack = LayerStack::new(cursor, StackKind::Image, size)?;
 
                 Some(NodeContent::Image(NodeImage {
                     type_,
@@ -313,12 +460,20 @@ impl<'a> Node<'a> {
 }
 
 impl<'a> LayerStack<'a> {
-    fn new<'c>(cursor: &'c mut Cursor<'a>, length: usize) -> HxaResult<Self> {
-        let stack_count = u32::parse(cursor)?.try_into().unwrap();
-        let mut layers = Vec::with_capacity(mem::size_of::<Layer>() * stack_count);
-        for _ in 0..stack_count {
+    fn new<'c>(cursor: &'c mut Cursor<'a>, kind: StackKind, length: usize) -> HxaResult<Self> {
+        cursor.enter(Segment::Stack(kind));
+        let stack_count = load_count(cursor)?;
+        if stack_count > cursor.options.max_layers_per_stack {
+            return Err(HxaError::LimitExceeded(Limit::LayersPerStack));
+        }
+        // Name length, component count and data type
+        let mut layers = cursor.vec_for(stack_count, 3)?;
+        for index in 0..stack_count {
+            cursor.enter(Segment::Layer(index, None));
             layers.push(Layer::new(cursor, length)?);
+            cursor.leave();
         }
+        cursor.leave();
         Ok(Self { layers })
     }
 
@@ -330,9 +485,12 @@ impl<'a> LayerStack<'a> {
 impl<'a> Layer<'a> {
     fn new<'c>(cursor: &'c mut Cursor<'a>, length: usize) -> HxaResult<Self> {
         let name = load_name(cursor)?;
+        cursor.name(name);
         let component_count = u8::parse(cursor)?;
         let type_ = LayerDataType::parse(cursor)?;
-        let len = component_count as usize * length;
+        let len = (component_count as usize)
+            .checked_mul(length)
+            .ok_or(HxaError::CountOverflow)?;
         let data = match type_ {
             LayerDataType::Uint8 => {
                 let data = cursor.take_bytes(len)?;
@@ -362,17 +520,26 @@ impl<'a> Layer<'a> {
 
 impl<'a> Meta<'a> {
     fn load<'c>(cursor: &'c mut Cursor<'a>, count: usize) -> HxaResult<Vec<Self>> {
-        let mut metadata = Vec::with_capacity(mem::size_of::<Meta>() * count);
-        for _ in 0..count {
-            metadata.push(Meta::new(cursor)?)
+        cursor.depth += 1;
+        if cursor.depth > cursor.options.max_metadata_depth {
+            return Err(HxaError::LimitExceeded(Limit::MetadataDepth));
+        }
+        // Name length, data type and array length
+        let mut metadata = cursor.vec_for(count, 6)?;
+        for index in 0..count {
+            cursor.enter(Segment::Metadata(index, None));
+            metadata.push(Meta::new(cursor)?);
+            cursor.leave();
         }
+        cursor.depth -= 1;
         Ok(metadata)
     }
 
     fn new<'c>(cursor: &'c mut Cursor<'a>) -> HxaResult<Self> {
         let name = load_name(cursor)?;
+        cursor.name(name);
         let type_ = MetadataType::parse(cursor)?;
-        let array_length = u32::parse(cursor)?.try_into().unwrap();
+        let array_length = load_count(cursor)?;
         let value = match type_ {
             MetadataType::Int64 => {
                 let data = load_slice(cursor, array_length)?;
@@ -406,36 +573,49 @@ impl<'a> Meta<'a> {
     }
 }
 
-fn load_name<'c, 'a>(cursor: &'c mut Cursor<'a>) -> HxaResult<&'a str> {
+fn load_count(cursor: &mut Cursor<'_>) -> HxaResult<usize> {
+    usize::try_from(u32::parse(cursor)?).map_err(|_| HxaError::CountOverflow)
+}
+
+fn load_name<'a>(cursor: &mut Cursor<'a>) -> HxaResult<&'a str> {
     let length = u8::parse(cursor)?;
     core::str::from_utf8(cursor.take_bytes(length as usize)?).map_err(HxaError::InvalidUtf8)
 }
 
-fn load_slice<'c, 'a, T>(cursor: &'c mut Cursor<'a>, length: usize) -> HxaResult<Cow<'a, [T]>>
+fn load_slice<'a, T>(cursor: &mut Cursor<'a>, length: usize) -> HxaResult<Cow<'a, [T]>>
 where
     T: Pod + FromData,
     [T]: ToOwned<Owned = Vec<T>>,
 {
-    let len = mem::size_of::<T>() * length;
+    let len = mem::size_of::<T>()
+        .checked_mul(length)
+        .ok_or(HxaError::CountOverflow)?;
+    let data = cursor.take_bytes(len)?;
 
-    #[cfg(target_endian = "little")]
+    // The file is little-endian, so its arrays can only be borrowed on
+    // little-endian targets. Even then, they are only as aligned as whatever
+    // precedes them in the file, so they can only be borrowed when they happen
+    // to line up. Everything else gets decoded into a properly aligned buffer.
+    if cfg!(target_endian = "little")
+        && cursor.options.zero_copy
+        && data.as_ptr().align_offset(mem::align_of::<T>()) == 0
     {
-        let ptr = cursor.take_bytes(len)?.as_ptr();
-        let slice = unsafe { slice::from_raw_parts(ptr.cast(), len / mem::size_of::<T>()) };
+        // SAFETY: `T: Pod`, so any bit pattern is a valid `T`, the target is
+        // little-endian, the pointer is aligned for `T`, and `data` spans
+        // exactly `length` values of `T` which live as long as the input buffer.
+        let slice = unsafe { slice::from_raw_parts(data.as_ptr().cast(), length) };
         Ok(Cow::Borrowed(slice))
+    } else {
+        cursor.allocate(len)?;
+        decode_slice(data).map(Cow::Owned)
     }
-    #[cfg(target_endian = "big")]
-    {
-        let data = cursor.take_bytes(len)?;
-        let data = data
-            .iter()
-            .chunks(mem::size_of::<T>())
-            .map(|data| T::_parse(data).unwrap())
-            .collect();
-        Ok(Cow::Owned(data))
-    }
 }
 
+fn decode_slice<T: FromData>(data: &[u8]) -> HxaResult<Vec<T>> {
+    data.chunks_exact(T::SIZE).map(T::_parse).collect()
+}
+
+/// Types for which every bit pattern is a valid value.
 trait Pod {}
 impl Pod for u8 {}
 impl Pod for i8 {}
@@ -449,3 +629,548 @@ impl Pod for u128 {}
 impl Pod for i128 {}
 impl Pod for f32 {}
 impl Pod for f64 {}
+
+/// Run these under `cargo miri test` as well, which catches any misaligned
+/// slice that slips through.
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+    use alloc::vec;
+
+    use super::*;
+
+    fn layer(name: &'static str, component_count: u8, data: LayerData<'static>) -> Layer<'static> {
+        Layer {
+            name: Cow::Borrowed(name),
+            component_count,
+            type_: data.type_(),
+            data,
+        }
+    }
+
+    fn meta(name: &'static str, value: MetaValue<'static>) -> Meta<'static> {
+        Meta {
+            name: Cow::Borrowed(name),
+            type_: value.type_(),
+            value,
+        }
+    }
+
+    fn stack(layers: Vec<Layer<'static>>) -> LayerStack<'static> {
+        LayerStack { layers }
+    }
+
+    /// A quad with every layer data type and every kind of metadata array, at
+    /// offsets that don't all line up with their alignment, followed by a
+    /// meta node referencing it.
+    fn fixture_hxa() -> Hxa<'static> {
+        let positions = vec![
+            0.0f64, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.5, 0.0, 1.0, 0.0,
+        ];
+        let uvs = vec![0.0f32, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let colors = vec![255u8, 0, 0, 0, 255, 0, 0, 0, 255, 9, 9, 9];
+        let quad = Node {
+            type_: NodeType::Geometry,
+            metadata: vec![
+                meta("name", MetaValue::Text("quad".into())),
+                meta("ids", MetaValue::Int64(vec![1, -2, i64::MAX].into())),
+                meta("weights", MetaValue::Double(vec![0.5, -0.25, 1e300].into())),
+                meta(
+                    "nested",
+                    MetaValue::Meta(vec![meta("blob", MetaValue::Bin(vec![1, 2, 3].into()))]),
+                ),
+            ],
+            content: Some(NodeContent::Geometry(NodeGeometry {
+                vertex_stack: stack(vec![
+                    layer("vertex", 3, LayerData::Double(positions.into())),
+                    layer("uv", 2, LayerData::Float(uvs.into())),
+                ]),
+                corner_stack: stack(vec![
+                    layer("reference", 1, LayerData::Int32(vec![0, 1, 2, -4].into())),
+                    layer("color", 3, LayerData::Uint8(colors.into())),
+                ]),
+                edge_stack: stack(Vec::new()),
+                face_stack: stack(vec![layer("material", 1, LayerData::Int32(vec![7].into()))]),
+            })),
+        };
+        let root = Node {
+            type_: NodeType::Meta,
+            metadata: vec![
+                meta("children", MetaValue::Node(vec![0].into())),
+                meta("scale", MetaValue::Double(vec![2.0].into())),
+            ],
+            content: None,
+        };
+        Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![quad, root],
+        }
+    }
+
+    fn fixture() -> Vec<u8> {
+        fixture_hxa().to_bytes().unwrap()
+    }
+
+    /// The position of the first occurrence of `needle` in `haystack`.
+    fn find(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap()
+    }
+
+    /// The start of a file that claims to hold `node_count` nodes.
+    fn header(version: u32, node_count: u32) -> Vec<u8> {
+        let mut bytes = b"HxA\0".to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&node_count.to_le_bytes());
+        bytes
+    }
+
+    fn push_u32s(bytes: &mut Vec<u8>, values: &[u32]) {
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// A file holding a single image node without metadata, whose only
+    /// layer is named "color" and holds no data.
+    fn image(type_: ImageType, resolution: &[u32], components: u8, layer_type: u8) -> Vec<u8> {
+        let mut bytes = header(3, 1);
+        bytes.push(NodeType::Image as u8);
+        push_u32s(&mut bytes, &[0]);
+        bytes.push(type_ as u8);
+        push_u32s(&mut bytes, resolution);
+        push_u32s(&mut bytes, &[1]);
+        bytes.push(5);
+        bytes.extend_from_slice(b"color");
+        bytes.extend_from_slice(&[components, layer_type]);
+        bytes
+    }
+
+    fn parse_error(data: &[u8], options: &ParseOptions) -> HxaError {
+        match Hxa::new_with_options(data, options) {
+            Ok(_) => panic!("parsed a malformed file"),
+            Err(HxaError::Parse(error)) => error.error,
+            Err(error) => panic!("expected a parse error, got {:?}", error),
+        }
+    }
+
+    fn limits(change: impl FnOnce(&mut ParseOptions)) -> ParseOptions {
+        let mut options = ParseOptions::default();
+        change(&mut options);
+        options
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = fixture();
+        for len in 0..bytes.len() {
+            let error = Hxa::new(&bytes[..len]).unwrap_err();
+            assert!(
+                matches!(error.root(), HxaError::UnexpectedEndOfData),
+                "{} bytes: {:?}",
+                len,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_counts() {
+        let options = ParseOptions::default();
+        // The counts are checked against the data before anything that large
+        // is allocated.
+        let bytes = header(3, u32::MAX);
+        assert_eq!(bytes.len(), 12);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::UnexpectedEndOfData
+        ));
+        let mut bytes = header(3, 1);
+        bytes.push(NodeType::Geometry as u8);
+        push_u32s(&mut bytes, &[0, u32::MAX, 1]);
+        bytes.push(6);
+        bytes.extend_from_slice(b"vertex");
+        bytes.extend_from_slice(&[255, LayerDataType::Double as u8]);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::UnexpectedEndOfData
+        ));
+
+        // A layer of a huge image holds more bytes than there are addresses.
+        #[cfg(target_pointer_width = "64")]
+        for (components, type_) in [(255, LayerDataType::Uint8), (1, LayerDataType::Float)] {
+            let bytes = image(
+                ImageType::Image2D,
+                &[u32::MAX, u32::MAX],
+                components,
+                type_ as u8,
+            );
+            assert!(matches!(
+                parse_error(&bytes, &options),
+                HxaError::CountOverflow
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_image_sizes() {
+        let options = ParseOptions::default();
+        let bytes = image(ImageType::Image3D, &[u32::MAX; 3], 1, 0);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::ImageSizeOverflow([u32::MAX, u32::MAX, u32::MAX])
+        ));
+        let bytes = image(ImageType::ImageCube, &[u32::MAX, u32::MAX], 1, 0);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::ImageSizeOverflow([u32::MAX, u32::MAX, 1])
+        ));
+    }
+
+    #[test]
+    fn reports_trailing_data() {
+        let mut bytes = fixture();
+        let len = bytes.len();
+        let expected = format!("{:?}", Hxa::new(&bytes).unwrap());
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        match Hxa::new(&bytes) {
+            Err(HxaError::TrailingData { offset, len: 3 }) => assert_eq!(offset, len),
+            other => panic!("expected trailing data, got {:?}", other),
+        }
+        let lenient = ParseOptions {
+            trailing_data: TrailingData::Allow,
+            ..ParseOptions::default()
+        };
+        let (hxa, trailing) = Hxa::new_with_options(&bytes, &lenient).unwrap();
+        assert_eq!(trailing, 3);
+        assert_eq!(format!("{:?}", hxa), expected);
+        let (_, trailing) = Hxa::new_with_options(&bytes[..len], &lenient).unwrap();
+        assert_eq!(trailing, 0);
+    }
+
+    #[test]
+    fn locates_errors_in_nested_elements() {
+        let bytes = fixture();
+
+        // Replace the type of the "color" corner layer.
+        let mut corrupt = bytes.clone();
+        let type_offset = find(&bytes, b"\x05color") + 7;
+        corrupt[type_offset] = 7;
+        match Hxa::new(&corrupt) {
+            Err(HxaError::Parse(error)) => {
+                assert_eq!(error.offset, type_offset);
+                assert_eq!(
+                    error.path,
+                    [
+                        PathSegment::Node(0),
+                        PathSegment::Stack(StackKind::Corner),
+                        PathSegment::Layer {
+                            index: 1,
+                            name: Some(String::from("color")),
+                        },
+                    ]
+                );
+                assert!(matches!(error.error, HxaError::UnexpectedLayerDataType(7)));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        // Replace the type of the "blob" entry inside "nested".
+        let mut corrupt = bytes.clone();
+        let type_offset = find(&bytes, b"\x04blob") + 5;
+        corrupt[type_offset] = 9;
+        match Hxa::new(&corrupt) {
+            Err(HxaError::Parse(error)) => {
+                assert_eq!(error.offset, type_offset);
+                assert_eq!(
+                    error.path,
+                    [
+                        PathSegment::Node(0),
+                        PathSegment::Metadata {
+                            index: 3,
+                            name: Some(String::from("nested")),
+                        },
+                        PathSegment::Metadata {
+                            index: 0,
+                            name: Some(String::from("blob")),
+                        },
+                    ]
+                );
+                assert!(matches!(error.error, HxaError::UnexpectedMetadataType(9)));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn exposes_the_source_of_invalid_text() {
+        use std::error::Error;
+
+        let mut bytes = fixture();
+        let text = find(&bytes, b"quad");
+        bytes[text] = 0xff;
+        let error = Hxa::new(&bytes).unwrap_err();
+        let inner = error.source().unwrap();
+        assert!(matches!(
+            inner.downcast_ref::<HxaError>(),
+            Some(HxaError::InvalidUtf8(_))
+        ));
+        assert!(inner.source().unwrap().is::<str::Utf8Error>());
+    }
+
+    #[test]
+    fn limits_the_node_count() {
+        let bytes = header(3, u32::MAX);
+        let options = limits(|options| options.max_nodes = 1000);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::LimitExceeded(Limit::Nodes)
+        ));
+        let options = limits(|options| options.max_nodes = 1);
+        assert!(Hxa::new_with_options(&fixture(), &options).is_err());
+        let options = limits(|options| options.max_nodes = 2);
+        assert!(Hxa::new_with_options(&fixture(), &options).is_ok());
+    }
+
+    #[test]
+    fn limits_layers_per_stack() {
+        let mut bytes = header(3, 1);
+        bytes.push(NodeType::Geometry as u8);
+        push_u32s(&mut bytes, &[0, 0, u32::MAX]);
+        let options = limits(|options| options.max_layers_per_stack = 16);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::LimitExceeded(Limit::LayersPerStack)
+        ));
+        let options = limits(|options| options.max_layers_per_stack = 1);
+        assert!(Hxa::new_with_options(&fixture(), &options).is_err());
+        let options = limits(|options| options.max_layers_per_stack = 2);
+        assert!(Hxa::new_with_options(&fixture(), &options).is_ok());
+    }
+
+    #[test]
+    fn limits_metadata_depth() {
+        // A meta node with `levels` nested `Meta` entries, the innermost of
+        // which is empty.
+        let nested = |levels: usize| {
+            let mut bytes = header(3, 1);
+            bytes.push(NodeType::Meta as u8);
+            push_u32s(&mut bytes, &[1]);
+            for level in 0..levels {
+                bytes.extend_from_slice(&[1, b'm', MetadataType::Meta as u8]);
+                push_u32s(&mut bytes, &[(level + 1 < levels) as u32]);
+            }
+            bytes
+        };
+        let options = limits(|options| options.max_metadata_depth = 4);
+        assert!(Hxa::new_with_options(&nested(3), &options).is_ok());
+        assert!(matches!(
+            parse_error(&nested(4), &options),
+            HxaError::LimitExceeded(Limit::MetadataDepth)
+        ));
+        // The default is low enough to keep deeply nested files from
+        // overflowing the stack.
+        assert!(matches!(
+            parse_error(&nested(100_000), &ParseOptions::default()),
+            HxaError::LimitExceeded(Limit::MetadataDepth)
+        ));
+    }
+
+    #[test]
+    fn limits_allocation() {
+        let options = limits(|options| options.max_allocation = 1 << 20);
+        // Twelve bytes asking for gigabytes of nodes.
+        assert!(matches!(
+            parse_error(&header(3, u32::MAX), &options),
+            HxaError::LimitExceeded(Limit::Allocation)
+        ));
+        let mut bytes = header(3, 1);
+        bytes.push(NodeType::Meta as u8);
+        push_u32s(&mut bytes, &[u32::MAX]);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::LimitExceeded(Limit::Allocation)
+        ));
+
+        // Arrays copied out of the input count as well.
+        let bytes = fixture();
+        let decode = |max_allocation| ParseOptions {
+            zero_copy: false,
+            max_allocation,
+            ..ParseOptions::default()
+        };
+        assert!(Hxa::new_with_options(&bytes, &decode(1 << 20)).is_ok());
+        assert!(matches!(
+            parse_error(&bytes, &decode(bytes.len())),
+            HxaError::LimitExceeded(Limit::Allocation)
+        ));
+    }
+
+    #[test]
+    fn limits_image_resolution() {
+        let options = limits(|options| options.max_image_resolution = 4096);
+        let bytes = image(ImageType::Image2D, &[4096, u32::MAX], 4, 0);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::LimitExceeded(Limit::ImageResolution)
+        ));
+        let bytes = image(ImageType::Image3D, &[1, 1, 4097], 4, 0);
+        assert!(matches!(
+            parse_error(&bytes, &options),
+            HxaError::LimitExceeded(Limit::ImageResolution)
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        let mut bytes = fixture();
+        for version in [0, 4, u32::MAX] {
+            bytes[4..8].copy_from_slice(&version.to_le_bytes());
+            match Hxa::new(&bytes) {
+                Err(HxaError::Parse(error)) => {
+                    assert_eq!(error.offset, 4);
+                    assert!(error.path.is_empty());
+                    assert!(matches!(error.error, HxaError::UnsupportedVersion(v) if v == version));
+                }
+                other => panic!("expected an unsupported version, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn reads_older_versions_without_edge_stacks() {
+        let mut hxa = fixture_hxa();
+        let latest = hxa.to_bytes().unwrap();
+        for version in [HxaVersion::V1, HxaVersion::V2] {
+            hxa.version = version;
+            let bytes = hxa.to_bytes().unwrap();
+            // The edge stack's layer count is missing from the one geometry
+            // node.
+            assert_eq!(bytes.len(), latest.len() - 4);
+            assert_eq!(bytes[4..8], (version as u32).to_le_bytes());
+
+            let parsed = Hxa::new(&bytes).unwrap();
+            assert_eq!(parsed.version, version);
+            assert_eq!(format!("{:?}", parsed.nodes), format!("{:?}", hxa.nodes));
+
+            // Read with the wrong layout, the face stack is misplaced.
+            let mut relabelled = bytes.clone();
+            relabelled[4..8].copy_from_slice(&3u32.to_le_bytes());
+            assert!(Hxa::new(&relabelled).is_err());
+        }
+    }
+
+    /// Whether an array was borrowed from the input, whether it is aligned,
+    /// and the alignment of its element type.
+    #[derive(Clone, Copy, Debug)]
+    struct Array {
+        borrowed: bool,
+        aligned: bool,
+        align: usize,
+    }
+
+    impl<T: Clone> From<&Cow<'_, [T]>> for Array {
+        fn from(cow: &Cow<'_, [T]>) -> Self {
+            Self {
+                borrowed: matches!(cow, Cow::Borrowed(_)),
+                aligned: cow.as_ptr().align_offset(mem::align_of::<T>()) == 0,
+                align: mem::align_of::<T>(),
+            }
+        }
+    }
+
+    /// The arrays of `hxa` that the parser may borrow, in file order.
+    fn arrays(hxa: &Hxa<'_>) -> Vec<Array> {
+        fn meta_arrays(metadata: &[Meta<'_>], arrays: &mut Vec<Array>) {
+            for meta in metadata {
+                match &meta.value {
+                    MetaValue::Int64(values) => arrays.push(Array::from(values)),
+                    MetaValue::Double(values) => arrays.push(Array::from(values)),
+                    MetaValue::Node(values) => arrays.push(Array::from(values)),
+                    MetaValue::Meta(metadata) => meta_arrays(metadata, arrays),
+                    MetaValue::Text(_) | MetaValue::Bin(_) => {}
+                }
+            }
+        }
+
+        let mut arrays = Vec::new();
+        for node in &hxa.nodes {
+            meta_arrays(&node.metadata, &mut arrays);
+            let stacks = match &node.content {
+                Some(NodeContent::Geometry(geometry)) => vec![
+                    &geometry.vertex_stack,
+                    &geometry.corner_stack,
+                    &geometry.edge_stack,
+                    &geometry.face_stack,
+                ],
+                Some(NodeContent::Image(image)) => vec![&image.image_stack],
+                None => Vec::new(),
+            };
+            for layer in stacks.into_iter().flat_map(|stack| &stack.layers) {
+                arrays.push(match &layer.data {
+                    LayerData::Uint8(data) => Array::from(data),
+                    LayerData::Int32(data) => Array::from(data),
+                    LayerData::Float(data) => Array::from(data),
+                    LayerData::Double(data) => Array::from(data),
+                });
+            }
+        }
+        arrays
+    }
+
+    #[test]
+    fn copies_misaligned_arrays() {
+        let bytes = fixture();
+        let expected = format!("{:?}", Hxa::new(&bytes).unwrap());
+        let mut copied = Vec::new();
+        for offset in 0..8 {
+            let mut buffer = vec![0; bytes.len() + 16];
+            let start = buffer.as_ptr().align_offset(8) + offset;
+            let data = &mut buffer[start..start + bytes.len()];
+            data.copy_from_slice(&bytes);
+
+            let hxa = Hxa::new(data).unwrap();
+            assert_eq!(format!("{:?}", hxa), expected);
+            let arrays = arrays(&hxa);
+            for array in &arrays {
+                assert!(!array.borrowed || array.aligned, "{:?}", array);
+            }
+            copied.push(arrays);
+        }
+
+        // Wherever an array lies in the file, some of the offsets misalign it.
+        for (index, array) in copied[0].iter().enumerate() {
+            let owned = copied
+                .iter()
+                .filter(|arrays| !arrays[index].borrowed)
+                .count();
+            if array.align > 1 {
+                assert_eq!(owned, 8 - 8 / array.align, "array {} {:?}", index, array);
+            } else {
+                assert_eq!(owned, 0, "array {} {:?}", index, array);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_copy_does_not_change_contents() {
+        let decode = ParseOptions {
+            zero_copy: false,
+            ..ParseOptions::default()
+        };
+        for bytes in [fixture(), include_bytes!("../examples/teapot.hxa").to_vec()] {
+            let borrowed = Hxa::new(&bytes).unwrap();
+            let (decoded, _) = Hxa::new_with_options(&bytes, &decode).unwrap();
+            assert_eq!(format!("{:?}", borrowed), format!("{:?}", decoded));
+            // Byte arrays need no decoding, so they are always borrowed.
+            let arrays = arrays(&decoded);
+            assert!(arrays
+                .iter()
+                .all(|array| array.align == 1 || !array.borrowed));
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use alloc::vec::Vec;
+
+use crate::{Hxa, HxaError, HxaResult, Instance, Transform};
+
+/// The hierarchy formed by nodes referencing each other from their metadata.
+///
+/// A node is a child of every node whose metadata contains a reference to it,
+/// at any depth. Nodes that no other node references are roots. The graph
+/// holds no references into the file it was built from.
+#[derive(Clone, Debug)]
+pub struct SceneGraph {
+    children: Vec<Vec<usize>>,
+    parents: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+    local: Vec<Transform>,
+}
+
+impl Hxa<'_> {
+    pub fn scene_graph(&self) -> HxaResult<SceneGraph> {
+        SceneGraph::new(self)
+    }
+}
+
+impl SceneGraph {
+    /// How many instances [`SceneGraph::instances`] places at most. Node
+    /// references can form a graph in which the number of paths grows
+    /// exponentially with the number of nodes, so some bound is needed.
+    pub const MAX_INSTANCES: usize = 1 << 20;
+
+    /// Resolves the node references in `hxa`.
+    ///
+    /// Fails if a reference points past the last node, or if the references
+    /// form a cycle, in which case the error names one of the nodes on it.
+    pub fn new(hxa: &Hxa<'_>) -> HxaResult<Self> {
+        let node_count = hxa.nodes.len();
+        let mut children = Vec::with_capacity(node_count);
+        // Marks the children of the node being resolved, so that a node
+        // referencing many others doesn't take quadratic time to dedupe.
+        let mut seen = alloc::vec![false; node_count];
+        for node in &hxa.nodes {
+            let mut referenced = Vec::new();
+            for (_, meta) in node.meta_walk() {
+                for &child in meta.value.as_node_refs().unwrap_or_default() {
+                    let child = child as usize;
+                    if child >= node_count {
+                        return Err(HxaError::NodeIndexOutOfRange(child as u32));
+                    }
+                    if !seen[child] {
+                        seen[child] = true;
+                        referenced.push(child);
+                    }
+                }
+            }
+            for &child in &referenced {
+                seen[child] = false;
+            }
+            children.push(referenced);
+        }
+
+        let mut parents = alloc::vec![Vec::new(); node_count];
+        for (parent, children) in children.iter().enumerate() {
+            for &child in children {
+                parents[child].push(parent);
+            }
+        }
+        let roots = (0..node_count)
+            .filter(|&node| parents[node].is_empty())
+            .collect();
+
+        let graph = Self {
+            children,
+            parents,
+            roots,
+            local: hxa
+                .nodes
+                .iter()
+                .map(|node| node.transform().unwrap_or_default())
+                .collect(),
+        };
+        match graph.find_cycle() {
+            Some(node) => Err(HxaError::NodeCycle(node)),
+            None => Ok(graph),
+        }
+    }
+
+    /// Removes nodes without parents until none are left, which only fails
+    /// to reach every node if there is a cycle.
+    fn find_cycle(&self) -> Option<usize> {
+        let mut remaining_parents: Vec<usize> = self.parents.iter().map(Vec::len).collect();
+        let mut queue = self.roots.clone();
+        while let Some(node) = queue.pop() {
+            for &child in &self.children[node] {
+                remaining_parents[child] -= 1;
+                if remaining_parents[child] == 0 {
+                    queue.push(child);
+                }
+            }
+        }
+
+        // Every node left over has a parent that is also left over. Walking
+        // back through those for as many steps as there are nodes is bound to
+        // end up on the cycle itself.
+        let mut node = remaining_parents.iter().position(|&count| count > 0)?;
+        for _ in 0..self.node_count() {
+            node = self.parents[node]
+                .iter()
+                .copied()
+                .find(|&parent| remaining_parents[parent] > 0)
+                .unwrap_or(node);
+        }
+        Some(node)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.children.len()
+    }
+
+    /// The nodes that no other node references, in file order.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// The nodes referenced by `node`, in the order they appear in its
+    /// metadata, each listed once.
+    ///
+    /// Panics if `node` is out of range, as do the other methods that take a
+    /// node.
+    pub fn children(&self, node: usize) -> &[usize] {
+        &self.children[node]
+    }
+
+    /// The nodes that reference `node`, in file order.
+    pub fn parents(&self, node: usize) -> &[usize] {
+        &self.parents[node]
+    }
+
+    /// The node's own `transform`, or the identity if it has none.
+    pub fn local_transform(&self, node: usize) -> Transform {
+        self.local[node]
+    }
+
+    /// The node's transform combined with those of its ancestors, following
+    /// the first parent of each node up to a root.
+    ///
+    /// Nodes with several parents appear in the scene more than once; use
+    /// [`SceneGraph::instances`] to get all of their placements.
+    pub fn world_transform(&self, mut node: usize) -> Transform {
+        let mut world = self.local[node];
+        while let Some(&parent) = self.parents[node].first() {
+            world = self.local[parent] * world;
+            node = parent;
+        }
+        world
+    }
+
+    /// Visits the nodes below `node` depth-first, parents before their
+    /// children, starting with `node` itself.
+    ///
+    /// Nodes reachable along several paths are visited once per path, which
+    /// can be exponentially many times; check [`SceneGraph::instance_count`]
+    /// before walking a graph from an untrusted file.
+    pub fn descendants(&self, node: usize) -> Descendants<'_> {
+        Descendants {
+            graph: self,
+            stack: alloc::vec![(node, 0, self.local[node])],
+            started: false,
+        }
+    }
+
+    /// How many instances [`SceneGraph::instances`] would place, saturating
+    /// at `usize::MAX`.
+    ///
+    /// This counts the paths down from the roots to every node without
+    /// walking them, so it takes time linear in the number of references.
+    pub fn instance_count(&self) -> usize {
+        let mut paths = alloc::vec![0usize; self.node_count()];
+        let mut remaining_parents: Vec<usize> = self.parents.iter().map(Vec::len).collect();
+        let mut queue = self.roots.clone();
+        for &root in &self.roots {
+            paths[root] = 1;
+        }
+        let mut total = 0usize;
+        while let Some(node) = queue.pop() {
+            total = total.saturating_add(paths[node]);
+            for &child in &self.children[node] {
+                paths[child] = paths[child].saturating_add(paths[node]);
+                remaining_parents[child] -= 1;
+                if remaining_parents[child] == 0 {
+                    queue.push(child);
+                }
+            }
+        }
+        total
+    }
+
+    /// Places every node in the scene by walking down from each root, so
+    /// that a node reached along several paths is placed once per path.
+    ///
+    /// Fails with [`HxaError::TooManyInstances`] if that would place more
+    /// than [`SceneGraph::MAX_INSTANCES`] nodes.
+    pub fn instances(&self) -> HxaResult<Vec<Instance>> {
+        self.instances_with_limit(Self::MAX_INSTANCES)
+    }
+
+    /// Like [`SceneGraph::instances`], but with a custom limit.
+    pub fn instances_with_limit(&self, limit: usize) -> HxaResult<Vec<Instance>> {
+        let count = self.instance_count();
+        if count > limit {
+            return Err(HxaError::TooManyInstances { count, limit });
+        }
+        Ok(self
+            .roots
+            .iter()
+            .flat_map(|&root| self.descendants(root))
+            .map(|visit| Instance {
+                node: visit.node,
+                world: visit.world,
+            })
+            .collect())
+    }
+}
+
+/// A node reached by [`SceneGraph::descendants`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Visit {
+    pub node: usize,
+    /// How many references were followed to get here.
+    pub depth: usize,
+    /// The node's transform combined with those of the nodes on the way here,
+    /// including the starting node.
+    pub world: Transform,
+}
+
+#[derive(Clone, Debug)]
+pub struct Descendants<'g> {
+    graph: &'g SceneGraph,
+    /// The node, the next of its children to visit, and its world transform
+    /// for each node on the path from the start.
+    stack: Vec<(usize, usize, Transform)>,
+    started: bool,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = Visit;
+
+    fn next(&mut self) -> Option<Visit> {
+        if !self.started {
+            self.started = true;
+            let &(node, _, world) = self.stack.first()?;
+            return Some(Visit {
+                node,
+                depth: 0,
+                world,
+            });
+        }
+
+        loop {
+            let (node, next, world) = self.stack.last_mut()?;
+            match self.graph.children[*node].get(*next) {
+                Some(&child) => {
+                    *next += 1;
+                    let world = *world * self.graph.local[child];
+                    self.stack.push((child, 0, world));
+                    return Some(Visit {
+                        node: child,
+                        depth: self.stack.len() - 1,
+                        world,
+                    });
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{HxaVersion, MetaBuilder, Node, NodeType};
+
+    fn meta_node(children: &[u32]) -> Node<'static> {
+        Node {
+            type_: NodeType::Meta,
+            metadata: MetaBuilder::new().node("children", children).build(),
+            content: None,
+        }
+    }
+
+    /// Node `i` references nodes `i + 1` and `i + 2`, so the number of paths
+    /// to the last node grows like the Fibonacci numbers.
+    fn diamonds(count: u32) -> Hxa<'static> {
+        Hxa {
+            version: HxaVersion::LATEST,
+            nodes: (0..count)
+                .map(|i| {
+                    let children: Vec<u32> = (i + 1..count).take(2).collect();
+                    meta_node(&children)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn counts_instances_without_walking_paths() {
+        let graph = diamonds(5).scene_graph().unwrap();
+        // Paths to each node: 1, 1, 2, 3, 5.
+        assert_eq!(graph.instance_count(), 12);
+        let instances = graph.instances().unwrap();
+        assert_eq!(instances.len(), 12);
+        assert_eq!(instances.iter().filter(|i| i.node == 4).count(), 5);
+
+        let graph = diamonds(200).scene_graph().unwrap();
+        assert_eq!(graph.instance_count(), usize::MAX);
+    }
+
+    #[test]
+    fn rejects_exponentially_many_instances() {
+        let hxa = diamonds(60);
+        match hxa.instances() {
+            Err(HxaError::TooManyInstances { count, limit }) => {
+                assert!(count > limit);
+                assert_eq!(limit, SceneGraph::MAX_INSTANCES);
+            }
+            other => panic!("expected TooManyInstances, got {:?}", other),
+        }
+        let graph = hxa.scene_graph().unwrap();
+        assert!(matches!(
+            graph.instances_with_limit(3),
+            Err(HxaError::TooManyInstances { limit: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn places_shared_children_once_per_parent() {
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![meta_node(&[1, 2]), meta_node(&[2]), meta_node(&[])],
+        };
+        let graph = hxa.scene_graph().unwrap();
+        assert_eq!(graph.roots(), &[0]);
+        assert_eq!(graph.parents(2), &[0, 1]);
+        let nodes: Vec<usize> = graph.descendants(0).map(|visit| visit.node).collect();
+        assert_eq!(nodes, vec![0, 1, 2, 2]);
+    }
+
+    #[test]
+    fn resolves_large_fan_outs() {
+        // Every leaf is referenced twice, which used to take quadratic time to
+        // dedupe.
+        let leaves = 200_000;
+        let references: Vec<u32> = (1..=leaves).chain((1..=leaves).rev()).collect();
+        let mut nodes = vec![meta_node(&references)];
+        nodes.extend((0..leaves).map(|_| meta_node(&[])));
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes,
+        };
+        let graph = hxa.scene_graph().unwrap();
+        assert_eq!(graph.roots(), &[0]);
+        assert_eq!(graph.children(0).len(), leaves as usize);
+        assert!(graph.children(0).iter().copied().eq(1..=leaves as usize));
+        assert_eq!(graph.parents(leaves as usize), &[0]);
+        assert_eq!(graph.instances().unwrap().len(), leaves as usize + 1);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    Layer, LayerStack, HC_BASE_CORNER_LAYER_NAME, HC_BASE_VERTEX_LAYER_NAME,
+    HC_EDGE_NEIGHBOUR_LAYER_NAME,
+};
+
+impl<'a> LayerStack<'a> {
+    pub fn get(&self, name: &str) -> Option<&Layer<'a>> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Layer<'a>> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Adds `layer` to the end of the stack, or replaces the layer with the
+    /// same name in place, returning the replaced layer.
+    pub fn insert(&mut self, layer: Layer<'a>) -> Option<Layer<'a>> {
+        match self.get_mut(&layer.name) {
+            Some(existing) => Some(core::mem::replace(existing, layer)),
+            None => {
+                self.layers.push(layer);
+                None
+            }
+        }
+    }
+
+    /// Removes the layer called `name`, keeping the order of the remaining
+    /// layers.
+    ///
+    /// Nothing stops this from removing the base layer of a vertex or corner
+    /// stack, which leaves the node invalid.
+    pub fn remove(&mut self, name: &str) -> Option<Layer<'a>> {
+        let index = self.layers.iter().position(|layer| layer.name == name)?;
+        Some(self.layers.remove(index))
+    }
+
+    /// Iterates over the layers that aren't required by the hard conventions,
+    /// i.e. everything but a leading `vertex` or `reference` base layer and an
+    /// edge stack's `neighbour` layer, wherever it is.
+    pub fn attributes(&self) -> impl Iterator<Item = &Layer<'a>> {
+        let skip = match self.layers.first() {
+            Some(layer)
+                if layer.name == HC_BASE_VERTEX_LAYER_NAME
+                    || layer.name == HC_BASE_CORNER_LAYER_NAME =>
+            {
+                1
+            }
+            _ => 0,
+        };
+        self.layers
+            .iter()
+            .skip(skip)
+            .filter(|layer| layer.name != HC_EDGE_NEIGHBOUR_LAYER_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{GeometryBuilder, ImageBuilder, NodeContent, NodeGeometry};
+
+    fn names<'l>(layers: impl Iterator<Item = &'l Layer<'l>>) -> Vec<String> {
+        layers.map(|layer| layer.name.to_string()).collect()
+    }
+
+    fn quad() -> NodeGeometry<'static> {
+        let node = GeometryBuilder::new(&[[0.0f32; 3]; 4])
+            .polygon(&[0, 1, 2, 3])
+            .vertex_layer("normal", &[[0.0f32; 3]; 4])
+            .corner_layer("uv", &[[0.0f32; 2]; 4])
+            .edge_layer("crease", &[0u8; 4])
+            .face_layer("material", &[1])
+            .build()
+            .unwrap();
+        match node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn finds_layers_by_name() {
+        let mut geometry = quad();
+        let stack = &mut geometry.corner_stack;
+        assert_eq!(stack.get("uv").unwrap().component_count, 2);
+        assert!(stack.get("normal").is_none());
+        assert!(stack.contains("reference"));
+        assert!(!stack.contains("UV"));
+
+        stack.get_mut("uv").unwrap().name = "uv0".into();
+        assert!(!stack.contains("uv"));
+        assert!(stack.contains("uv0"));
+    }
+
+    #[test]
+    fn inserts_and_removes_layers() {
+        let mut geometry = quad();
+        let stack = &mut geometry.vertex_stack;
+        assert!(stack
+            .insert(Layer::from_slice("weight", &[1.0f32; 4]))
+            .is_none());
+        assert_eq!(names(stack.layers.iter()), ["vertex", "normal", "weight"]);
+
+        // Replacing a layer keeps its place in the stack.
+        let replaced = stack.insert(Layer::from_slice("normal", &[[1.0f64; 3]; 4]));
+        assert_eq!(replaced.unwrap().component_count, 3);
+        assert_eq!(names(stack.layers.iter()), ["vertex", "normal", "weight"]);
+        assert!(stack.get("normal").unwrap().as_slice::<f64>().is_some());
+
+        assert_eq!(stack.remove("normal").unwrap().name, "normal");
+        assert!(stack.remove("normal").is_none());
+        assert_eq!(names(stack.layers.iter()), ["vertex", "weight"]);
+    }
+
+    #[test]
+    fn skips_hard_convention_layers_in_every_stack() {
+        let mut geometry = quad();
+        assert_eq!(names(geometry.vertex_stack.attributes()), ["normal"]);
+        assert_eq!(names(geometry.corner_stack.attributes()), ["uv"]);
+        assert_eq!(names(geometry.face_stack.attributes()), ["material"]);
+        assert_eq!(names(geometry.edge_stack.attributes()), ["crease"]);
+
+        // The neighbour layer is skipped wherever it is.
+        geometry.update_neighbours().unwrap();
+        assert_eq!(
+            names(geometry.edge_stack.layers.iter()),
+            ["crease", "neighbour"]
+        );
+        assert_eq!(names(geometry.edge_stack.attributes()), ["crease"]);
+        geometry.edge_stack.layers.reverse();
+        assert_eq!(names(geometry.edge_stack.attributes()), ["crease"]);
+
+        // Only leading base layers are skipped.
+        geometry.vertex_stack.layers.reverse();
+        assert_eq!(
+            names(geometry.vertex_stack.attributes()),
+            ["normal", "vertex"]
+        );
+
+        let image = ImageBuilder::image_1d(2)
+            .layer("color", &[[0u8; 3]; 2])
+            .layer("light", &[0.0f32; 2])
+            .build()
+            .unwrap();
+        match &image.content {
+            Some(NodeContent::Image(image)) => {
+                assert_eq!(names(image.image_stack.attributes()), ["color", "light"]);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
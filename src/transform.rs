@@ -0,0 +1,298 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::ops::Mul;
+
+use crate::{Hxa, HxaResult, Meta, MetaBuilder, MetaValue, MetadataType, Node, SC_TRANSFORM};
+
+/// A 4x4 matrix, as stored in the `transform` metadata of a node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    /// The matrix's columns, so `columns[3]` holds the translation.
+    pub columns: [[f64; 4]; 4],
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        columns: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn from_translation([x, y, z]: [f64; 3]) -> Self {
+        let mut transform = Self::IDENTITY;
+        transform.columns[3] = [x, y, z, 1.0];
+        transform
+    }
+
+    /// Creates a matrix from 16 values in column-major order.
+    pub fn from_cols_array(values: &[f64; 16]) -> Self {
+        let mut columns = [[0.0; 4]; 4];
+        for (column, values) in columns.iter_mut().zip(values.chunks_exact(4)) {
+            column.copy_from_slice(values);
+        }
+        Self { columns }
+    }
+
+    /// The matrix's 16 values in column-major order.
+    pub fn to_cols_array(&self) -> [f64; 16] {
+        let mut values = [0.0; 16];
+        for (values, column) in values.chunks_exact_mut(4).zip(&self.columns) {
+            values.copy_from_slice(column);
+        }
+        values
+    }
+
+    pub fn transform_point(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let c = &self.columns;
+        let mut point = [0.0; 3];
+        for (row, value) in point.iter_mut().enumerate() {
+            *value = c[0][row] * x + c[1][row] * y + c[2][row] * z + c[3][row];
+        }
+        point
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    /// Combines two transforms so that `rhs` is applied first.
+    fn mul(self, rhs: Self) -> Self {
+        let mut columns = [[0.0; 4]; 4];
+        for (column, rhs) in columns.iter_mut().zip(&rhs.columns) {
+            for (row, value) in column.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.columns[k][row] * rhs[k]).sum();
+            }
+        }
+        Self { columns }
+    }
+}
+
+impl Node<'_> {
+    /// The node's `transform` metadata, if it holds the 16 `Double`s the soft
+    /// conventions call for.
+    pub fn transform(&self) -> Option<Transform> {
+        let values = self.meta_f64s(SC_TRANSFORM)?;
+        values.try_into().ok().map(Transform::from_cols_array)
+    }
+
+    /// Stores `transform` as the node's `transform` metadata, replacing any
+    /// previous value.
+    pub fn set_transform(&mut self, transform: Transform) {
+        let value = MetaValue::Double(Cow::Owned(transform.to_cols_array().to_vec()));
+        match self
+            .metadata
+            .iter_mut()
+            .find(|meta| meta.name == SC_TRANSFORM)
+        {
+            Some(meta) => {
+                meta.type_ = MetadataType::Double;
+                meta.value = value;
+            }
+            None => self.metadata.push(Meta {
+                name: Cow::Borrowed(SC_TRANSFORM),
+                type_: MetadataType::Double,
+                value,
+            }),
+        }
+    }
+}
+
+impl MetaBuilder {
+    pub fn transform(self, transform: Transform) -> Self {
+        self.double(SC_TRANSFORM, &transform.to_cols_array())
+    }
+}
+
+/// A node placed in the scene, as returned by [`SceneGraph::instances`](crate::SceneGraph::instances).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instance {
+    pub node: usize,
+    /// The node's transform combined with those of all the nodes referencing
+    /// it on the way from the root.
+    pub world: Transform,
+}
+
+impl Hxa<'_> {
+    /// Places every node in the scene.
+    ///
+    /// See [`SceneGraph::instances`](crate::SceneGraph::instances) for how nodes are placed.
+    pub fn instances(&self) -> HxaResult<Vec<Instance>> {
+        self.scene_graph()?.instances()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{HxaVersion, NodeType};
+
+    fn scale(factor: f64) -> Transform {
+        let mut transform = Transform::IDENTITY;
+        for (i, column) in transform.columns.iter_mut().take(3).enumerate() {
+            column[i] = factor;
+        }
+        transform
+    }
+
+    fn node(metadata: MetaBuilder) -> Node<'static> {
+        Node {
+            type_: NodeType::Meta,
+            metadata: metadata.build(),
+            content: None,
+        }
+    }
+
+    #[test]
+    fn stores_columns_in_order() {
+        let values: Vec<f64> = (0..16).map(f64::from).collect();
+        let transform = Transform::from_cols_array(values[..].try_into().unwrap());
+        assert_eq!(transform.columns[0], [0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(transform.columns[3], [12.0, 13.0, 14.0, 15.0]);
+        assert_eq!(transform.to_cols_array().to_vec(), values);
+
+        let translation = Transform::from_translation([1.0, 2.0, 3.0]);
+        assert_eq!(translation.to_cols_array()[12..], [1.0, 2.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn transforms_points() {
+        assert_eq!(
+            Transform::IDENTITY.transform_point([1.0, 2.0, 3.0]),
+            [1.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            Transform::from_translation([1.0, 2.0, 3.0]).transform_point([1.0, 1.0, 1.0]),
+            [2.0, 3.0, 4.0]
+        );
+
+        // Each column holds the image of one axis.
+        let values: Vec<f64> = (0..16).map(f64::from).collect();
+        let transform = Transform::from_cols_array(values[..].try_into().unwrap());
+        assert_eq!(
+            transform.transform_point([0.0, 0.0, 0.0]),
+            [12.0, 13.0, 14.0]
+        );
+        assert_eq!(
+            transform.transform_point([1.0, 0.0, 0.0]),
+            [12.0, 14.0, 16.0]
+        );
+        assert_eq!(
+            transform.transform_point([0.0, 1.0, 0.0]),
+            [16.0, 18.0, 20.0]
+        );
+    }
+
+    #[test]
+    fn applies_the_right_hand_side_first() {
+        let translate = Transform::from_translation([1.0, 0.0, 0.0]);
+        let point = [1.0, 1.0, 1.0];
+        assert_eq!(
+            (translate * scale(2.0)).transform_point(point),
+            [3.0, 2.0, 2.0]
+        );
+        assert_eq!(
+            (scale(2.0) * translate).transform_point(point),
+            [4.0, 2.0, 2.0]
+        );
+        assert_eq!(translate * Transform::IDENTITY, translate);
+        assert_eq!(Transform::IDENTITY * translate, translate);
+    }
+
+    #[test]
+    fn round_trips_through_metadata() {
+        let mut named = node(MetaBuilder::new().text("name", "a"));
+        assert_eq!(named.transform(), None);
+
+        let transform = Transform::from_translation([1.0, 2.0, 3.0]) * scale(2.0);
+        named.set_transform(transform);
+        assert_eq!(named.transform(), Some(transform));
+        named.set_transform(scale(3.0));
+        assert_eq!(named.transform(), Some(scale(3.0)));
+        assert_eq!(named.metadata.len(), 2);
+
+        // A value of the wrong type is replaced too.
+        let mut text = node(MetaBuilder::new().text(SC_TRANSFORM, "none"));
+        text.set_transform(scale(2.0));
+        assert_eq!(text.transform(), Some(scale(2.0)));
+        assert_eq!(text.metadata.len(), 1);
+
+        let built = node(MetaBuilder::new().transform(scale(4.0)));
+        assert_eq!(built.transform(), Some(scale(4.0)));
+    }
+
+    #[test]
+    fn ignores_malformed_transforms() {
+        let short = node(MetaBuilder::new().double(SC_TRANSFORM, &[1.0; 15]));
+        let long = node(MetaBuilder::new().double(SC_TRANSFORM, &[1.0; 17]));
+        let ints = node(MetaBuilder::new().int64(SC_TRANSFORM, &[1; 16]));
+        for node in &[short, long, ints] {
+            assert_eq!(node.transform(), None);
+        }
+
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![
+                node(
+                    MetaBuilder::new()
+                        .double(SC_TRANSFORM, &[2.0; 15])
+                        .node("children", &[1]),
+                ),
+                node(MetaBuilder::new()),
+            ],
+        };
+        let instances = hxa.instances().unwrap();
+        assert!(instances.iter().all(|i| i.world == Transform::IDENTITY));
+    }
+
+    #[test]
+    fn places_children_below_their_parents() {
+        let parent = Transform::from_translation([0.0, 0.0, 5.0]);
+        let child = scale(2.0);
+        let hxa = Hxa {
+            version: HxaVersion::LATEST,
+            nodes: vec![
+                node(MetaBuilder::new().transform(child)),
+                node(
+                    MetaBuilder::new()
+                        .transform(parent)
+                        .node("children", &[0, 2]),
+                ),
+                node(MetaBuilder::new()),
+            ],
+        };
+        let instances = hxa.instances().unwrap();
+        assert_eq!(
+            instances,
+            [
+                Instance {
+                    node: 1,
+                    world: parent
+                },
+                Instance {
+                    node: 0,
+                    world: parent * child
+                },
+                Instance {
+                    node: 2,
+                    world: parent
+                },
+            ]
+        );
+        assert_eq!(
+            instances[1].world.transform_point([1.0, 1.0, 1.0]),
+            [2.0, 2.0, 7.0]
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::{
+    HxaError, HxaResult, LayerData, NodeGeometry, StackKind, HC_BASE_VERTEX_LAYER_COMPONENTS,
+    HC_BASE_VERTEX_LAYER_ID,
+};
+
+/// The result of [`NodeGeometry::triangulate`].
+///
+/// All three vectors have one entry per triangle. Triangles keep the winding
+/// order of the polygons they were cut from.
+#[derive(Clone, Debug, Default)]
+pub struct Triangulation {
+    /// The vertex indices of each triangle.
+    pub triangles: Vec<[u32; 3]>,
+    /// The corners each triangle's vertices came from, which can be used to
+    /// look up corner attributes such as UVs or normals.
+    pub corners: Vec<[u32; 3]>,
+    /// The polygon each triangle was cut from.
+    pub polygons: Vec<u32>,
+}
+
+impl NodeGeometry<'_> {
+    /// Splits every polygon into triangles.
+    ///
+    /// Concave polygons are handled by ear clipping in the plane that best
+    /// fits the polygon. Polygons with fewer than three corners produce no
+    /// triangles.
+    pub fn triangulate(&self) -> HxaResult<Triangulation> {
+        let positions = self.positions()?;
+        let mut triangulation = Triangulation::default();
+        let mut scratch = Scratch::default();
+
+        for (index, polygon) in self.polygons()?.enumerate() {
+            let polygon = polygon?;
+            let index = u32::try_from(index).map_err(|_| HxaError::CountTooLarge(index))?;
+            let first = u32::try_from(polygon.corners.start)
+                .map_err(|_| HxaError::CountTooLarge(polygon.corners.start))?;
+
+            scratch.clear();
+            for vertex in polygon.vertices() {
+                let position = positions
+                    .get(vertex as usize)
+                    .ok_or(HxaError::VertexIndexOutOfRange(vertex))?;
+                scratch.vertices.push(vertex);
+                scratch.positions.push(position);
+            }
+
+            scratch.triangulate();
+            for &[a, b, c] in &scratch.triangles {
+                let vertices = &scratch.vertices;
+                triangulation
+                    .triangles
+                    .push([vertices[a], vertices[b], vertices[c]]);
+                triangulation
+                    .corners
+                    .push([first + a as u32, first + b as u32, first + c as u32]);
+                triangulation.polygons.push(index);
+            }
+        }
+
+        Ok(triangulation)
+    }
+
+    fn positions(&self) -> HxaResult<Positions<'_>> {
+        let layer = self
+            .vertex_stack
+            .layers
+            .get(HC_BASE_VERTEX_LAYER_ID)
+            .filter(|layer| layer.component_count == HC_BASE_VERTEX_LAYER_COMPONENTS)
+            .ok_or(HxaError::InvalidBaseLayer(StackKind::Vertex))?;
+        match &layer.data {
+            LayerData::Float(data) => Ok(Positions::Float(data)),
+            LayerData::Double(data) => Ok(Positions::Double(data)),
+            _ => Err(HxaError::InvalidBaseLayer(StackKind::Vertex)),
+        }
+    }
+}
+
+enum Positions<'g> {
+    Float(&'g [f32]),
+    Double(&'g [f64]),
+}
+
+impl Positions<'_> {
+    fn get(&self, vertex: usize) -> Option<[f64; 3]> {
+        let range = vertex * 3..vertex * 3 + 3;
+        match self {
+            Positions::Float(data) => {
+                let p = data.get(range)?;
+                Some([p[0] as f64, p[1] as f64, p[2] as f64])
+            }
+            Positions::Double(data) => {
+                let p = data.get(range)?;
+                Some([p[0], p[1], p[2]])
+            }
+        }
+    }
+}
+
+/// Buffers reused between polygons.
+#[derive(Default)]
+struct Scratch {
+    vertices: Vec<u32>,
+    positions: Vec<[f64; 3]>,
+    projected: Vec<[f64; 2]>,
+    remaining: Vec<usize>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl Scratch {
+    fn clear(&mut self) {
+        self.vertices.clear();
+        self.positions.clear();
+    }
+
+    /// Triangulates the polygon in `positions` into `triangles`, which are
+    /// made of indices into it.
+    fn triangulate(&mut self) {
+        self.triangles.clear();
+        let n = self.positions.len();
+        if n == 3 {
+            self.triangles.push([0, 1, 2]);
+        } else if n > 3 {
+            self.ear_clip();
+        }
+    }
+
+    fn ear_clip(&mut self) {
+        let normal = newell_normal(&self.positions);
+        // Drop the axis the polygon is most aligned with.
+        let abs = [normal[0].abs(), normal[1].abs(), normal[2].abs()];
+        let (u, v) = if abs[0] >= abs[1] && abs[0] >= abs[2] {
+            (1, 2)
+        } else if abs[1] >= abs[2] {
+            (2, 0)
+        } else {
+            (0, 1)
+        };
+        self.projected.clear();
+        self.projected
+            .extend(self.positions.iter().map(|p| [p[u], p[v]]));
+
+        let area = signed_area(&self.projected);
+        if area == 0.0 || !area.is_finite() {
+            // There is no sensible plane to work in, so fall back to a fan.
+            let n = self.positions.len();
+            self.triangles.extend((1..n - 1).map(|i| [0, i, i + 1]));
+            return;
+        }
+        // Make convex corners turn in the positive direction.
+        let orientation = area.signum();
+
+        self.remaining.clear();
+        self.remaining.extend(0..self.positions.len());
+        while self.remaining.len() > 3 {
+            let len = self.remaining.len();
+            let ear = (0..len)
+                .find(|&i| self.is_ear(i, orientation))
+                // Self-intersecting or numerically troublesome polygons may not
+                // have any ears left. Clipping anything still makes progress,
+                // but a convex corner at least keeps the triangle facing the
+                // right way.
+                .or_else(|| (0..len).find(|&i| self.is_convex(i, orientation)))
+                .unwrap_or(0);
+            self.triangles.push(self.corner(ear));
+            self.remaining.remove(ear);
+        }
+        self.triangles
+            .push([self.remaining[0], self.remaining[1], self.remaining[2]]);
+    }
+
+    /// The corner before, at and after the `i`th remaining corner.
+    fn corner(&self, i: usize) -> [usize; 3] {
+        let len = self.remaining.len();
+        [
+            self.remaining[(i + len - 1) % len],
+            self.remaining[i],
+            self.remaining[(i + 1) % len],
+        ]
+    }
+
+    fn is_convex(&self, i: usize, orientation: f64) -> bool {
+        let [prev, this, next] = self.corner(i);
+        let [a, b, c] = [
+            self.projected[prev],
+            self.projected[this],
+            self.projected[next],
+        ];
+        cross(a, b, c) * orientation > 0.0
+    }
+
+    fn is_ear(&self, i: usize, orientation: f64) -> bool {
+        if !self.is_convex(i, orientation) {
+            return false;
+        }
+
+        let [prev, this, next] = self.corner(i);
+        let [a, b, c] = [
+            self.projected[prev],
+            self.projected[this],
+            self.projected[next],
+        ];
+
+        self.remaining.iter().all(|&other| {
+            if other == prev || other == this || other == next {
+                return true;
+            }
+            let p = self.projected[other];
+            if p == a || p == b || p == c {
+                return true;
+            }
+            let inside = cross(a, b, p) * orientation >= 0.0
+                && cross(b, c, p) * orientation >= 0.0
+                && cross(c, a, p) * orientation >= 0.0;
+            !inside
+        })
+    }
+}
+
+fn newell_normal(positions: &[[f64; 3]]) -> [f64; 3] {
+    let mut normal = [0.0; 3];
+    for (i, a) in positions.iter().enumerate() {
+        let b = positions[(i + 1) % positions.len()];
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    normal
+}
+
+fn signed_area(points: &[[f64; 2]]) -> f64 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a[0] * b[1] - b[0] * a[1];
+    }
+    area / 2.0
+}
+
+/// The z component of `(b - a) x (c - b)`.
+fn cross(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GeometryBuilder, Node, NodeContent};
+
+    fn node(positions: &[[f64; 3]], polygons: &[&[u32]]) -> Node<'static> {
+        GeometryBuilder::new(positions)
+            .polygons(polygons.iter().copied())
+            .build()
+            .unwrap()
+    }
+
+    fn triangulate(node: &Node<'_>) -> Triangulation {
+        match &node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry.triangulate().unwrap(),
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    /// Twice the area of each triangle, projected onto `axis` so that it is
+    /// negative for triangles wound the other way around it.
+    fn areas(positions: &[[f64; 3]], triangulation: &Triangulation, axis: [f64; 3]) -> Vec<f64> {
+        triangulation
+            .triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|vertex| positions[vertex as usize]);
+                let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+                let normal = [
+                    u[1] * v[2] - u[2] * v[1],
+                    u[2] * v[0] - u[0] * v[2],
+                    u[0] * v[1] - u[1] * v[0],
+                ];
+                normal[0] * axis[0] + normal[1] * axis[1] + normal[2] * axis[2]
+            })
+            .collect()
+    }
+
+    const L_SHAPE: [[f64; 3]; 6] = [
+        [0.0, 0.0, 0.0],
+        [2.0, 0.0, 0.0],
+        [2.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [1.0, 2.0, 0.0],
+        [0.0, 2.0, 0.0],
+    ];
+
+    #[test]
+    fn clips_concave_polygons() {
+        let triangulation = triangulate(&node(&L_SHAPE, &[&[0, 1, 2, 3, 4, 5]]));
+        assert_eq!(triangulation.triangles.len(), 4);
+        // Triangles inside the polygon that don't overlap cover it exactly,
+        // while any cut across the notch would add area outside it.
+        let twice = areas(&L_SHAPE, &triangulation, [0.0, 0.0, 1.0]);
+        assert!(twice.iter().all(|&area| area > 0.0), "{:?}", twice);
+        assert_eq!(twice.iter().sum::<f64>(), 2.0 * 3.0);
+    }
+
+    #[test]
+    fn preserves_winding() {
+        let triangulation = triangulate(&node(&L_SHAPE, &[&[5, 4, 3, 2, 1, 0]]));
+        let twice = areas(&L_SHAPE, &triangulation, [0.0, 0.0, 1.0]);
+        assert!(twice.iter().all(|&area| area < 0.0), "{:?}", twice);
+        assert_eq!(twice.iter().sum::<f64>(), -2.0 * 3.0);
+
+        // The same shape standing upright, facing down the x axis.
+        let upright: Vec<[f64; 3]> = L_SHAPE.iter().map(|&[x, y, _]| [0.0, y, x]).collect();
+        let triangulation = triangulate(&node(&upright, &[&[0, 1, 2, 3, 4, 5]]));
+        let twice = areas(&upright, &triangulation, [-1.0, 0.0, 0.0]);
+        assert!(twice.iter().all(|&area| area > 0.0), "{:?}", twice);
+        assert_eq!(twice.iter().sum::<f64>(), 2.0 * 3.0);
+    }
+
+    #[test]
+    fn splits_non_planar_quads() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.5],
+            [0.0, 1.0, 0.0],
+        ];
+        let triangulation = triangulate(&node(&positions, &[&[0, 1, 2, 3]]));
+        assert_eq!(triangulation.triangles.len(), 2);
+        let mut used: Vec<u32> = triangulation.triangles.iter().flatten().copied().collect();
+        used.sort_unstable();
+        used.dedup();
+        assert_eq!(used, [0, 1, 2, 3]);
+        let twice = areas(&positions, &triangulation, [0.0, 0.0, 1.0]);
+        assert!(twice.iter().all(|&area| area > 0.0), "{:?}", twice);
+    }
+
+    #[test]
+    fn handles_collinear_and_degenerate_polygons() {
+        // A square with an extra corner halfway along its bottom edge.
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [0.5, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let triangulation = triangulate(&node(&positions, &[&[0, 1, 2, 3, 4]]));
+        assert_eq!(triangulation.triangles.len(), 3);
+        let twice = areas(&positions, &triangulation, [0.0, 0.0, 1.0]);
+        assert!(twice.iter().all(|&area| area >= 0.0), "{:?}", twice);
+        assert_eq!(twice.iter().sum::<f64>(), 2.0);
+
+        // With no area at all, there is no plane to clip in, so the polygon
+        // is split into a fan instead.
+        let line = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [3.0, 0.0, 0.0],
+        ];
+        let triangulation = triangulate(&node(&line, &[&[0, 1, 2, 3]]));
+        assert_eq!(triangulation.triangles, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn maps_triangles_to_corners_and_polygons() {
+        let mut positions = L_SHAPE.to_vec();
+        positions.push([5.0, 5.0, 0.0]);
+        let node = node(&positions, &[&[6, 0, 1], &[0, 1, 2, 3, 4, 5]]);
+        let triangulation = triangulate(&node);
+        assert_eq!(triangulation.polygons, [0, 1, 1, 1, 1]);
+        assert_eq!(triangulation.triangles[0], [6, 0, 1]);
+        assert_eq!(triangulation.corners[0], [0, 1, 2]);
+
+        // The second polygon's corners come after the three of the first,
+        // with each corner referencing the vertex its triangle uses.
+        let references = [6, 0, 1, 0, 1, 2, 3, 4, 5];
+        for (triangle, corners) in triangulation.triangles.iter().zip(&triangulation.corners) {
+            for (&vertex, &corner) in triangle.iter().zip(corners) {
+                assert_eq!(references[corner as usize], vertex);
+            }
+        }
+        for corners in &triangulation.corners[1..] {
+            assert!(corners.iter().all(|&corner| corner >= 3));
+        }
+    }
+}
@@ -0,0 +1,703 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{
+    Hxa, LayerData, LayerDataType, LayerStack, Node, NodeContent, NodeGeometry, NodeImage,
+    NodeType, StackKind, HC_BASE_CORNER_LAYER_COMPONENTS, HC_BASE_CORNER_LAYER_NAME,
+    HC_BASE_CORNER_LAYER_TYPE, HC_BASE_VERTEX_LAYER_COMPONENTS, HC_BASE_VERTEX_LAYER_NAME,
+    HC_EDGE_NEIGHBOUR_LAYER_NAME, HC_EDGE_NEIGHBOUR_LAYER_TYPE,
+};
+
+/// A violation of the hard conventions found by [`Hxa::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Index of the offending node.
+    pub node: usize,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// The node's content does not match its type.
+    ContentMismatch,
+    MissingBaseLayer(StackKind),
+    BaseLayerName {
+        stack: StackKind,
+        name: String,
+    },
+    BaseLayerComponents {
+        stack: StackKind,
+        components: u8,
+    },
+    BaseLayerType {
+        stack: StackKind,
+        type_: LayerDataType,
+    },
+    /// A layer's `type_` does not match the type of its data.
+    LayerTypeMismatch {
+        stack: StackKind,
+        layer: usize,
+    },
+    LayerLength {
+        stack: StackKind,
+        layer: usize,
+        expected: usize,
+        found: usize,
+    },
+    DuplicateLayerName {
+        stack: StackKind,
+        name: String,
+    },
+    /// A corner references a vertex that does not exist.
+    ReferenceOutOfRange {
+        corner: usize,
+        vertex: i32,
+    },
+    /// The corners from `corner` onwards are not terminated by a negative
+    /// reference.
+    UnterminatedPolygon {
+        corner: usize,
+    },
+    NeighbourLayerType(LayerDataType),
+    NeighbourLayerComponents(u8),
+    /// An edge's neighbour is neither -1 nor a valid edge.
+    NeighbourOutOfRange {
+        edge: usize,
+        neighbour: i32,
+    },
+    /// A `Node` metadata value references a node that does not exist.
+    NodeReferenceOutOfRange {
+        meta: String,
+        node: u32,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Node {}: ", self.node)?;
+        match &self.kind {
+            DiagnosticKind::ContentMismatch => {
+                write!(f, "Content does not match the node type")
+            }
+            DiagnosticKind::MissingBaseLayer(stack) => {
+                write!(f, "The {} stack has no base layer", stack)
+            }
+            DiagnosticKind::BaseLayerName { stack, name } => {
+                write!(f, r#"The {} base layer is named "{}""#, stack, name)
+            }
+            DiagnosticKind::BaseLayerComponents { stack, components } => {
+                write!(f, "The {} base layer has {} components", stack, components)
+            }
+            DiagnosticKind::BaseLayerType { stack, type_ } => {
+                write!(f, "The {} base layer is of type {:?}", stack, type_)
+            }
+            DiagnosticKind::LayerTypeMismatch { stack, layer } => {
+                write!(
+                    f,
+                    "Layer {} of the {} stack does not match its declared type",
+                    layer, stack
+                )
+            }
+            DiagnosticKind::LayerLength {
+                stack,
+                layer,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Layer {} of the {} stack has {} values instead of {}",
+                layer, stack, found, expected
+            ),
+            DiagnosticKind::DuplicateLayerName { stack, name } => {
+                write!(
+                    f,
+                    r#"More than one layer of the {} stack is named "{}""#,
+                    stack, name
+                )
+            }
+            DiagnosticKind::ReferenceOutOfRange { corner, vertex } => {
+                write!(
+                    f,
+                    "Corner {} references vertex {}, which does not exist",
+                    corner, vertex
+                )
+            }
+            DiagnosticKind::UnterminatedPolygon { corner } => {
+                write!(
+                    f,
+                    "The polygon starting at corner {} is unterminated",
+                    corner
+                )
+            }
+            DiagnosticKind::NeighbourLayerType(type_) => {
+                write!(f, "The neighbour layer is of type {:?}", type_)
+            }
+            DiagnosticKind::NeighbourLayerComponents(components) => {
+                write!(f, "The neighbour layer has {} components", components)
+            }
+            DiagnosticKind::NeighbourOutOfRange { edge, neighbour } => {
+                write!(
+                    f,
+                    "Edge {} has neighbour {}, which does not exist",
+                    edge, neighbour
+                )
+            }
+            DiagnosticKind::NodeReferenceOutOfRange { meta, node } => {
+                write!(
+                    f,
+                    r#"Metadata "{}" references node {}, which does not exist"#,
+                    meta, node
+                )
+            }
+        }
+    }
+}
+
+impl Hxa<'_> {
+    /// Checks the file against the hard conventions of the format.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator {
+            node_count: self.nodes.len(),
+            node: 0,
+            diagnostics: Vec::new(),
+        };
+        for (index, node) in self.nodes.iter().enumerate() {
+            validator.node = index;
+            validator.node(node);
+        }
+        validator.diagnostics
+    }
+}
+
+struct Validator {
+    node_count: usize,
+    node: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn report(&mut self, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            node: self.node,
+            kind,
+        });
+    }
+
+    fn node(&mut self, node: &Node<'_>) {
+        self.metadata(node);
+        match (&node.type_, &node.content) {
+            (NodeType::Geometry, Some(NodeContent::Geometry(geometry))) => self.geometry(geometry),
+            (NodeType::Image, Some(NodeContent::Image(image))) => self.image(image),
+            (NodeType::Meta, None) => {}
+            _ => self.report(DiagnosticKind::ContentMismatch),
+        }
+    }
+
+    fn metadata(&mut self, node: &Node<'_>) {
+        for (path, meta) in node.meta_walk() {
+            for &referenced in meta.value.as_node_refs().unwrap_or_default() {
+                if referenced as usize >= self.node_count {
+                    self.report(DiagnosticKind::NodeReferenceOutOfRange {
+                        meta: path.clone(),
+                        node: referenced,
+                    });
+                }
+            }
+        }
+    }
+
+    fn geometry(&mut self, geometry: &NodeGeometry<'_>) {
+        let vertex_count = self.vertex_base_layer(&geometry.vertex_stack);
+        let references = self.corner_base_layer(&geometry.corner_stack);
+        let corner_count = references.map_or(0, <[i32]>::len);
+
+        let mut face_count = 0;
+        let mut polygon_start = 0;
+        for (corner, &reference) in references.unwrap_or_default().iter().enumerate() {
+            let vertex = if reference < 0 {
+                -(reference + 1)
+            } else {
+                reference
+            };
+            if let Some(vertex_count) = vertex_count {
+                if vertex as usize >= vertex_count {
+                    self.report(DiagnosticKind::ReferenceOutOfRange {
+                        corner,
+                        vertex: reference,
+                    });
+                }
+            }
+            if reference < 0 {
+                face_count += 1;
+                polygon_start = corner + 1;
+            }
+        }
+        if polygon_start < corner_count {
+            self.report(DiagnosticKind::UnterminatedPolygon {
+                corner: polygon_start,
+            });
+        }
+
+        if let Some(vertex_count) = vertex_count {
+            self.stack(StackKind::Vertex, &geometry.vertex_stack, vertex_count);
+        }
+        if references.is_some() {
+            self.stack(StackKind::Corner, &geometry.corner_stack, corner_count);
+            self.stack(StackKind::Edge, &geometry.edge_stack, corner_count);
+            self.stack(StackKind::Face, &geometry.face_stack, face_count);
+        }
+
+        if let Some(layer) = geometry.edge_stack.get(HC_EDGE_NEIGHBOUR_LAYER_NAME) {
+            if layer.component_count != 1 {
+                self.report(DiagnosticKind::NeighbourLayerComponents(
+                    layer.component_count,
+                ));
+            }
+            match &layer.data {
+                LayerData::Int32(neighbours) if layer.type_ == HC_EDGE_NEIGHBOUR_LAYER_TYPE => {
+                    for (edge, &neighbour) in neighbours.iter().enumerate() {
+                        if neighbour < -1 || neighbour >= 0 && neighbour as usize >= corner_count {
+                            self.report(DiagnosticKind::NeighbourOutOfRange { edge, neighbour });
+                        }
+                    }
+                }
+                data => self.report(DiagnosticKind::NeighbourLayerType(data.type_())),
+            }
+        }
+    }
+
+    /// Checks the base vertex layer, returning the number of vertices if it
+    /// is usable.
+    fn vertex_base_layer(&mut self, stack: &LayerStack<'_>) -> Option<usize> {
+        let stack_kind = StackKind::Vertex;
+        let layer = match stack.layers.first() {
+            Some(layer) => layer,
+            None => {
+                self.report(DiagnosticKind::MissingBaseLayer(stack_kind));
+                return None;
+            }
+        };
+        if layer.name != HC_BASE_VERTEX_LAYER_NAME {
+            self.report(DiagnosticKind::BaseLayerName {
+                stack: stack_kind,
+                name: String::from(&*layer.name),
+            });
+        }
+        if layer.component_count != HC_BASE_VERTEX_LAYER_COMPONENTS {
+            self.report(DiagnosticKind::BaseLayerComponents {
+                stack: stack_kind,
+                components: layer.component_count,
+            });
+            return None;
+        }
+        match layer.data.type_() {
+            LayerDataType::Float | LayerDataType::Double => {
+                Some(layer.data.len() / HC_BASE_VERTEX_LAYER_COMPONENTS as usize)
+            }
+            type_ => {
+                self.report(DiagnosticKind::BaseLayerType {
+                    stack: stack_kind,
+                    type_,
+                });
+                None
+            }
+        }
+    }
+
+    /// Checks the base corner layer, returning its references if it is
+    /// usable.
+    fn corner_base_layer<'s>(&mut self, stack: &'s LayerStack<'_>) -> Option<&'s [i32]> {
+        let stack_kind = StackKind::Corner;
+        let layer = match stack.layers.first() {
+            Some(layer) => layer,
+            None => {
+                self.report(DiagnosticKind::MissingBaseLayer(stack_kind));
+                return None;
+            }
+        };
+        if layer.name != HC_BASE_CORNER_LAYER_NAME {
+            self.report(DiagnosticKind::BaseLayerName {
+                stack: stack_kind,
+                name: String::from(&*layer.name),
+            });
+        }
+        if layer.component_count != HC_BASE_CORNER_LAYER_COMPONENTS {
+            self.report(DiagnosticKind::BaseLayerComponents {
+                stack: stack_kind,
+                components: layer.component_count,
+            });
+            return None;
+        }
+        match &layer.data {
+            LayerData::Int32(references) if layer.type_ == HC_BASE_CORNER_LAYER_TYPE => {
+                Some(references)
+            }
+            data => {
+                self.report(DiagnosticKind::BaseLayerType {
+                    stack: stack_kind,
+                    type_: data.type_(),
+                });
+                None
+            }
+        }
+    }
+
+    fn image(&mut self, image: &NodeImage<'_>) {
+        if let Ok(size) = image.pixel_count() {
+            self.stack(StackKind::Image, &image.image_stack, size);
+        }
+    }
+
+    fn stack(&mut self, kind: StackKind, stack: &LayerStack<'_>, length: usize) {
+        for (index, layer) in stack.layers.iter().enumerate() {
+            if layer.type_ != layer.data.type_() {
+                self.report(DiagnosticKind::LayerTypeMismatch {
+                    stack: kind,
+                    layer: index,
+                });
+            }
+            let expected = layer.component_count as usize * length;
+            if layer.data.len() != expected {
+                self.report(DiagnosticKind::LayerLength {
+                    stack: kind,
+                    layer: index,
+                    expected,
+                    found: layer.data.len(),
+                });
+            }
+            if stack.layers[..index]
+                .iter()
+                .any(|other| other.name == layer.name)
+            {
+                self.report(DiagnosticKind::DuplicateLayerName {
+                    stack: kind,
+                    name: String::from(&*layer.name),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+    use alloc::vec;
+
+    use super::*;
+    use crate::{GeometryBuilder, HxaVersion, ImageBuilder, Layer, MetaBuilder};
+
+    fn quad() -> Node<'static> {
+        GeometryBuilder::new(&[[0.0f32; 3]; 4])
+            .polygon(&[0, 1, 2, 3])
+            .corner_layer("uv", &[[0.0f32; 2]; 4])
+            .build()
+            .unwrap()
+    }
+
+    fn geometry<'n>(node: &'n mut Node<'static>) -> &'n mut NodeGeometry<'static> {
+        match &mut node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    fn validate(nodes: Vec<Node<'static>>) -> Vec<Diagnostic> {
+        Hxa {
+            version: HxaVersion::LATEST,
+            nodes,
+        }
+        .validate()
+    }
+
+    /// Validates a quad after applying `change` to its geometry.
+    fn validate_quad(change: impl FnOnce(&mut NodeGeometry<'static>)) -> Vec<DiagnosticKind> {
+        let mut node = quad();
+        change(geometry(&mut node));
+        validate(vec![node])
+            .into_iter()
+            .map(|diagnostic| diagnostic.kind)
+            .collect()
+    }
+
+    fn set_references(geometry: &mut NodeGeometry<'static>, references: &[i32]) {
+        geometry.corner_stack.layers[0] = Layer::from_slice("reference", references);
+    }
+
+    #[test]
+    fn accepts_well_formed_files() {
+        let image = ImageBuilder::image_2d(2, 2)
+            .layer("color", &[[0u8; 3]; 4])
+            .build()
+            .unwrap();
+        let meta = Node {
+            type_: NodeType::Meta,
+            metadata: MetaBuilder::new().node("children", &[0, 1]).build(),
+            content: None,
+        };
+        assert_eq!(validate(vec![quad(), image, meta]), []);
+    }
+
+    #[test]
+    fn checks_content_against_node_type() {
+        let mut image = quad();
+        image.type_ = NodeType::Image;
+        let mut meta = quad();
+        meta.type_ = NodeType::Meta;
+        let mut empty = quad();
+        empty.content = None;
+        let diagnostics = validate(vec![quad(), image, meta, empty]);
+        let nodes: Vec<usize> = diagnostics.iter().map(|d| d.node).collect();
+        assert_eq!(nodes, [1, 2, 3]);
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.kind == DiagnosticKind::ContentMismatch));
+    }
+
+    #[test]
+    fn checks_the_base_vertex_layer() {
+        assert_eq!(
+            validate_quad(|g| g.vertex_stack.layers.clear()),
+            [DiagnosticKind::MissingBaseLayer(StackKind::Vertex)]
+        );
+        assert_eq!(
+            validate_quad(|g| g.vertex_stack.layers[0].name = Cow::Borrowed("position")),
+            [DiagnosticKind::BaseLayerName {
+                stack: StackKind::Vertex,
+                name: String::from("position"),
+            }]
+        );
+        assert_eq!(
+            validate_quad(|g| g.vertex_stack.layers[0] = Layer::from_slice("vertex", &[0.0f32; 8])),
+            [DiagnosticKind::BaseLayerComponents {
+                stack: StackKind::Vertex,
+                components: 1,
+            }]
+        );
+        assert_eq!(
+            validate_quad(|g| g.vertex_stack.layers[0] = Layer::from_slice("vertex", &[[0; 3]; 4])),
+            [DiagnosticKind::BaseLayerType {
+                stack: StackKind::Vertex,
+                type_: LayerDataType::Int32,
+            }]
+        );
+    }
+
+    #[test]
+    fn checks_the_base_corner_layer() {
+        assert_eq!(
+            validate_quad(|g| g.corner_stack.layers.clear()),
+            [DiagnosticKind::MissingBaseLayer(StackKind::Corner)]
+        );
+        assert_eq!(
+            validate_quad(|g| g.corner_stack.layers[0].name = Cow::Borrowed("index")),
+            [DiagnosticKind::BaseLayerName {
+                stack: StackKind::Corner,
+                name: String::from("index"),
+            }]
+        );
+        assert_eq!(
+            validate_quad(
+                |g| g.corner_stack.layers[0] = Layer::from_slice("reference", &[[0, -2]; 2])
+            ),
+            [DiagnosticKind::BaseLayerComponents {
+                stack: StackKind::Corner,
+                components: 2,
+            }]
+        );
+        assert_eq!(
+            validate_quad(
+                |g| g.corner_stack.layers[0] = Layer::from_slice("reference", &[0.0f32; 4])
+            ),
+            [DiagnosticKind::BaseLayerType {
+                stack: StackKind::Corner,
+                type_: LayerDataType::Float,
+            }]
+        );
+    }
+
+    #[test]
+    fn checks_references() {
+        // -10 is the last corner of a polygon, referencing vertex 9.
+        assert_eq!(
+            validate_quad(|g| set_references(g, &[0, 4, 2, -10])),
+            [
+                DiagnosticKind::ReferenceOutOfRange {
+                    corner: 1,
+                    vertex: 4,
+                },
+                DiagnosticKind::ReferenceOutOfRange {
+                    corner: 3,
+                    vertex: -10,
+                },
+            ]
+        );
+        assert_eq!(
+            validate_quad(|g| set_references(g, &[0, 1, 2, 3])),
+            [DiagnosticKind::UnterminatedPolygon { corner: 0 }]
+        );
+        assert_eq!(
+            validate_quad(|g| {
+                set_references(g, &[0, 1, -3, 3]);
+                g.corner_stack.layers[1] = Layer::from_slice("uv", &[[0.0f32; 2]; 4]);
+            }),
+            [DiagnosticKind::UnterminatedPolygon { corner: 3 }]
+        );
+    }
+
+    #[test]
+    fn checks_layer_lengths_and_types() {
+        assert_eq!(
+            validate_quad(|g| {
+                g.vertex_stack
+                    .layers
+                    .push(Layer::from_slice("weight", &[1.0f32; 3]));
+                g.face_stack
+                    .layers
+                    .push(Layer::from_slice("material", &[0, 0]));
+                g.edge_stack
+                    .layers
+                    .push(Layer::from_slice("crease", &[0u8; 8]));
+            }),
+            [
+                DiagnosticKind::LayerLength {
+                    stack: StackKind::Vertex,
+                    layer: 1,
+                    expected: 4,
+                    found: 3,
+                },
+                DiagnosticKind::LayerLength {
+                    stack: StackKind::Edge,
+                    layer: 0,
+                    expected: 4,
+                    found: 8,
+                },
+                DiagnosticKind::LayerLength {
+                    stack: StackKind::Face,
+                    layer: 0,
+                    expected: 1,
+                    found: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            validate_quad(|g| g.corner_stack.layers[1].type_ = LayerDataType::Double),
+            [DiagnosticKind::LayerTypeMismatch {
+                stack: StackKind::Corner,
+                layer: 1,
+            }]
+        );
+
+        let mut image = ImageBuilder::cube(2, 2)
+            .layer("color", &[[0u8; 4]; 24])
+            .build()
+            .unwrap();
+        if let Some(NodeContent::Image(image)) = &mut image.content {
+            image.resolution = [2, 3, 1];
+        }
+        assert_eq!(
+            validate(vec![image]),
+            [Diagnostic {
+                node: 0,
+                kind: DiagnosticKind::LayerLength {
+                    stack: StackKind::Image,
+                    layer: 0,
+                    expected: 4 * 36,
+                    found: 4 * 24,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn checks_for_duplicate_names() {
+        assert_eq!(
+            validate_quad(|g| {
+                let uv = g.corner_stack.layers[1].clone();
+                g.corner_stack.layers.push(uv);
+            }),
+            [DiagnosticKind::DuplicateLayerName {
+                stack: StackKind::Corner,
+                name: String::from("uv"),
+            }]
+        );
+    }
+
+    #[test]
+    fn checks_the_neighbour_layer() {
+        assert_eq!(
+            validate_quad(|g| g
+                .edge_stack
+                .layers
+                .push(Layer::from_slice("neighbour", &[-1, -1, -1, -1]))),
+            []
+        );
+        assert_eq!(
+            validate_quad(|g| g
+                .edge_stack
+                .layers
+                .push(Layer::from_slice("neighbour", &[-1.0f32; 4]))),
+            [DiagnosticKind::NeighbourLayerType(LayerDataType::Float)]
+        );
+        assert_eq!(
+            validate_quad(|g| g
+                .edge_stack
+                .layers
+                .push(Layer::from_slice("neighbour", &[[-1, 0]; 4]))),
+            [DiagnosticKind::NeighbourLayerComponents(2)]
+        );
+        assert_eq!(
+            validate_quad(|g| g
+                .edge_stack
+                .layers
+                .push(Layer::from_slice("neighbour", &[3, 4, -1, -2]))),
+            [
+                DiagnosticKind::NeighbourOutOfRange {
+                    edge: 1,
+                    neighbour: 4,
+                },
+                DiagnosticKind::NeighbourOutOfRange {
+                    edge: 3,
+                    neighbour: -2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_node_references_in_nested_metadata() {
+        let metadata = MetaBuilder::new()
+            .node("children", &[0, 2])
+            .meta(
+                "material",
+                MetaBuilder::new()
+                    .text("name", "steel")
+                    .meta("albedo", MetaBuilder::new().node("texture", &[1, 7])),
+            )
+            .build();
+        let node = Node {
+            type_: NodeType::Meta,
+            metadata,
+            content: None,
+        };
+        assert_eq!(
+            validate(vec![quad(), node]),
+            [
+                Diagnostic {
+                    node: 1,
+                    kind: DiagnosticKind::NodeReferenceOutOfRange {
+                        meta: String::from("children"),
+                        node: 2,
+                    },
+                },
+                Diagnostic {
+                    node: 1,
+                    kind: DiagnosticKind::NodeReferenceOutOfRange {
+                        meta: String::from("material/albedo/texture"),
+                        node: 7,
+                    },
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,578 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::{
+    Hxa, HxaError, HxaResult, HxaVersion, ImageType, Layer, LayerData, LayerDataType, LayerStack,
+    Meta, MetaValue, MetadataType, Node, NodeContent, NodeGeometry, NodeImage, NodeType,
+};
+
+trait Sink {
+    fn put(&mut self, bytes: &[u8]) -> HxaResult<()>;
+}
+
+impl Sink for Vec<u8> {
+    fn put(&mut self, bytes: &[u8]) -> HxaResult<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+struct IoSink<W>(W);
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Sink for IoSink<W> {
+    fn put(&mut self, bytes: &[u8]) -> HxaResult<()> {
+        self.0.write_all(bytes).map_err(HxaError::Io)
+    }
+}
+
+trait ToData {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()>;
+}
+
+impl ToData for u8 {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(&[*self])
+    }
+}
+
+impl ToData for u32 {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(&self.to_le_bytes())
+    }
+}
+
+impl ToData for i32 {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(&self.to_le_bytes())
+    }
+}
+
+impl ToData for i64 {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(&self.to_le_bytes())
+    }
+}
+
+impl ToData for f32 {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(&self.to_le_bytes())
+    }
+}
+
+impl ToData for f64 {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(&self.to_le_bytes())
+    }
+}
+
+impl ToData for HxaVersion {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        let n: u32 = match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+            Self::V3 => 3,
+        };
+        n.write(sink)
+    }
+}
+
+impl ToData for NodeType {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        let n: u8 = match self {
+            Self::Meta => 0,
+            Self::Geometry => 1,
+            Self::Image => 2,
+        };
+        n.write(sink)
+    }
+}
+
+impl ToData for LayerDataType {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        let n: u8 = match self {
+            Self::Uint8 => 0,
+            Self::Int32 => 1,
+            Self::Float => 2,
+            Self::Double => 3,
+        };
+        n.write(sink)
+    }
+}
+
+impl ToData for ImageType {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        let n: u8 = match self {
+            Self::ImageCube => 0,
+            Self::Image1D => 1,
+            Self::Image2D => 2,
+            Self::Image3D => 3,
+        };
+        n.write(sink)
+    }
+}
+
+impl ToData for MetadataType {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        let n: u8 = match self {
+            Self::Int64 => 0,
+            Self::Double => 1,
+            Self::Node => 2,
+            Self::Text => 3,
+            Self::Binary => 4,
+            Self::Meta => 5,
+        };
+        n.write(sink)
+    }
+}
+
+impl Hxa<'_> {
+    /// Serializes the file into a freshly allocated buffer, using the layout
+    /// of `self.version`.
+    pub fn to_bytes(&self) -> HxaResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Serializes the file into `writer`.
+    #[cfg(feature = "std")]
+    pub fn write_to<W: io::Write>(&self, writer: W) -> HxaResult<()> {
+        self.write(&mut IoSink(writer))
+    }
+
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        sink.put(b"HxA\0")?;
+        self.version.write(sink)?;
+        write_count(sink, self.nodes.len())?;
+        for node in &self.nodes {
+            node.write(sink, self.version)?;
+        }
+        Ok(())
+    }
+}
+
+impl Node<'_> {
+    fn write<S: Sink>(&self, sink: &mut S, version: HxaVersion) -> HxaResult<()> {
+        self.type_.write(sink)?;
+        Meta::write_all(sink, &self.metadata)?;
+
+        match (&self.type_, &self.content) {
+            (NodeType::Geometry, Some(NodeContent::Geometry(geometry))) => {
+                geometry.write(sink, version)
+            }
+            (NodeType::Image, Some(NodeContent::Image(image))) => image.write(sink),
+            (NodeType::Meta, None) => Ok(()),
+            (type_, _) => Err(HxaError::NodeContentMismatch(type_.clone())),
+        }
+    }
+}
+
+impl NodeGeometry<'_> {
+    fn write<S: Sink>(&self, sink: &mut S, version: HxaVersion) -> HxaResult<()> {
+        if !version.has_edge_stack() && !self.edge_stack.layers.is_empty() {
+            return Err(HxaError::EdgeStackUnsupported(version));
+        }
+
+        let vertex_count = self.vertex_stack.length()?.unwrap_or(0);
+        let corner_count = self.corner_stack.length()?;
+        let edge_count = self.edge_stack.length()?;
+        let edge_corner_count = match (corner_count, edge_count) {
+            (Some(corners), Some(edges)) if corners != edges => {
+                return Err(HxaError::LayerLengthMismatch {
+                    expected: corners,
+                    found: edges,
+                })
+            }
+            (corners, edges) => corners.or(edges).unwrap_or(0),
+        };
+        // Without any face layers the face count can still be recovered from
+        // the polygon terminators in the reference layer.
+        let face_count = match self.face_stack.length()? {
+            Some(face_count) => face_count,
+            None => match self.corner_stack.layers.first().map(|layer| &layer.data) {
+                Some(LayerData::Int32(references)) => references.iter().filter(|&&i| i < 0).count(),
+                _ => 0,
+            },
+        };
+
+        write_count(sink, vertex_count)?;
+        self.vertex_stack.write(sink, vertex_count)?;
+        write_count(sink, edge_corner_count)?;
+        self.corner_stack.write(sink, edge_corner_count)?;
+        if version.has_edge_stack() {
+            self.edge_stack.write(sink, edge_corner_count)?;
+        }
+        write_count(sink, face_count)?;
+        self.face_stack.write(sink, face_count)
+    }
+}
+
+impl NodeImage<'_> {
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        self.type_.write(sink)?;
+        for axis in &self.resolution[..self.type_.dimensions()] {
+            axis.write(sink)?;
+        }
+        self.image_stack.write(sink, self.pixel_count()?)
+    }
+}
+
+impl LayerStack<'_> {
+    /// Infers the number of elements in the stack from the first layer that
+    /// has any components, checking that all other layers agree with it.
+    fn length(&self) -> HxaResult<Option<usize>> {
+        let mut length = None;
+        for layer in &self.layers {
+            let found = layer.data.len();
+            let components = layer.component_count as usize;
+            if components == 0 || found % components != 0 {
+                if found == 0 {
+                    continue;
+                }
+                return Err(HxaError::LayerLengthMismatch {
+                    expected: length.map_or(0, |length| length * components),
+                    found,
+                });
+            }
+            match length {
+                None => length = Some(found / components),
+                Some(length) if length * components != found => {
+                    return Err(HxaError::LayerLengthMismatch {
+                        expected: length * components,
+                        found,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(length)
+    }
+
+    fn write<S: Sink>(&self, sink: &mut S, length: usize) -> HxaResult<()> {
+        write_count(sink, self.layers.len())?;
+        for layer in &self.layers {
+            layer.write(sink, length)?;
+        }
+        Ok(())
+    }
+}
+
+impl Layer<'_> {
+    fn write<S: Sink>(&self, sink: &mut S, length: usize) -> HxaResult<()> {
+        let expected = self.component_count as usize * length;
+        if self.data.len() != expected {
+            return Err(HxaError::LayerLengthMismatch {
+                expected,
+                found: self.data.len(),
+            });
+        }
+
+        write_name(sink, &self.name)?;
+        self.component_count.write(sink)?;
+        self.data.type_().write(sink)?;
+        match &self.data {
+            LayerData::Uint8(data) => sink.put(data),
+            LayerData::Int32(data) => write_slice(sink, data),
+            LayerData::Float(data) => write_slice(sink, data),
+            LayerData::Double(data) => write_slice(sink, data),
+        }
+    }
+}
+
+impl Meta<'_> {
+    fn write_all<S: Sink>(sink: &mut S, metadata: &[Self]) -> HxaResult<()> {
+        write_count(sink, metadata.len())?;
+        for meta in metadata {
+            meta.write(sink)?;
+        }
+        Ok(())
+    }
+
+    fn write<S: Sink>(&self, sink: &mut S) -> HxaResult<()> {
+        write_name(sink, &self.name)?;
+        self.value.type_().write(sink)?;
+        match &self.value {
+            MetaValue::Int64(data) => {
+                write_count(sink, data.len())?;
+                write_slice(sink, data)
+            }
+            MetaValue::Double(data) => {
+                write_count(sink, data.len())?;
+                write_slice(sink, data)
+            }
+            MetaValue::Node(data) => {
+                write_count(sink, data.len())?;
+                write_slice(sink, data)
+            }
+            MetaValue::Text(data) => {
+                write_count(sink, data.len())?;
+                sink.put(data.as_bytes())
+            }
+            MetaValue::Bin(data) => {
+                write_count(sink, data.len())?;
+                sink.put(data)
+            }
+            MetaValue::Meta(metadata) => Meta::write_all(sink, metadata),
+        }
+    }
+}
+
+fn write_count<S: Sink>(sink: &mut S, count: usize) -> HxaResult<()> {
+    u32::try_from(count)
+        .map_err(|_| HxaError::CountTooLarge(count))?
+        .write(sink)
+}
+
+fn write_name<S: Sink>(sink: &mut S, name: &str) -> HxaResult<()> {
+    let length = u8::try_from(name.len()).map_err(|_| HxaError::NameTooLong(name.len()))?;
+    length.write(sink)?;
+    sink.put(name.as_bytes())
+}
+
+fn write_slice<S: Sink, T: ToData>(sink: &mut S, data: &[T]) -> HxaResult<()> {
+    for value in data {
+        value.write(sink)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+    use alloc::format;
+    use alloc::vec;
+
+    use super::*;
+
+    const TEAPOT: &[u8] = include_bytes!("../examples/teapot.hxa");
+
+    fn layer(name: &'static str, component_count: u8, data: LayerData<'static>) -> Layer<'static> {
+        Layer {
+            name: Cow::Borrowed(name),
+            component_count,
+            type_: data.type_(),
+            data,
+        }
+    }
+
+    fn stack(layers: Vec<Layer<'static>>) -> LayerStack<'static> {
+        LayerStack { layers }
+    }
+
+    fn quad() -> Node<'static> {
+        let vertices = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        let uvs = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        Node {
+            type_: NodeType::Geometry,
+            metadata: vec![
+                Meta {
+                    name: Cow::Borrowed("name"),
+                    type_: MetadataType::Text,
+                    value: MetaValue::Text(Cow::Borrowed("quad")),
+                },
+                Meta {
+                    name: Cow::Borrowed("id"),
+                    type_: MetadataType::Int64,
+                    value: MetaValue::Int64(Cow::Owned(vec![7])),
+                },
+            ],
+            content: Some(NodeContent::Geometry(NodeGeometry {
+                vertex_stack: stack(vec![layer(
+                    "vertex",
+                    3,
+                    LayerData::Float(Cow::Owned(vertices)),
+                )]),
+                corner_stack: stack(vec![
+                    layer(
+                        "reference",
+                        1,
+                        LayerData::Int32(Cow::Owned(vec![0, 1, 2, -4])),
+                    ),
+                    layer("uv", 2, LayerData::Float(Cow::Owned(uvs))),
+                ]),
+                edge_stack: stack(Vec::new()),
+                face_stack: stack(vec![layer(
+                    "material",
+                    1,
+                    LayerData::Int32(Cow::Owned(vec![3])),
+                )]),
+            })),
+        }
+    }
+
+    fn geometry<'n>(node: &'n mut Node<'static>) -> &'n mut NodeGeometry<'static> {
+        match &mut node.content {
+            Some(NodeContent::Geometry(geometry)) => geometry,
+            _ => panic!("expected a geometry node"),
+        }
+    }
+
+    fn file(version: HxaVersion, nodes: Vec<Node<'static>>) -> Hxa<'static> {
+        Hxa { version, nodes }
+    }
+
+    #[test]
+    fn round_trips_the_teapot() {
+        let hxa = Hxa::new(TEAPOT).unwrap();
+        assert_eq!(hxa.to_bytes().unwrap(), TEAPOT);
+
+        let mut written = Vec::new();
+        hxa.write_to(&mut written).unwrap();
+        assert_eq!(written, TEAPOT);
+    }
+
+    #[test]
+    fn writes_older_versions_without_edge_stacks() {
+        let latest = file(HxaVersion::V3, vec![quad()]).to_bytes().unwrap();
+        for version in [HxaVersion::V1, HxaVersion::V2] {
+            let bytes = file(version, vec![quad()]).to_bytes().unwrap();
+            assert_eq!(bytes[4..8], (version as u32).to_le_bytes());
+            // Only the edge stack's layer count is missing.
+            assert_eq!(bytes.len(), latest.len() - 4);
+
+            let parsed = Hxa::new(&bytes).unwrap();
+            assert_eq!(parsed.version, version);
+            assert_eq!(parsed.to_bytes().unwrap(), bytes);
+            let reparsed = Hxa::new(&latest).unwrap();
+            assert_eq!(
+                format!("{:?}", parsed.nodes),
+                format!("{:?}", reparsed.nodes)
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_every_kind_of_content() {
+        let mut quad = quad();
+        quad.metadata.push(Meta {
+            name: Cow::Borrowed("nested"),
+            type_: MetadataType::Meta,
+            value: MetaValue::Meta(vec![Meta {
+                name: Cow::Borrowed("blob"),
+                type_: MetadataType::Binary,
+                value: MetaValue::Bin(Cow::Borrowed(&[1, 2, 3])),
+            }]),
+        });
+        let image = Node {
+            type_: NodeType::Image,
+            metadata: Vec::new(),
+            content: Some(NodeContent::Image(NodeImage {
+                type_: ImageType::Image2D,
+                resolution: [2, 3, 1],
+                image_stack: stack(vec![layer(
+                    "color",
+                    3,
+                    LayerData::Uint8(Cow::Owned(vec![7; 18])),
+                )]),
+            })),
+        };
+        let bytes = file(HxaVersion::V3, vec![quad, image]).to_bytes().unwrap();
+        let parsed = Hxa::new(&bytes).unwrap();
+        assert_eq!(parsed.nodes.len(), 2);
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn rejects_edge_layers_before_v3() {
+        let mut node = quad();
+        geometry(&mut node).edge_stack.layers.push(layer(
+            "crease",
+            1,
+            LayerData::Float(Cow::Owned(vec![0.0; 4])),
+        ));
+        assert!(file(HxaVersion::V3, vec![node.clone()]).to_bytes().is_ok());
+        for version in [HxaVersion::V1, HxaVersion::V2] {
+            match file(version, vec![node.clone()]).to_bytes() {
+                Err(HxaError::EdgeStackUnsupported(found)) => assert_eq!(found, version),
+                other => panic!("expected EdgeStackUnsupported, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_layers_of_the_wrong_length() {
+        let mut node = quad();
+        geometry(&mut node).vertex_stack.layers.push(layer(
+            "normal",
+            3,
+            LayerData::Float(Cow::Owned(vec![0.0; 9])),
+        ));
+        match file(HxaVersion::V3, vec![node]).to_bytes() {
+            Err(HxaError::LayerLengthMismatch { expected, found }) => {
+                assert_eq!((expected, found), (12, 9));
+            }
+            other => panic!("expected LayerLengthMismatch, got {:?}", other),
+        }
+
+        let mut node = quad();
+        geometry(&mut node).edge_stack.layers.push(layer(
+            "crease",
+            1,
+            LayerData::Float(Cow::Owned(vec![0.0; 3])),
+        ));
+        match file(HxaVersion::V3, vec![node]).to_bytes() {
+            Err(HxaError::LayerLengthMismatch { expected, found }) => {
+                assert_eq!((expected, found), (4, 3));
+            }
+            other => panic!("expected LayerLengthMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_long_names() {
+        let name = "n".repeat(256);
+        let mut node = quad();
+        node.metadata[0].name = Cow::Owned(name.clone());
+        match file(HxaVersion::V3, vec![node]).to_bytes() {
+            Err(HxaError::NameTooLong(256)) => {}
+            other => panic!("expected NameTooLong, got {:?}", other),
+        }
+
+        let mut node = quad();
+        geometry(&mut node).corner_stack.layers[1].name = Cow::Owned(name);
+        assert!(matches!(
+            file(HxaVersion::V3, vec![node]).to_bytes(),
+            Err(HxaError::NameTooLong(256))
+        ));
+
+        let mut node = quad();
+        node.metadata[0].name = Cow::Owned("n".repeat(255));
+        assert!(file(HxaVersion::V3, vec![node]).to_bytes().is_ok());
+    }
+
+    #[test]
+    fn rejects_content_that_does_not_match_the_node_type() {
+        let mut node = quad();
+        node.type_ = NodeType::Image;
+        assert!(matches!(
+            file(HxaVersion::V3, vec![node]).to_bytes(),
+            Err(HxaError::NodeContentMismatch(NodeType::Image))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reports_write_errors() {
+        struct Full;
+
+        impl io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::WriteZero))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        match file(HxaVersion::V3, vec![quad()]).write_to(Full) {
+            Err(HxaError::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::WriteZero),
+            other => panic!("expected Io, got {:?}", other),
+        }
+    }
+}